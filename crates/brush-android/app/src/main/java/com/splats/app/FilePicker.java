@@ -9,7 +9,9 @@ public class FilePicker {
     @SuppressLint("StaticFieldLeak")
     private static Activity _activity;
     public static final int REQUEST_CODE_PICK_FILE = 1;
+    public static final int REQUEST_CODE_PICK_FILES = 2;
     private static native void onFilePickerResult(int fd);
+    private static native void onFilesPickerResult(int[] fds, String[] names);
 
     public static void Register(Activity activity) {
         _activity = activity;
@@ -23,7 +25,19 @@ public class FilePicker {
         _activity.startActivityForResult(intent, REQUEST_CODE_PICK_FILE);
     }
 
+    public static void startFilesPicker() {
+        Intent intent = new Intent(Intent.ACTION_OPEN_DOCUMENT);
+        intent.addCategory(Intent.CATEGORY_OPENABLE);
+        intent.setType("*/*");
+        intent.putExtra(Intent.EXTRA_ALLOW_MULTIPLE, true);
+        _activity.startActivityForResult(intent, REQUEST_CODE_PICK_FILES);
+    }
+
     public static void onPicked(int resultCode, int fd) {
         onFilePickerResult(fd);
     }
+
+    public static void onPickedMultiple(int resultCode, int[] fds, String[] names) {
+        onFilesPickerResult(fds, names);
+    }
 }
@@ -6,14 +6,19 @@ import androidx.core.view.WindowInsetsControllerCompat;
 
 import com.google.androidgamesdk.GameActivity;
 
+import android.content.ClipData;
 import android.content.Intent;
+import android.database.Cursor;
 import android.net.Uri;
 import android.os.Bundle;
 import android.os.ParcelFileDescriptor;
+import android.provider.OpenableColumns;
 import android.view.View;
 import android.view.WindowManager;
 
 import java.io.IOException;
+import java.util.ArrayList;
+import java.util.List;
 
 public class MainActivity extends GameActivity {
     static {
@@ -59,9 +64,51 @@ public class MainActivity extends GameActivity {
                 FilePicker.onPicked(requestCode, fd);
             }
         }
+        if (requestCode == FilePicker.REQUEST_CODE_PICK_FILES) {
+            List<Uri> uris = new ArrayList<>();
+            if (data != null && data.getClipData() != null) {
+                ClipData clip = data.getClipData();
+                for (int i = 0; i < clip.getItemCount(); i++) {
+                    uris.add(clip.getItemAt(i).getUri());
+                }
+            } else if (data != null && data.getData() != null) {
+                uris.add(data.getData());
+            }
+
+            int[] fds = new int[uris.size()];
+            String[] names = new String[uris.size()];
+            for (int i = 0; i < uris.size(); i++) {
+                fds[i] = -1;
+                names[i] = displayName(uris.get(i), i);
+                try {
+                    ParcelFileDescriptor parcelFileDescriptor = getContentResolver()
+                            .openFileDescriptor(uris.get(i), "r");
+                    if (parcelFileDescriptor != null) {
+                        fds[i] = parcelFileDescriptor.detachFd();
+                    }
+                } catch (IOException ignored) {
+                }
+            }
+            FilePicker.onPickedMultiple(requestCode, fds, names);
+        }
         super.onActivityResult(requestCode, resultCode, data);
     }
 
+    // The file name shown in the picker, as content URIs don't contain it.
+    private String displayName(Uri uri, int index) {
+        try (Cursor cursor = getContentResolver().query(uri,
+                new String[]{OpenableColumns.DISPLAY_NAME}, null, null, null)) {
+            if (cursor != null && cursor.moveToFirst()) {
+                String name = cursor.getString(0);
+                if (name != null) {
+                    return name;
+                }
+            }
+        } catch (Exception ignored) {
+        }
+        return "file_" + index;
+    }
+
     @Override
     protected void onCreate(Bundle savedInstanceState) {
         super.onCreate(savedInstanceState);
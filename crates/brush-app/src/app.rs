@@ -7,7 +7,7 @@ use crate::panels::{DatasetPanel, PresetsPanel, ScenePanel, StatsPanel, TracingP
 use brush_dataset::Dataset;
 use brush_process::data_source::DataSource;
 use brush_process::process_loop::{
-    ControlMessage, ProcessArgs, ProcessMessage, RunningProcess, start_process,
+    ControlMessage, ProcessArgs, ProcessMessage, ProgressKind, RunningProcess, start_process,
 };
 use brush_render::camera::Camera;
 use brush_train::scene::SceneView;
@@ -121,6 +121,7 @@ pub struct AppContext {
 
     loading: bool,
     training: bool,
+    load_progress: Option<(ProgressKind, u64, Option<u64>)>,
 
     cam_settings: CameraSettings,
 
@@ -156,6 +157,7 @@ impl AppContext {
             view_aspect: None,
             loading: false,
             training: false,
+            load_progress: None,
             dataset: Dataset::empty(),
             running_process: None,
             cam_settings,
@@ -233,6 +235,34 @@ impl AppContext {
     pub fn loading(&self) -> bool {
         self.loading
     }
+
+    /// A progress bar for whatever is currently being loaded, if anything.
+    pub(crate) fn load_progress_bar(&self) -> Option<egui::ProgressBar> {
+        let (kind, done, total) = self.load_progress?;
+        let text = match (kind, total) {
+            (ProgressKind::Download, Some(total)) => format!(
+                "Downloading {:.1} / {:.1} MB",
+                done as f64 / 1e6,
+                total as f64 / 1e6
+            ),
+            (ProgressKind::Download, None) => format!("Downloading {:.1} MB", done as f64 / 1e6),
+            (ProgressKind::Images, _) => format!("{done} / {} images", total.unwrap_or(done)),
+            (ProgressKind::Splats, _) => format!("{done} / {} splats", total.unwrap_or(done)),
+        };
+        let fraction = total.map_or(0.0, |total| done as f32 / total.max(1) as f32);
+        Some(
+            egui::ProgressBar::new(fraction)
+                .text(text)
+                .animate(total.is_none()),
+        )
+    }
+
+    /// Whether the current splats are trained on linear colors, and need a display transform.
+    pub fn linear_space(&self) -> bool {
+        self.running_process
+            .as_ref()
+            .is_some_and(|p| p.start_args.train_config.linear_space)
+    }
 }
 
 pub struct AppCreateCb {
@@ -436,6 +466,13 @@ impl App {
                 }
                 ProcessMessage::DoneLoading { training: _ } => {
                     context.loading = false;
+                    context.load_progress = None;
+                }
+                ProcessMessage::Progress { kind, done, total } => {
+                    context.load_progress = Some((kind, done, total));
+                }
+                ProcessMessage::Error(_) => {
+                    context.load_progress = None;
                 }
                 _ => (),
             }
@@ -35,7 +35,26 @@ fn main() -> MainResult {
         runtime.block_on(async {
             env_logger::init();
 
-            if args.with_viewer {
+            if args.check {
+                let Some(source) = args.source else {
+                    panic!("Validation of args failed?");
+                };
+
+                let device = brush_render::burn_init_setup().await;
+                let report =
+                    brush_process::check::check_dataset(source, &args.process, &device).await;
+                if args.json {
+                    println!("{}", report.to_json());
+                } else {
+                    print!("{report}");
+                }
+
+                if !report.is_ok() {
+                    // Let scripts know the dataset has problems.
+                    #[allow(clippy::exit)]
+                    std::process::exit(1);
+                }
+            } else if args.with_viewer {
                 let icon = eframe::icon_data::from_png_bytes(
                     &include_bytes!("../../assets/icon-256.png")[..],
                 )
@@ -1,6 +1,6 @@
 use crate::app::{AppContext, AppPanel};
 use brush_process::process_loop::ProcessMessage;
-use brush_train::scene::{Scene, SceneView, ViewImageType, ViewType};
+use brush_train::scene::{Scene, SceneView, ViewImageType, ViewMetadata, ViewType};
 use egui::{Slider, TextureHandle, TextureOptions, pos2};
 
 struct SelectedView {
@@ -9,6 +9,32 @@ struct SelectedView {
     texture_handle: TextureHandle,
 }
 
+/// How a view was captured, if the dataset has anything on it. Times are shown relative to the
+/// first view of the scene.
+fn metadata_info(metadata: &ViewMetadata, start_time: Option<f64>) -> Option<String> {
+    let mut parts = vec![];
+    if let Some(id) = metadata.camera_id {
+        parts.push(format!("camera {id}"));
+    }
+    if let Some(time) = metadata.exposure_time {
+        parts.push(if time < 1.0 {
+            format!("1/{:.0}s", 1.0 / time)
+        } else {
+            format!("{time:.1}s")
+        });
+    }
+    if let Some(f_number) = metadata.f_number {
+        parts.push(format!("f/{f_number:.1}"));
+    }
+    if let Some(iso) = metadata.iso {
+        parts.push(format!("ISO {iso}"));
+    }
+    if let Some(time) = metadata.timestamp {
+        parts.push(format!("at {:.2}s", time - start_time.unwrap_or(time)));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
 fn selected_scene(t: ViewType, context: &AppContext) -> &Scene {
     if let Some(eval_scene) = context.dataset.eval.as_ref() {
         match t {
@@ -51,8 +77,9 @@ impl AppPanel for DatasetPanel {
                 *self = Self::new();
             }
             ProcessMessage::Dataset { data: d } => {
-                // Set train view to last loaded camera.
-                if let Some(view) = d.train.views.last() {
+                // Set train view to last loaded camera. Views added while training shouldn't
+                // move the camera away from where the user is looking.
+                if let Some(view) = d.train.views.last().filter(|_| context.loading()) {
                     context.focus_view(view);
                 }
                 context.dataset = d.clone();
@@ -79,13 +106,20 @@ impl AppPanel for DatasetPanel {
 
             if dirty {
                 let view = &pick_scene.views[*nearest];
-                let image = &view.image;
-                let img_size = [image.width() as usize, image.height() as usize];
-                let color_img = if image.color().has_alpha() {
-                    let data = image.to_rgba8().into_vec();
-                    egui::ColorImage::from_rgba_unmultiplied(img_size, &data)
-                } else {
-                    egui::ColorImage::from_rgb(img_size, &image.to_rgb8().into_vec())
+                let color_img = match view.image.load() {
+                    Ok(image) => {
+                        let img_size = [image.width() as usize, image.height() as usize];
+                        if image.color().has_alpha() {
+                            let data = image.to_rgba8().into_vec();
+                            egui::ColorImage::from_rgba_unmultiplied(img_size, &data)
+                        } else {
+                            egui::ColorImage::from_rgb(img_size, &image.to_rgb8().into_vec())
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Failed to load image {}: {e:?}", view.path);
+                        egui::ColorImage::new([1, 1], egui::Color32::BLACK)
+                    }
                 };
 
                 self.selected_view = Some(SelectedView {
@@ -178,7 +212,7 @@ impl AppPanel for DatasetPanel {
 
                     ui.add_space(10.0);
 
-                    let mask_info = if selected_view.image.color().has_alpha() {
+                    let mask_info = if selected_view.image.has_alpha() {
                         if selected_view.img_type == ViewImageType::Alpha {
                             "rgb + alpha transparency"
                         } else {
@@ -196,12 +230,28 @@ impl AppPanel for DatasetPanel {
                         mask_info
                     );
                     ui.label(info);
+
+                    let start_time = pick_scene
+                        .views
+                        .iter()
+                        .filter_map(|v| v.metadata.timestamp)
+                        .reduce(f64::min);
+                    if let Some(info) = metadata_info(&selected_view.metadata, start_time) {
+                        ui.label(info);
+                    }
                 });
             }
         }
 
         if context.loading() && context.training() {
-            ui.label("Loading...");
+            match context.load_progress_bar() {
+                Some(progress) => {
+                    ui.add(progress);
+                }
+                None => {
+                    ui.label("Loading...");
+                }
+            }
         }
     }
 
@@ -1,6 +1,10 @@
-use brush_dataset::splat_export;
+use brush_dataset::{DatasetError, splat_export};
 use brush_process::process_loop::{ControlMessage, ProcessMessage};
-use brush_train::{scene::ViewImageType, train::TrainBack};
+use brush_train::{
+    image::{bake_display_colors, linear_to_display},
+    scene::ViewImageType,
+    train::TrainBack,
+};
 use brush_ui::burn_texture::BurnTexture;
 use burn::tensor::backend::AutodiffBackend;
 use core::f32;
@@ -27,11 +31,51 @@ struct RenderState {
     cam_rot: Quat,
 
     frame: f32,
+    exposure: Option<f32>,
 }
 
 struct ErrorDisplay {
     headline: String,
     context: Vec<String>,
+    hints: Vec<String>,
+}
+
+/// Suggestions on how to fix a dataset that failed to load.
+fn dataset_error_hints(err: &DatasetError) -> Vec<String> {
+    match err {
+        DatasetError::NotFound { format, .. } => vec![format!(
+            "Check that all {format} files are included, and not inside another archive."
+        )],
+        DatasetError::InvalidFile { path, .. } => vec![format!(
+            "{} might be damaged or written by an unsupported version, try exporting it again.",
+            path.display()
+        )],
+        DatasetError::UnsupportedCameraModel { model, .. } => vec![
+            format!("Brush can't use {model} cameras."),
+            "Undistort the images first, eg. with `colmap image_undistorter`, which writes PINHOLE cameras.".to_owned(),
+        ],
+        DatasetError::MissingImage { path, .. } => vec![
+            format!("Make sure {} is included with the dataset.", path.display()),
+            "Image names are case sensitive, and need to match the names in the camera data."
+                .to_owned(),
+        ],
+        DatasetError::InvalidImage { path, .. } => vec![
+            format!("Check that {} opens in an image viewer.", path.display()),
+            "Brush reads PNG, JPEG, WebP, TIFF, EXR and HDR images.".to_owned(),
+        ],
+        DatasetError::Other { format, .. } => {
+            vec![format!("Check that the data is a complete {format} export.")]
+        }
+        DatasetError::InvalidEvalSplit { .. } => vec![
+            "Use only one eval split option, and list one image name per line in a split file."
+                .to_owned(),
+        ],
+        DatasetError::UnknownFormat { .. } => vec![
+            "Datasets need a nerfstudio transforms.json, COLMAP sparse folder, Metashape cameras XML, Bundler .out file, OpenMVG/AliceVision sfm file, or a Polycam/Record3D export.".to_owned(),
+            "If the data is in a zip or tar file, make sure it isn't inside another archive."
+                .to_owned(),
+        ],
+    }
 }
 
 pub(crate) struct ScenePanel {
@@ -41,6 +85,7 @@ pub(crate) struct ScenePanel {
     view_splats: Vec<Splats<<TrainBack as AutodiffBackend>::InnerBackend>>,
     frame_count: u32,
     frame: f32,
+    exposure: f32,
 
     // Ui state.
     live_update: bool,
@@ -70,6 +115,7 @@ impl ScenePanel {
             zen,
             frame_count: 0,
             frame: 0.0,
+            exposure: 0.0,
         }
     }
 
@@ -119,6 +165,7 @@ impl ScenePanel {
             cam_pos: camera.position,
             cam_rot: camera.rotation,
             frame: self.frame,
+            exposure: context.linear_space().then_some(self.exposure),
         };
 
         let dirty = self.last_state != Some(state);
@@ -134,6 +181,10 @@ impl ScenePanel {
         if size.x > 0 && size.y > 0 && dirty {
             let _span = trace_span!("Render splats").entered();
             let (img, _) = splats.render(&context.camera, size, false);
+            let img = match state.exposure {
+                Some(exposure) => linear_to_display(img, exposure),
+                None => img,
+            };
             self.backbuffer.update_texture(img);
         }
 
@@ -141,7 +192,7 @@ impl ScenePanel {
             ui.scope(|ui| {
                 let mut background = false;
                 if let Some(view) = context.dataset.train.views.first() {
-                    if view.image.color().has_alpha() && view.img_type == ViewImageType::Alpha {
+                    if view.image.has_alpha() && view.img_type == ViewImageType::Alpha {
                         background = true;
                         // if training views have alpha, show a background checker. Masked images
                         // should still use a black background.
@@ -216,9 +267,24 @@ impl AppPanel for ScenePanel {
                 }
             }
             ProcessMessage::Error(e) => {
-                let headline = e.to_string();
-                let context = e.chain().skip(1).map(|cause| format!("{cause}")).collect();
-                self.err = Some(ErrorDisplay { headline, context });
+                let mut headline = e.to_string();
+                let mut context: Vec<_> =
+                    e.chain().skip(1).map(|cause| format!("{cause}")).collect();
+                let dataset_err = DatasetError::find_in(e);
+                // List why each format didn't match on their own lines, instead of as part of
+                // the message.
+                if let Some(DatasetError::UnknownFormat { attempts }) = dataset_err {
+                    let first_line = |s: &str| s.lines().next().unwrap_or_default().to_owned();
+                    headline = first_line(&headline);
+                    context = context.iter().map(|c| first_line(c)).collect();
+                    context.extend(attempts.iter().map(ToString::to_string));
+                }
+                let hints = dataset_err.map(dataset_error_hints).unwrap_or_default();
+                self.err = Some(ErrorDisplay {
+                    headline,
+                    context,
+                    hints,
+                });
             }
             _ => {}
         }
@@ -229,6 +295,16 @@ impl AppPanel for ScenePanel {
 
         self.last_draw = Some(cur_time);
 
+        // Nothing to show yet, but something is on its way.
+        if self.view_splats.is_empty() && self.err.is_none() {
+            if let Some(progress) = context.load_progress_bar() {
+                ui.heading("Loading...");
+                ui.add_space(5.0);
+                ui.add(progress.desired_width(300.0));
+                return;
+            }
+        }
+
         // Empty scene, nothing to show.
         if !context.training() && self.view_splats.is_empty() && self.err.is_none() && !self.zen {
             ui.heading("Load a ply file or dataset to get started.");
@@ -237,7 +313,7 @@ impl AppPanel for ScenePanel {
                 r#"
 Load a pretrained .ply file to view it
 
-Or load a dataset to train on. These are zip files with:
+Or load a dataset to train on. These are zip or tar files with:
     - a transforms.json and images, like the nerfstudio dataset format.
     - COLMAP data, containing the `images` & `sparse` folder."#,
             );
@@ -280,6 +356,14 @@ For bigger training runs consider using the native app."#,
                     ui.add_space(2.0);
                 }
             });
+
+            if !err.hints.is_empty() {
+                ui.add_space(10.0);
+                for hint in &err.hints {
+                    ui.label(format!("💡 {hint}"));
+                    ui.add_space(2.0);
+                }
+            }
         } else if !self.view_splats.is_empty() {
             const FPS: f32 = 24.0;
 
@@ -314,7 +398,14 @@ For bigger training runs consider using the native app."#,
                 if context.loading() {
                     ui.horizontal(|ui| {
                         ui.label("Loading... Please wait.");
-                        ui.spinner();
+                        match context.load_progress_bar() {
+                            Some(progress) => {
+                                ui.add(progress.desired_width(200.0));
+                            }
+                            None => {
+                                ui.spinner();
+                            }
+                        }
                     });
                 }
 
@@ -346,8 +437,23 @@ For bigger training runs consider using the native app."#,
 
                     ui.add_space(15.0);
 
+                    if context.linear_space() {
+                        ui.label("Exposure");
+                        ui.add(
+                            egui::Slider::new(&mut self.exposure, -8.0..=8.0)
+                                .suffix(" stops")
+                                .clamping(egui::SliderClamping::Never),
+                        );
+                    }
+
+                    ui.add_space(15.0);
+
                     if ui.button("⬆ Export").clicked() {
-                        let splats = splats.clone();
+                        let splats = if context.linear_space() {
+                            bake_display_colors(splats.clone(), self.exposure)
+                        } else {
+                            splats.clone()
+                        };
 
                         let fut = async move {
                             let file = rrfd::save_file("export.ply").await;
@@ -1,5 +1,7 @@
 use crate::app::{AppContext, AppPanel};
-use brush_dataset::{LoadDataseConfig, ModelConfig};
+use brush_dataset::{
+    CompressionConfig, LoadDataseConfig, ModelConfig, scene_normalization::SceneCenter,
+};
 use brush_process::{
     data_source::DataSource,
     process_loop::{ProcessArgs, ProcessConfig, RerunConfig, start_process},
@@ -7,6 +9,13 @@ use brush_process::{
 use brush_train::train::TrainConfig;
 use egui::Slider;
 
+fn clear_eval_split(load: &mut LoadDataseConfig) {
+    load.eval_split_every = None;
+    load.eval_split_percent = None;
+    load.eval_split_glob = None;
+    load.eval_split_file = None;
+}
+
 pub(crate) struct SettingsPanel {
     args: ProcessArgs,
     url: String,
@@ -22,6 +31,7 @@ impl SettingsPanel {
                 LoadDataseConfig::new(),
                 ProcessConfig::new(),
                 RerunConfig::new(),
+                CompressionConfig::new(),
             ),
             url: "splat.com/example.ply".to_owned(),
         }
@@ -64,16 +74,47 @@ impl AppPanel for SettingsPanel {
                 ui.add(Slider::new(max_frames, 1..=256).clamping(egui::SliderClamping::Never));
             }
 
-            let mut use_eval_split = self.args.load_config.eval_split_every.is_some();
+            let load = &mut self.args.load_config;
+            let mut use_eval_split = load.eval_split_every.is_some()
+                || load.eval_split_percent.is_some()
+                || load.eval_split_glob.is_some()
+                || load.eval_split_file.is_some();
             if ui
                 .checkbox(&mut use_eval_split, "Split dataset for evaluation")
+                .on_hover_text("Without a split, the eval views of the dataset are used if any")
                 .clicked()
             {
-                self.args.load_config.eval_split_every =
-                    if use_eval_split { Some(8) } else { None };
+                clear_eval_split(load);
+                load.eval_split_every = use_eval_split.then_some(8);
             }
 
-            if let Some(eval_split) = self.args.load_config.eval_split_every.as_mut() {
+            if use_eval_split {
+                ui.horizontal(|ui| {
+                    if ui
+                        .radio(load.eval_split_every.is_some(), "Every nth")
+                        .clicked()
+                    {
+                        clear_eval_split(load);
+                        load.eval_split_every = Some(8);
+                    }
+                    if ui
+                        .radio(load.eval_split_percent.is_some(), "Random")
+                        .clicked()
+                    {
+                        clear_eval_split(load);
+                        load.eval_split_percent = Some(10.0);
+                    }
+                    if ui
+                        .radio(load.eval_split_glob.is_some(), "Pattern")
+                        .clicked()
+                    {
+                        clear_eval_split(load);
+                        load.eval_split_glob = Some("*_eval*".to_owned());
+                    }
+                });
+            }
+
+            if let Some(eval_split) = load.eval_split_every.as_mut() {
                 ui.add(
                     Slider::new(eval_split, 2..=32)
                         .clamping(egui::SliderClamping::Never)
@@ -82,6 +123,33 @@ impl AppPanel for SettingsPanel {
                 );
             }
 
+            if let Some(percent) = load.eval_split_percent.as_mut() {
+                ui.add(Slider::new(percent, 1.0..=50.0).suffix("% of frames"));
+                ui.add(Slider::new(&mut load.eval_split_seed, 0..=100).text("Seed"));
+            }
+
+            if let Some(pattern) = load.eval_split_glob.as_mut() {
+                ui.text_edit_singleline(pattern)
+                    .on_hover_text("Frames with an image name matching this pattern");
+            }
+
+            if let Some(file) = &load.eval_split_file {
+                ui.label(format!("Frames listed in {file}"));
+            }
+
+            let mut normalize = self.args.load_config.normalize_scene.is_some();
+            if ui.checkbox(&mut normalize, "Normalize scene").clicked() {
+                self.args.load_config.normalize_scene = normalize.then_some(SceneCenter::Cameras);
+            }
+
+            if let Some(center) = self.args.load_config.normalize_scene.as_mut() {
+                ui.horizontal(|ui| {
+                    ui.label("Center on");
+                    ui.radio_value(center, SceneCenter::Cameras, "Cameras");
+                    ui.radio_value(center, SceneCenter::Points, "Points");
+                });
+            }
+
             ui.heading("Training Settings");
 
             ui.horizontal(|ui| {
@@ -94,8 +162,19 @@ impl AppPanel for SettingsPanel {
                 );
             });
 
+            ui.checkbox(
+                &mut self.args.train_config.linear_space,
+                "Train in linear color space (for HDR images)",
+            );
+
             ui.heading("Process Settings");
 
+            ui.checkbox(
+                &mut self.args.process_config.train_while_loading,
+                "Start training while the dataset is loading",
+            )
+            .on_hover_text("Views that are still loading are added while training");
+
             ui.horizontal(|ui| {
                 ui.label("Evaluate");
                 ui.add(
@@ -117,6 +196,26 @@ impl AppPanel for SettingsPanel {
                             .suffix(" steps"),
                     );
                 });
+
+                let compression = &mut self.args.compression_config;
+                ui.checkbox(
+                    &mut compression.export_sh_codebook,
+                    "Compress SH in exports",
+                );
+                if compression.export_sh_codebook {
+                    ui.horizontal(|ui| {
+                        ui.label("Codebook size");
+                        ui.add(
+                            egui::Slider::new(&mut compression.sh_codebook_size, 256..=65536)
+                                .logarithmic(true)
+                                .clamping(egui::SliderClamping::Never),
+                        );
+                    });
+                    ui.checkbox(
+                        &mut compression.quantize_attributes,
+                        "Quantize other attributes",
+                    );
+                }
             }
 
             #[cfg(all(not(target_family = "wasm"), not(target_os = "android")))]
@@ -168,9 +267,10 @@ impl AppPanel for SettingsPanel {
 
             ui.add_space(20.0);
 
-            ui.label("Select a .ply to visualize, or a .zip with training data.");
+            ui.label("Select a .ply to visualize, or a .zip or .tar with training data.");
 
             let file = ui.button("Load file").clicked();
+            let files = ui.button("Load files").clicked();
 
             let can_pick_dir = !cfg!(target_family = "wasm") && !cfg!(target_os = "android");
             let dir = can_pick_dir && ui.button("Load directory").clicked();
@@ -182,9 +282,11 @@ impl AppPanel for SettingsPanel {
 
             ui.add_space(10.0);
 
-            if file || dir || url {
+            if file || files || dir || url {
                 let source = if file {
                     DataSource::PickFile
+                } else if files {
+                    DataSource::PickFiles
                 } else if dir {
                     DataSource::PickDirectory
                 } else {
@@ -13,6 +13,7 @@ pub(crate) struct StatsPanel {
     last_train_step: (Instant, u32),
     train_iter_per_s: f32,
     last_eval: Option<String>,
+    last_compression: Option<String>,
     cur_sh_degree: u32,
 
     training_started: bool,
@@ -30,6 +31,7 @@ impl StatsPanel {
             last_train_step: (Instant::now(), 0),
             train_iter_per_s: 0.0,
             last_eval: None,
+            last_compression: None,
             training_started: false,
             num_splats: 0,
             frames: 0,
@@ -77,6 +79,7 @@ impl AppPanel for StatsPanel {
                 self.num_splats = 0;
                 self.cur_sh_degree = 0;
                 self.last_eval = None;
+                self.last_compression = None;
                 self.training_started = *training;
             }
             ProcessMessage::ViewSplats {
@@ -109,6 +112,16 @@ impl AppPanel for StatsPanel {
             } => {
                 self.last_eval = Some(format!("{avg_psnr:.2} PSNR, {avg_ssim:.3} SSIM"));
             }
+            ProcessMessage::CompressedExport {
+                iter: _,
+                codebook_size,
+                psnr_drop,
+            } => {
+                self.last_compression = Some(match psnr_drop {
+                    Some(drop) => format!("{codebook_size} entries, -{drop:.3} PSNR"),
+                    None => format!("{codebook_size} entries"),
+                });
+            }
             _ => {}
         }
     }
@@ -150,6 +163,12 @@ impl AppPanel for StatsPanel {
                     });
                     ui.end_row();
 
+                    if let Some(compression) = self.last_compression.as_ref() {
+                        ui.label("SH codebook:");
+                        ui.label(compression);
+                        ui.end_row();
+                    }
+
                     ui.label("Training time");
                     // Round duration to seconds.
                     let elapsed = Duration::from_secs(self.start_load_time.elapsed().as_secs());
@@ -25,12 +25,28 @@ pub struct Cli {
     )]
     pub with_viewer: bool,
 
+    #[arg(long, help = "Load the dataset and report on it, without training")]
+    pub check: bool,
+
+    #[arg(
+        long,
+        requires = "check",
+        help = "Print the dataset check report as JSON"
+    )]
+    pub json: bool,
+
     #[clap(flatten)]
     pub process: ProcessArgs,
 }
 
 impl Cli {
     pub fn validate(self) -> Result<Self, Error> {
+        if self.check && self.source.is_none() {
+            return Err(Error::raw(
+                ErrorKind::MissingRequiredArgument,
+                "--check needs a source to check",
+            ));
+        }
         if !self.with_viewer && self.source.is_none() {
             return Err(Error::raw(
                 ErrorKind::MissingRequiredArgument,
@@ -1,8 +1,26 @@
 use std::time::Duration;
 
-use brush_process::process_loop::{ProcessMessage, RunningProcess};
+use brush_process::process_loop::{ProcessMessage, ProgressKind, RunningProcess};
 use indicatif::{ProgressBar, ProgressStyle};
 
+fn load_progress_bar(kind: ProgressKind, total: Option<u64>) -> ProgressBar {
+    let (template, message) = match kind {
+        ProgressKind::Download => (
+            "{bar:40.cyan/blue} {bytes:>10}/{total_bytes:10} {msg} ({bytes_per_sec})",
+            "Downloading",
+        ),
+        ProgressKind::Images => ("{bar:40.cyan/blue} {pos:>7}/{len:7} {msg}", "Images"),
+        ProgressKind::Splats => ("{bar:40.cyan/blue} {pos:>10}/{len:10} {msg}", "Splats"),
+    };
+    let bar = total.map_or_else(ProgressBar::no_length, ProgressBar::new);
+    bar.with_style(
+        ProgressStyle::with_template(template)
+            .expect("Invalid indicatif config")
+            .progress_chars("◍○○"),
+    )
+    .with_message(message)
+}
+
 pub async fn process_ui(process: RunningProcess) {
     let mut process = process;
 
@@ -75,6 +93,9 @@ pub async fn process_ui(process: RunningProcess) {
             sp.println("ℹ️  running in debug mode, compile with --release for best performance");
     }
 
+    // Shown while loading, for whatever is currently being loaded.
+    let mut load_progress: Option<(ProgressKind, ProgressBar)> = None;
+
     while let Some(msg) = process.messages.recv().await {
         match msg {
             ProcessMessage::NewSource => {
@@ -110,8 +131,26 @@ pub async fn process_ui(process: RunningProcess) {
                     ));
                 }
             }
+            ProcessMessage::Progress { kind, done, total } => {
+                if load_progress.as_ref().is_none_or(|(k, _)| *k != kind) {
+                    if let Some((_, bar)) = load_progress.take() {
+                        bar.finish_and_clear();
+                    }
+                    let bar = sp.insert_after(&main_spinner, load_progress_bar(kind, total));
+                    load_progress = Some((kind, bar));
+                }
+                if let Some((_, bar)) = &load_progress {
+                    if let Some(total) = total {
+                        bar.set_length(total);
+                    }
+                    bar.set_position(done);
+                }
+            }
             ProcessMessage::DoneLoading { .. } => {
                 main_spinner.set_message("Dataset loaded");
+                if let Some((_, bar)) = load_progress.take() {
+                    bar.finish_and_clear();
+                }
             }
             ProcessMessage::TrainStep {
                 splats,
@@ -137,6 +176,20 @@ pub async fn process_ui(process: RunningProcess) {
                 ));
                 // Show eval results.
             }
+            ProcessMessage::CompressedExport {
+                iter,
+                codebook_size,
+                psnr_drop,
+            } => {
+                let _ = sp.println(match psnr_drop {
+                    Some(drop) => format!(
+                        "ℹ️  Compressed export iter {iter}: {codebook_size} SH entries, PSNR -{drop:.3}"
+                    ),
+                    None => format!(
+                        "ℹ️  Compressed export iter {iter}: {codebook_size} SH entries"
+                    ),
+                });
+            }
         }
     }
 }
@@ -5,16 +5,18 @@
 // The reason is that picking directories isn't supported on
 // rfd on wasm, nor is drag-and-dropping folders in egui.
 use std::{
-    collections::HashMap,
-    io::{Cursor, Read},
+    collections::{BTreeMap, HashMap},
+    io::{Cursor, Read, Seek, SeekFrom},
+    ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use anyhow::Context;
+use async_compression::tokio::bufread::GzipDecoder;
 use path_clean::PathClean;
 use tokio::{
-    io::{AsyncRead, AsyncReadExt},
+    io::{AsyncRead, AsyncReadExt, BufReader},
     sync::Mutex,
 };
 
@@ -32,16 +34,148 @@ impl<T: AsyncRead + WasmNotSend + Unpin> DynRead for T {}
 type SharedRead = Arc<Mutex<Option<Box<dyn DynRead>>>>;
 
 #[derive(Clone)]
-pub struct ZipData {
+pub struct ArchiveData {
     data: Arc<Vec<u8>>,
 }
 
-impl AsRef<[u8]> for ZipData {
+impl AsRef<[u8]> for ArchiveData {
     fn as_ref(&self) -> &[u8] {
         &self.data
     }
 }
 
+/// A file on disk, read with positional reads so clones can read independently.
+#[cfg(not(target_family = "wasm"))]
+#[derive(Clone)]
+pub struct DiskFile {
+    file: Arc<std::fs::File>,
+    pos: u64,
+    len: u64,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl DiskFile {
+    fn new(file: std::fs::File) -> std::io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file: Arc::new(file),
+            pos: 0,
+            len,
+        })
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Read for DiskFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let read = std::os::unix::fs::FileExt::read_at(&*self.file, buf, self.pos)?;
+        #[cfg(windows)]
+        let read = std::os::windows::fs::FileExt::seek_read(&*self.file, buf, self.pos)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Seek for DiskFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or(std::io::ErrorKind::InvalidInput)?;
+        Ok(self.pos)
+    }
+}
+
+/// The data of an archive, either in memory or read lazily from disk.
+#[derive(Clone)]
+pub enum ArchiveReader {
+    Memory(Cursor<ArchiveData>),
+    #[cfg(not(target_family = "wasm"))]
+    Disk(DiskFile),
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Memory(cursor) => Read::read(cursor, buf),
+            #[cfg(not(target_family = "wasm"))]
+            Self::Disk(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for ArchiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::Memory(cursor) => cursor.seek(pos),
+            #[cfg(not(target_family = "wasm"))]
+            Self::Disk(file) => file.seek(pos),
+        }
+    }
+}
+
+/// Copy a stream to an anonymous temporary file, which is removed once closed.
+#[cfg(not(target_family = "wasm"))]
+async fn spool_to_disk(
+    file: std::fs::File,
+    mut reader: impl AsyncRead + Unpin,
+) -> std::io::Result<std::fs::File> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = tokio::fs::File::from_std(file);
+    tokio::io::copy(&mut reader, &mut file).await?;
+    // Make sure all writes finished before reading the file again.
+    file.flush().await?;
+    Ok(file.into_std().await)
+}
+
+/// An uncompressed tar archive, with the location of each file in it.
+#[derive(Clone)]
+pub struct TarArchive {
+    data: ArchiveReader,
+    files: Arc<BTreeMap<PathBuf, Range<u64>>>,
+}
+
+impl TarArchive {
+    fn new(mut data: ArchiveReader) -> anyhow::Result<Self> {
+        let len = data.seek(SeekFrom::End(0))?;
+        data.rewind()?;
+
+        let mut files = BTreeMap::new();
+        let mut archive = tar::Archive::new(data.clone());
+
+        for entry in archive.entries_with_seek().context("Invalid tar archive")? {
+            let entry = entry.context("Invalid tar entry")?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let start = entry.raw_file_position();
+            let range = start..start + entry.size();
+            anyhow::ensure!(range.end <= len, "Truncated tar archive");
+            // Entries are often stored with a leading `./`.
+            files.insert(entry.path()?.clean(), range);
+        }
+
+        Ok(Self {
+            data,
+            files: Arc::new(files),
+        })
+    }
+
+    fn read(&self, path: &Path, max_len: u64) -> anyhow::Result<Vec<u8>> {
+        let range = self.files.get(path).context("File not found")?;
+        let mut data = self.data.clone();
+        data.seek(SeekFrom::Start(range.start))?;
+        let mut buffer = vec![0; (range.end - range.start).min(max_len) as usize];
+        data.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct PathReader {
     paths: HashMap<PathBuf, SharedRead>,
@@ -67,25 +201,157 @@ impl PathReader {
     }
 }
 
+/// A zip archive, with the index of each file by its normalized path.
+#[derive(Clone)]
+pub struct ZipFiles {
+    archive: ZipArchive<ArchiveReader>,
+    files: Arc<HashMap<PathBuf, usize>>,
+}
+
+impl ZipFiles {
+    fn new(archive: ZipArchive<ArchiveReader>) -> Self {
+        let files = (0..archive.len())
+            .filter_map(|i| Some((Path::new(archive.name_for_index(i)?).clean(), i)))
+            .collect();
+        Self {
+            archive,
+            files: Arc::new(files),
+        }
+    }
+
+    fn read(&self, path: &Path, max_len: u64) -> anyhow::Result<Vec<u8>> {
+        let index = *self.files.get(path).ok_or(ZipError::FileNotFound)?;
+        let mut buffer = vec![];
+        self.archive
+            .clone()
+            .by_index(index)?
+            .take(max_len)
+            .read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Reads files from a VFS again later without async IO, eg. to lazily load images.
+#[derive(Clone)]
+pub(crate) enum BlockingReader {
+    Zip(ZipFiles),
+    Tar(TarArchive),
+    #[cfg(not(target_family = "wasm"))]
+    Directory(PathBuf),
+}
+
+impl BlockingReader {
+    pub(crate) fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        self.read_start(path, u64::MAX)
+    }
+
+    /// Read at most the first `max_len` bytes of a file.
+    pub(crate) fn read_start(&self, path: &Path, max_len: u64) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Zip(archive) => archive.read(path, max_len),
+            Self::Tar(archive) => archive.read(path, max_len),
+            #[cfg(not(target_family = "wasm"))]
+            Self::Directory(dir) => {
+                let mut buffer = vec![];
+                std::fs::File::open(dir.join(path))?
+                    .take(max_len)
+                    .read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+        }
+    }
+
+    /// Like [`Self::read_start`], but without blocking the async executor. On wasm there are
+    /// no threads to move the read to, so it's done inline.
+    pub(crate) async fn read_start_async(
+        &self,
+        path: &Path,
+        max_len: u64,
+    ) -> anyhow::Result<Vec<u8>> {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let reader = self.clone();
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || reader.read_start(&path, max_len))
+                .await
+                .context("File reading task failed")?
+        }
+        #[cfg(target_family = "wasm")]
+        self.read_start(path, max_len)
+    }
+}
+
 #[derive(Clone)]
 pub enum BrushVfs {
-    Zip(ZipArchive<Cursor<ZipData>>),
+    Zip(ZipFiles),
+    Tar(TarArchive),
     Manual(PathReader),
     #[cfg(not(target_family = "wasm"))]
     Directory(PathBuf, Vec<PathBuf>),
 }
 
 impl BrushVfs {
+    /// Read a zip archive from a stream. On native the stream is spooled to a temporary file,
+    /// so large archives don't have to fit in memory.
     pub async fn from_zip_reader(reader: impl AsyncRead + Unpin) -> ZipResult<Self> {
+        #[cfg(not(target_family = "wasm"))]
+        match tempfile::tempfile() {
+            Ok(file) => return Self::from_zip_file(spool_to_disk(file, reader).await?),
+            Err(e) => log::warn!("Failed to create temporary file, reading zip in memory: {e}"),
+        }
+
         let mut bytes = vec![];
         let mut reader = reader;
         reader.read_to_end(&mut bytes).await?;
 
-        let zip_data = ZipData {
+        let zip_data = ArchiveData {
             data: Arc::new(bytes),
         };
-        let archive = ZipArchive::new(Cursor::new(zip_data))?;
-        Ok(Self::Zip(archive))
+        let archive = ZipArchive::new(ArchiveReader::Memory(Cursor::new(zip_data)))?;
+        Ok(Self::Zip(ZipFiles::new(archive)))
+    }
+
+    /// Read a zip archive from disk. Only the index is read upfront, files are read from disk
+    /// when opened.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn from_zip_file(file: std::fs::File) -> ZipResult<Self> {
+        let archive = ZipArchive::new(ArchiveReader::Disk(DiskFile::new(file)?))?;
+        Ok(Self::Zip(ZipFiles::new(archive)))
+    }
+
+    /// Read a tar archive, optionally gzip compressed. Like zip archives, on native the
+    /// (decompressed) stream is spooled to a temporary file.
+    pub async fn from_tar_reader(
+        reader: impl AsyncRead + Unpin,
+        gzipped: bool,
+    ) -> anyhow::Result<Self> {
+        let mut reader: Box<dyn AsyncRead + Unpin + '_> = if gzipped {
+            let mut decoder = GzipDecoder::new(BufReader::new(reader));
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        } else {
+            Box::new(reader)
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        match tempfile::tempfile() {
+            Ok(file) => return Self::from_tar_file(spool_to_disk(file, reader).await?),
+            Err(e) => log::warn!("Failed to create temporary file, reading tar in memory: {e}"),
+        }
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        let data = ArchiveReader::Memory(Cursor::new(ArchiveData {
+            data: Arc::new(bytes),
+        }));
+        Ok(Self::Tar(TarArchive::new(data)?))
+    }
+
+    /// Read an uncompressed tar archive from disk. Only the file locations are read upfront.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn from_tar_file(file: std::fs::File) -> anyhow::Result<Self> {
+        let data = ArchiveReader::Disk(DiskFile::new(file)?);
+        Ok(Self::Tar(TarArchive::new(data)?))
     }
 
     pub fn from_paths(paths: PathReader) -> Self {
@@ -96,13 +362,20 @@ impl BrushVfs {
         #[cfg(not(target_family = "wasm"))]
         {
             if dir.is_file() {
-                let file = tokio::fs::File::open(dir).await?;
-                if dir.extension().is_some_and(|e| e == "zip") {
-                    Ok(Self::from_zip_reader(file).await?)
+                let name = dir
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                if name.ends_with(".zip") {
+                    Ok(Self::from_zip_file(std::fs::File::open(dir)?)?)
+                } else if name.ends_with(".tar") {
+                    Self::from_tar_file(std::fs::File::open(dir)?)
+                } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+                    Self::from_tar_reader(tokio::fs::File::open(dir).await?, true).await
                 } else {
                     // Make a VFS with just this file.
                     let mut paths = PathReader::default();
-                    paths.add(dir, file);
+                    paths.add(dir, tokio::fs::File::open(dir).await?);
                     Ok(Self::from_paths(paths))
                 }
             } else {
@@ -144,7 +417,8 @@ impl BrushVfs {
 
     pub fn file_names(&self) -> impl Iterator<Item = PathBuf> + '_ {
         let iterator: Box<dyn Iterator<Item = &Path>> = match self {
-            Self::Zip(archive) => Box::new(archive.file_names().map(Path::new)),
+            Self::Zip(archive) => Box::new(archive.archive.file_names().map(Path::new)),
+            Self::Tar(archive) => Box::new(archive.files.keys().map(|p| p.as_path())),
             Self::Manual(map) => Box::new(map.paths().map(|p| p.as_path())),
             #[cfg(not(target_family = "wasm"))]
             Self::Directory(_, paths) => Box::new(paths.iter().map(|p| p.as_path())),
@@ -159,17 +433,29 @@ impl BrushVfs {
         })
     }
 
+    /// A reader to read files again later, if this VFS supports it. Manually added readers
+    /// can only be read once.
+    pub(crate) fn blocking_reader(&self) -> Option<BlockingReader> {
+        match self {
+            Self::Zip(archive) => Some(BlockingReader::Zip(archive.clone())),
+            Self::Tar(archive) => Some(BlockingReader::Tar(archive.clone())),
+            Self::Manual(_) => None,
+            #[cfg(not(target_family = "wasm"))]
+            Self::Directory(dir, _) => Some(BlockingReader::Directory(dir.clone())),
+        }
+    }
+
     pub async fn open_path(&mut self, path: &Path) -> anyhow::Result<Box<dyn DynRead>> {
         match self {
             Self::Zip(archive) => {
-                let name = archive
-                    .file_names()
-                    .find(|name| path == Path::new(name))
-                    .ok_or(ZipError::FileNotFound)?;
-                let name = name.to_owned();
-                let mut buffer = vec![];
-                archive.by_name(&name)?.read_to_end(&mut buffer)?;
-                Ok(Box::new(Cursor::new(buffer)))
+                let reader = BlockingReader::Zip(archive.clone());
+                let data = reader.read_start_async(path, u64::MAX).await?;
+                Ok(Box::new(Cursor::new(data)))
+            }
+            Self::Tar(archive) => {
+                let reader = BlockingReader::Tar(archive.clone());
+                let data = reader.read_start_async(path, u64::MAX).await?;
+                Ok(Box::new(Cursor::new(data)))
             }
             Self::Manual(map) => map.open(path).await,
             #[cfg(not(target_family = "wasm"))]
@@ -182,3 +468,102 @@ impl BrushVfs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BrushVfs;
+    use std::path::{Path, PathBuf};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn read_tar_archive() {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in [("./transforms.json", "{}"), ("images/0.png", "png")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, data.as_bytes())
+                .expect("Failed to write tar");
+        }
+        let data = builder.into_inner().expect("Failed to finish tar");
+
+        let mut vfs = BrushVfs::from_tar_reader(data.as_slice(), false)
+            .await
+            .expect("Failed to read tar");
+        let names: Vec<_> = vfs.file_names().collect();
+        assert_eq!(
+            names,
+            [
+                PathBuf::from("images/0.png"),
+                PathBuf::from("transforms.json")
+            ],
+            "Tar should list all files"
+        );
+
+        let mut contents = String::new();
+        vfs.open_path(Path::new("transforms.json"))
+            .await
+            .expect("Missing file")
+            .read_to_string(&mut contents)
+            .await
+            .expect("Failed to read file");
+        assert_eq!(contents, "{}", "File contents should match");
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    #[tokio::test]
+    async fn read_zip_from_disk() {
+        use std::io::{Seek, Write};
+
+        let mut file = tempfile::tempfile().expect("Failed to create temp file");
+        let mut writer = zip::ZipWriter::new(&mut file);
+        writer
+            .start_file("images/0.png", zip::write::SimpleFileOptions::default())
+            .expect("Failed to start zip file");
+        writer.write_all(b"png").expect("Failed to write zip");
+        writer
+            .start_file(
+                "./sparse/0/cameras.bin",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .expect("Failed to start zip file");
+        writer.write_all(b"cams").expect("Failed to write zip");
+        writer.finish().expect("Failed to finish zip");
+        file.rewind().expect("Failed to rewind");
+
+        let mut vfs = BrushVfs::from_zip_file(file).expect("Failed to read zip");
+        // Clones share the file, but should read independently.
+        let mut other = vfs.clone();
+        for vfs in [&mut vfs, &mut other] {
+            let mut contents = String::new();
+            vfs.open_path(Path::new("images/0.png"))
+                .await
+                .expect("Missing file")
+                .read_to_string(&mut contents)
+                .await
+                .expect("Failed to read file");
+            assert_eq!(contents, "png", "File contents should match");
+        }
+
+        // Entries are opened by their normalized name, as listed by `file_names`.
+        let path = vfs
+            .file_names()
+            .find(|p| p.ends_with("cameras.bin"))
+            .expect("Missing cameras file");
+        assert_eq!(
+            path,
+            PathBuf::from("sparse/0/cameras.bin"),
+            "Name should be cleaned"
+        );
+        let mut contents = String::new();
+        vfs.open_path(&path)
+            .await
+            .expect("Missing file")
+            .read_to_string(&mut contents)
+            .await
+            .expect("Failed to read file");
+        assert_eq!(contents, "cams", "File contents should match");
+    }
+}
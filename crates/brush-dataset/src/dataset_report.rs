@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use brush_train::scene::ViewImageType;
+use burn::prelude::Backend;
+use path_clean::PathClean;
+use serde::Serialize;
+use tokio_stream::StreamExt;
+
+use crate::{
+    Dataset, DatasetError, LoadDataseConfig, LoadProgress, brush_vfs::BrushVfs,
+    load_dataset_with_format,
+};
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "webp", "tif", "tiff", "exr", "hdr"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolutionCount {
+    pub width: u32,
+    pub height: u32,
+    pub views: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraModelReport {
+    pub model: String,
+    /// Whether the cameras have lens distortion, which Brush ignores.
+    pub distortion_ignored: bool,
+}
+
+/// A summary of a dataset, to check it before training on it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatasetReport {
+    /// The detected dataset format, if any format could be loaded.
+    pub format: Option<String>,
+    pub train_views: usize,
+    pub eval_views: usize,
+    /// Views with a separate mask image.
+    pub masked_views: usize,
+    /// Image sizes as loaded, so after applying the max resolution. Most common first.
+    pub resolutions: Vec<ResolutionCount>,
+    pub camera_models: Vec<CameraModelReport>,
+    pub scene_extent: Option<f32>,
+    pub up_axis: Option<[f32; 3]>,
+    /// Points used to initialize training, from the SfM data or an included point cloud.
+    pub sfm_points: Option<u32>,
+    /// Images the dataset refers to which aren't in the data.
+    pub missing_images: Vec<PathBuf>,
+    /// Images in the data which aren't used by any view.
+    pub unused_images: Vec<PathBuf>,
+    /// Masks in the data which don't belong to any view.
+    pub unmatched_masks: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+/// A path without its extension, as nerfstudio datasets can leave out the image extension.
+fn path_key(path: &Path) -> PathBuf {
+    path.clean().with_extension("")
+}
+
+fn parent_dir_is(path: &Path, names: &[&str]) -> bool {
+    path.parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|p| names.iter().any(|n| p == *n))
+}
+
+fn is_mask(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|s| s.to_string_lossy().ends_with("_mask"))
+        || parent_dir_is(path, &["masks"])
+}
+
+impl DatasetReport {
+    /// Load a dataset, and report what was found and what went wrong.
+    pub async fn create<B: Backend>(
+        vfs: BrushVfs,
+        load_args: &LoadDataseConfig,
+        device: &B::Device,
+    ) -> Self {
+        let mut report = Self::default();
+        let progress = LoadProgress::default();
+
+        let (format, mut init_stream, mut data_stream) =
+            match load_dataset_with_format::<B>(vfs.clone(), load_args, &progress, device).await {
+                Ok(streams) => streams,
+                Err(e) => {
+                    report.errors.push(e.to_string());
+                    return report;
+                }
+            };
+        report.format = Some(format.to_string());
+
+        // Keep going after failed views to find all problems.
+        let mut dataset = Dataset::empty();
+        while let Some(d) = data_stream.next().await {
+            match d {
+                Ok(d) => dataset = d,
+                Err(e) => match DatasetError::find_in(&e) {
+                    Some(DatasetError::MissingImage { path, .. }) => {
+                        report.missing_images.push(path.clone());
+                    }
+                    _ => report.errors.push(format!("{e:#}")),
+                },
+            }
+        }
+
+        let mut up_axis = (!dataset.train.views.is_empty()).then(|| dataset.estimate_up());
+        while let Some(message) = init_stream.next().await {
+            match message {
+                Ok(message) => {
+                    up_axis = message.meta.up_axis.or(up_axis);
+                    report.sfm_points = Some(message.meta.total_splats);
+                }
+                Err(e) => report.errors.push(format!("{e:#}")),
+            }
+        }
+
+        report.camera_models = progress
+            .camera_models()
+            .into_iter()
+            .map(|(model, distortion_ignored)| CameraModelReport {
+                model,
+                distortion_ignored,
+            })
+            .collect();
+        report.up_axis = up_axis.map(|up| up.to_array());
+        report.add_views(&vfs, &dataset);
+        report
+    }
+
+    fn add_views(&mut self, vfs: &BrushVfs, dataset: &Dataset) {
+        let views: Vec<_> = dataset
+            .train
+            .views
+            .iter()
+            .chain(dataset.eval.iter().flat_map(|e| e.views.iter()))
+            .collect();
+
+        self.train_views = dataset.train.views.len();
+        self.eval_views = dataset.eval.as_ref().map_or(0, |e| e.views.len());
+        self.masked_views = views
+            .iter()
+            .filter(|v| v.img_type == ViewImageType::Masked)
+            .count();
+        self.scene_extent = dataset.train.estimate_extent();
+
+        let mut resolutions = BTreeMap::new();
+        for view in &views {
+            *resolutions
+                .entry((view.image.width(), view.image.height()))
+                .or_default() += 1;
+        }
+        self.resolutions = resolutions
+            .into_iter()
+            .map(|((width, height), views)| ResolutionCount {
+                width,
+                height,
+                views,
+            })
+            .collect();
+        self.resolutions.sort_by_key(|r| std::cmp::Reverse(r.views));
+
+        // View paths are relative to the dataset files, so match files on any trailing part.
+        let view_keys: HashSet<_> = views.iter().map(|v| path_key(Path::new(&v.path))).collect();
+        let is_view = |file: &Path| {
+            let key = path_key(file);
+            let components: Vec<_> = key.components().collect();
+            (0..components.len())
+                .any(|i| view_keys.contains(&components[i..].iter().collect::<PathBuf>()))
+        };
+
+        // Depth and confidence maps of phone captures aren't views.
+        let images: Vec<_> = vfs
+            .file_names()
+            .filter(|p| {
+                p.extension().is_some_and(|ext| {
+                    IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+                }) && !parent_dir_is(p, &["depth", "confidence", "conf"])
+            })
+            .collect();
+        let (masks, images): (Vec<_>, Vec<_>) = images.into_iter().partition(|p| is_mask(p));
+        let (used, unused): (Vec<_>, Vec<_>) = images.into_iter().partition(|p| is_view(p));
+
+        // Masks are either next to their image as `<name>_mask`, or in a sibling masks folder.
+        let used_keys: HashSet<_> = used.iter().map(|p| path_key(p)).collect();
+        let used_names: HashSet<_> = used
+            .iter()
+            .map(|p| {
+                let key = path_key(p);
+                let root = key.parent().and_then(Path::parent).map(Path::to_path_buf);
+                (root, key.file_name().map(|n| n.to_owned()))
+            })
+            .collect();
+        let has_image = |mask: &Path| {
+            let key = path_key(mask);
+            let name = key.file_name().map(|n| n.to_owned());
+            if parent_dir_is(mask, &["masks"]) {
+                let root = key.parent().and_then(Path::parent).map(Path::to_path_buf);
+                used_names.contains(&(root, name))
+            } else {
+                let image = key.to_string_lossy().trim_end_matches("_mask").to_owned();
+                used_keys.contains(Path::new(&image))
+            }
+        };
+
+        self.unused_images = unused;
+        self.unmatched_masks = masks.into_iter().filter(|m| !has_image(m)).collect();
+    }
+
+    /// A dataset with views and without any problems.
+    pub fn is_ok(&self) -> bool {
+        self.train_views > 0 && self.missing_images.is_empty() && self.errors.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Report is always valid json")
+    }
+}
+
+fn write_paths(f: &mut fmt::Formatter<'_>, title: &str, paths: &[PathBuf]) -> fmt::Result {
+    const MAX_SHOWN: usize = 10;
+
+    if paths.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{title} ({}):", paths.len())?;
+    for path in paths.iter().take(MAX_SHOWN) {
+        writeln!(f, "  {}", path.display())?;
+    }
+    if paths.len() > MAX_SHOWN {
+        writeln!(f, "  ... and {} more", paths.len() - MAX_SHOWN)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for DatasetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = self.format.as_deref().unwrap_or("Unknown");
+        writeln!(f, "Format: {format}")?;
+        writeln!(
+            f,
+            "Views: {} train, {} eval, {} masked",
+            self.train_views, self.eval_views, self.masked_views
+        )?;
+
+        if !self.resolutions.is_empty() {
+            let resolutions: Vec<_> = self
+                .resolutions
+                .iter()
+                .map(|r| format!("{}x{} ({} views)", r.width, r.height, r.views))
+                .collect();
+            writeln!(f, "Loaded resolutions: {}", resolutions.join(", "))?;
+        }
+
+        if !self.camera_models.is_empty() {
+            let models: Vec<_> = self
+                .camera_models
+                .iter()
+                .map(|c| {
+                    if c.distortion_ignored {
+                        format!("{} (distortion ignored)", c.model)
+                    } else {
+                        c.model.clone()
+                    }
+                })
+                .collect();
+            writeln!(f, "Camera models: {}", models.join(", "))?;
+        }
+
+        match self.scene_extent {
+            Some(extent) => writeln!(f, "Scene extent: {extent:.3}")?,
+            None => writeln!(f, "Scene extent: unknown (needs at least 5 views)")?,
+        }
+        if let Some([x, y, z]) = self.up_axis {
+            writeln!(f, "Up axis: [{x:.3}, {y:.3}, {z:.3}]")?;
+        }
+        match self.sfm_points {
+            Some(points) => writeln!(f, "SfM points: {points}")?,
+            None => writeln!(f, "SfM points: none, training starts from random points")?,
+        }
+
+        write_paths(f, "Missing images", &self.missing_images)?;
+        write_paths(f, "Unused images", &self.unused_images)?;
+        write_paths(f, "Unmatched masks", &self.unmatched_masks)?;
+
+        if !self.errors.is_empty() {
+            writeln!(f, "Errors ({}):", self.errors.len())?;
+            for error in &self.errors {
+                writeln!(f, "  {error}")?;
+            }
+        }
+        Ok(())
+    }
+}
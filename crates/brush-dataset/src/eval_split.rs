@@ -0,0 +1,312 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Context;
+use rand::{SeedableRng, rngs::StdRng};
+use tokio::io::AsyncReadExt;
+
+use crate::{Dataset, LoadDataseConfig, brush_vfs::BrushVfs};
+
+/// Name of the file the eval views are written to, which can be loaded again with
+/// `--eval-split-file`.
+pub const EVAL_SPLIT_FILE: &str = "eval_split.txt";
+
+/// How the views of a dataset are split into train and eval views.
+#[derive(Debug, Clone)]
+pub enum EvalSplit {
+    /// Use the eval views that come with the dataset, like the `transforms_val.json` of a
+    /// nerfstudio dataset. Most formats don't have any.
+    Dataset,
+    /// Every nth view.
+    Every(usize),
+    /// Views with an image matching one of these names.
+    Names(Vec<String>),
+    /// Views with an image path matching a glob pattern.
+    Glob(glob::Pattern),
+    /// A random percentage of the views.
+    Percent { percent: f32, seed: u64 },
+}
+
+/// Whether an image path matches a name. Names can be a file name, or a trailing part of the path,
+/// with or without extension.
+fn matches_name(path: &str, name: &str) -> bool {
+    let path = Path::new(path);
+    let name = Path::new(name);
+    path.ends_with(name) || path.with_extension("").ends_with(name.with_extension(""))
+}
+
+/// Read the names of a split file, skipping empty lines and `#` comments.
+fn parse_names(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Read a split file from the dataset, or from disk if the dataset doesn't include it.
+async fn read_split_file(vfs: &mut BrushVfs, path: &str) -> anyhow::Result<String> {
+    let path = Path::new(path);
+    if vfs.file_names().any(|p| p == path) {
+        let mut text = String::new();
+        vfs.open_path(path).await?.read_to_string(&mut text).await?;
+        return Ok(text);
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    {
+        tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read eval split file {path:?}"))
+    }
+    #[cfg(target_family = "wasm")]
+    {
+        anyhow::bail!("Eval split file {path:?} not found in dataset")
+    }
+}
+
+impl EvalSplit {
+    /// The split chosen in the config, reading the split file if there is one.
+    pub async fn from_config(
+        config: &LoadDataseConfig,
+        vfs: &mut BrushVfs,
+    ) -> anyhow::Result<Self> {
+        let options = [
+            config.eval_split_every.is_some(),
+            config.eval_split_file.is_some(),
+            config.eval_split_glob.is_some(),
+            config.eval_split_percent.is_some(),
+        ];
+        anyhow::ensure!(
+            options.iter().filter(|o| **o).count() <= 1,
+            "Only one of the eval split options can be used at a time"
+        );
+
+        if let Some(every) = config.eval_split_every {
+            anyhow::ensure!(
+                every > 1,
+                "Eval split period must be at least 2, otherwise there are no views to train on"
+            );
+            Ok(Self::Every(every))
+        } else if let Some(file) = &config.eval_split_file {
+            let names = parse_names(&read_split_file(vfs, file).await?);
+            anyhow::ensure!(
+                !names.is_empty(),
+                "Eval split file {file:?} lists no images"
+            );
+            Ok(Self::Names(names))
+        } else if let Some(pattern) = &config.eval_split_glob {
+            let pattern = glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid eval split pattern {pattern:?}"))?;
+            Ok(Self::Glob(pattern))
+        } else if let Some(percent) = config.eval_split_percent {
+            anyhow::ensure!(
+                (0.0..100.0).contains(&percent),
+                "Eval split percentage must be at least 0 and below 100, got {percent}"
+            );
+            Ok(Self::Percent {
+                percent,
+                seed: config.eval_split_seed,
+            })
+        } else {
+            Ok(Self::Dataset)
+        }
+    }
+
+    /// Picks the eval views out of `num_views` views, which are loaded in a fixed order. The last
+    /// `num_dataset_eval` views are the eval views of the dataset itself.
+    pub(crate) fn selector(&self, num_views: usize, num_dataset_eval: usize) -> EvalSelector {
+        let indices = match self {
+            Self::Percent { percent, seed } => {
+                let amount = ((num_views as f32 * percent / 100.0).round() as usize).min(num_views);
+                let mut rng = StdRng::seed_from_u64(*seed);
+                rand::seq::index::sample(&mut rng, num_views, amount)
+                    .into_iter()
+                    .collect()
+            }
+            Self::Dataset => (num_views.saturating_sub(num_dataset_eval)..num_views).collect(),
+            Self::Every(_) | Self::Names(_) | Self::Glob(_) => HashSet::new(),
+        };
+        EvalSelector {
+            split: self.clone(),
+            indices,
+        }
+    }
+}
+
+/// Decides for each loaded view whether it's an eval view.
+pub(crate) struct EvalSelector {
+    split: EvalSplit,
+    indices: HashSet<usize>,
+}
+
+impl EvalSelector {
+    /// Whether the view at `index`, with an image at `path`, is an eval view.
+    pub(crate) fn is_eval(&self, index: usize, path: &str) -> bool {
+        match &self.split {
+            EvalSplit::Dataset | EvalSplit::Percent { .. } => self.indices.contains(&index),
+            EvalSplit::Every(every) => index % every == 0,
+            EvalSplit::Names(names) => names.iter().any(|n| matches_name(path, n)),
+            EvalSplit::Glob(pattern) => {
+                // Patterns without a directory only need to match the file name.
+                let name = if pattern.as_str().contains('/') {
+                    Some(path)
+                } else {
+                    Path::new(path).file_name().and_then(|n| n.to_str())
+                };
+                name.is_some_and(|n| pattern.matches(n))
+            }
+        }
+    }
+}
+
+/// The eval views of a dataset as a split file, or `None` if there are no eval views.
+pub fn eval_split_file(dataset: &Dataset) -> Option<String> {
+    let eval = dataset.eval.as_ref()?;
+    let mut text =
+        format!("# Eval views, load the same split with --eval-split-file {EVAL_SPLIT_FILE}\n");
+    for view in eval.views.as_slice() {
+        text.push_str(&view.path);
+        text.push('\n');
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvalSplit, matches_name, parse_names};
+    use crate::{
+        LoadDataseConfig,
+        brush_vfs::{BrushVfs, PathReader},
+    };
+
+    fn eval_indices(split: &EvalSplit, paths: &[&str]) -> Vec<usize> {
+        let selector = split.selector(paths.len(), 0);
+        (0..paths.len())
+            .filter(|&i| selector.is_eval(i, paths[i]))
+            .collect()
+    }
+
+    #[test]
+    fn split_file_names() {
+        let names = parse_names("# comment\n\nimages/a.png\n  b.jpg  \n");
+        assert_eq!(
+            names,
+            ["images/a.png", "b.jpg"],
+            "Should skip comments and blank lines"
+        );
+
+        assert!(
+            matches_name("images/a.png", "a.png"),
+            "Should match a file name"
+        );
+        assert!(
+            matches_name("images/a.png", "images/a"),
+            "Should match without extension"
+        );
+        assert!(
+            matches_name("train/a", "a.png"),
+            "Should match paths without extension"
+        );
+        assert!(
+            !matches_name("images/ba.png", "a.png"),
+            "Should match whole names only"
+        );
+        assert!(
+            !matches_name("other/a.png", "images/a.png"),
+            "Should match the directory"
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_splits_without_train_views() {
+        let mut vfs = BrushVfs::from_paths(PathReader::default());
+        let every = LoadDataseConfig::new().with_eval_split_every(Some(1));
+        assert!(
+            EvalSplit::from_config(&every, &mut vfs).await.is_err(),
+            "A period of one leaves no train views"
+        );
+        let percent = LoadDataseConfig::new().with_eval_split_percent(Some(100.0));
+        assert!(
+            EvalSplit::from_config(&percent, &mut vfs).await.is_err(),
+            "A hundred percent leaves no train views"
+        );
+        let every = LoadDataseConfig::new().with_eval_split_every(Some(8));
+        assert!(
+            matches!(
+                EvalSplit::from_config(&every, &mut vfs).await,
+                Ok(EvalSplit::Every(8))
+            ),
+            "Should accept a period above one"
+        );
+    }
+
+    #[test]
+    fn selector_every() {
+        let paths = ["a.png"; 10];
+        assert_eq!(
+            eval_indices(&EvalSplit::Every(4), &paths),
+            [0, 4, 8],
+            "Should pick every nth view"
+        );
+        assert_eq!(
+            eval_indices(&EvalSplit::Every(1), &paths).len(),
+            10,
+            "A period of one should pick all views"
+        );
+    }
+
+    #[test]
+    fn selector_glob() {
+        let paths = [
+            "images/a_eval.png",
+            "images/b.png",
+            "eval/c.png",
+            "d_eval.jpg",
+        ];
+        let pattern = |p| EvalSplit::Glob(glob::Pattern::new(p).expect("Valid pattern"));
+        assert_eq!(
+            eval_indices(&pattern("*_eval.*"), &paths),
+            [0, 3],
+            "Patterns without a directory should match file names"
+        );
+        assert_eq!(
+            eval_indices(&pattern("eval/*"), &paths),
+            [2],
+            "Patterns with a directory should match the whole path"
+        );
+    }
+
+    #[test]
+    fn selector_percent() {
+        let paths = ["a.png"; 100];
+        let percent = |percent, seed| EvalSplit::Percent { percent, seed };
+
+        let picked = eval_indices(&percent(10.0, 42), &paths);
+        assert_eq!(picked.len(), 10, "Should pick a percentage of the views");
+        assert_eq!(
+            picked,
+            eval_indices(&percent(10.0, 42), &paths),
+            "The same seed should pick the same views"
+        );
+        assert_ne!(
+            picked,
+            eval_indices(&percent(10.0, 7), &paths),
+            "Another seed should pick other views"
+        );
+
+        assert!(
+            eval_indices(&percent(0.0, 42), &paths).is_empty(),
+            "Zero percent should pick no views"
+        );
+        assert_eq!(
+            eval_indices(&percent(100.0, 42), &paths).len(),
+            100,
+            "A hundred percent should pick all views"
+        );
+        assert_eq!(
+            eval_indices(&percent(50.0, 42), &paths[..3]).len(),
+            2,
+            "Amounts should round to the nearest view"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use ply_rs::ply::{Property, ScalarType};
+
+/// Per-splat scalar properties from a ply file that Brush doesn't use itself, eg. normals,
+/// segmentation labels or custom features.
+///
+/// These are kept on the side so they can be written back out on export.
+#[derive(Clone, Debug, Default)]
+pub struct ExtraProperties {
+    /// Name and type of each property, in the order they appear in the ply.
+    pub properties: Vec<(String, ScalarType)>,
+    /// Values of all properties, [num_splats, properties.len()].
+    pub values: Vec<f64>,
+}
+
+impl ExtraProperties {
+    pub fn new(properties: Vec<(String, ScalarType)>) -> Self {
+        Self {
+            properties,
+            values: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    pub fn num_splats(&self) -> usize {
+        if self.properties.is_empty() {
+            0
+        } else {
+            self.values.len() / self.properties.len()
+        }
+    }
+
+    pub fn row(&self, index: usize) -> &[f64] {
+        let width = self.properties.len();
+        &self.values[index * width..(index + 1) * width]
+    }
+
+    /// Add the values of one splat. Nb: The values need to be in the same order as the properties.
+    pub(crate) fn push_row(&mut self, row: &[f64]) {
+        // Be resilient to elements that somehow miss properties.
+        let width = self.properties.len();
+        self.values.extend(
+            row.iter()
+                .copied()
+                .chain(std::iter::repeat(0.0))
+                .take(width),
+        );
+    }
+
+    /// Reorder the values to match splats that were selected with these indices.
+    pub fn select(&self, indices: &[u32]) -> Self {
+        if self.is_empty() {
+            return self.clone();
+        }
+
+        Self {
+            properties: self.properties.clone(),
+            values: indices
+                .iter()
+                .flat_map(|&i| self.row(i as usize))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// Read any scalar ply property as a double. This is lossless for all ply scalar types.
+pub(crate) fn scalar_value(property: &Property) -> Option<f64> {
+    match *property {
+        Property::Char(v) => Some(v as f64),
+        Property::UChar(v) => Some(v as f64),
+        Property::Short(v) => Some(v as f64),
+        Property::UShort(v) => Some(v as f64),
+        Property::Int(v) => Some(v as f64),
+        Property::UInt(v) => Some(v as f64),
+        Property::Float(v) => Some(v as f64),
+        Property::Double(v) => Some(v),
+        _ => None,
+    }
+}
@@ -0,0 +1,406 @@
+use std::{future::Future, path::PathBuf};
+
+use super::{
+    DataStream, DatasetError, DatasetFormat, ViewImageLoader, find_mask_and_img, read_text,
+    split_views,
+};
+use crate::{
+    Dataset, LoadDataseConfig, LoadProgress, brush_vfs::BrushVfs, eval_split::EvalSplit,
+    extra_properties::ExtraProperties, splat_import::SplatMessage, stream_fut_parallel,
+};
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::{
+    camera::{Camera, focal_to_fov},
+    gaussian_splats::Splats,
+    sh::rgb_to_sh,
+};
+use brush_train::scene::SceneView;
+use burn::prelude::Backend;
+use glam::{Mat3, Vec3};
+use tokio_stream::StreamExt;
+
+#[derive(Clone)]
+struct BundlerCamera {
+    /// Focal length in pixels. Cameras that weren't reconstructed have a focal length of 0.
+    focal: f32,
+    /// Radial distortion coefficients.
+    k1: f32,
+    k2: f32,
+    /// World to camera rotation, in Bundler's convention (-Z forward, +Y up).
+    rotation: Mat3,
+    translation: Vec3,
+}
+
+struct BundlerPoint {
+    position: Vec3,
+    color: [u8; 3],
+}
+
+struct Bundle {
+    cameras: Vec<BundlerCamera>,
+    points: Vec<BundlerPoint>,
+}
+
+/// Whitespace separated values of a bundle file.
+struct Tokens<I>(I);
+
+impl<'a, I: Iterator<Item = &'a str>> Tokens<I> {
+    fn next<T: std::str::FromStr>(&mut self, what: &str) -> Result<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let token = self
+            .0
+            .next()
+            .with_context(|| format!("Unexpected end of file reading {what}"))?;
+        token
+            .parse()
+            .with_context(|| format!("Invalid value {token} for {what}"))
+    }
+}
+
+/// Parse a bundle.out file. See the [Bundler docs](https://www.cs.cornell.edu/~snavely/bundler/bundler-v0.4-manual.html).
+fn parse_bundle(text: &str) -> Result<Bundle> {
+    // The format is line based, but the values are easiest to parse as one stream of tokens.
+    let mut tokens = Tokens(
+        text.lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace),
+    );
+
+    let num_cameras: usize = tokens.next("camera count")?;
+    let num_points: usize = tokens.next("point count")?;
+
+    let mut cameras = Vec::with_capacity(num_cameras);
+    for _ in 0..num_cameras {
+        let focal = tokens.next("focal length")?;
+        let k1 = tokens.next("k1")?;
+        let k2 = tokens.next("k2")?;
+        let mut rows = [Vec3::ZERO; 3];
+        for row in &mut rows {
+            *row = Vec3::new(
+                tokens.next("rotation")?,
+                tokens.next("rotation")?,
+                tokens.next("rotation")?,
+            );
+        }
+        let translation = Vec3::new(
+            tokens.next("translation")?,
+            tokens.next("translation")?,
+            tokens.next("translation")?,
+        );
+        cameras.push(BundlerCamera {
+            focal,
+            k1,
+            k2,
+            // Stored row major.
+            rotation: Mat3::from_cols(rows[0], rows[1], rows[2]).transpose(),
+            translation,
+        });
+    }
+
+    let mut points = Vec::with_capacity(num_points);
+    for _ in 0..num_points {
+        let position = Vec3::new(
+            tokens.next("position")?,
+            tokens.next("position")?,
+            tokens.next("position")?,
+        );
+        let color = [
+            tokens.next("color")?,
+            tokens.next("color")?,
+            tokens.next("color")?,
+        ];
+        // Skip the view list, each entry is <camera> <key> <x> <y>.
+        let num_views: usize = tokens.next("view count")?;
+        let num_values = num_views.checked_mul(4).context("Invalid view count")?;
+        for _ in 0..num_values {
+            tokens.next::<f32>("view list")?;
+        }
+        points.push(BundlerPoint { position, color });
+    }
+
+    Ok(Bundle { cameras, points })
+}
+
+async fn read_bundle(vfs: &mut BrushVfs) -> Result<(Bundle, Vec<String>), DatasetError> {
+    let bundle_path = vfs
+        .file_names()
+        .find(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.to_lowercase().ends_with(".out"))
+        })
+        .ok_or_else(|| DatasetError::not_found(DatasetFormat::Bundler, "No .out file found"))?;
+
+    // list.txt lives next to the bundle folder in a standard Bundler run, but could be anywhere.
+    let list_path = vfs
+        .file_names()
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.eq_ignore_ascii_case("list.txt"))
+        })
+        .min_by_key(|p| p.components().count())
+        .ok_or_else(|| DatasetError::not_found(DatasetFormat::Bundler, "No list.txt found"))?;
+
+    let bundle = read_text(vfs, DatasetFormat::Bundler, &bundle_path).await?;
+    let bundle = parse_bundle(&bundle)
+        .map_err(|e| DatasetError::invalid_file(DatasetFormat::Bundler, &bundle_path, e))?;
+
+    let list = read_text(vfs, DatasetFormat::Bundler, &list_path).await?;
+    // Each line is the image path, optionally followed by an initial focal length guess.
+    let names: Vec<String> = list
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .map(|n| n.to_owned())
+        .collect();
+
+    if names.len() != bundle.cameras.len() {
+        return Err(DatasetError::invalid_file(
+            DatasetFormat::Bundler,
+            list_path,
+            format!(
+                "list.txt has {} images, but the bundle has {} cameras",
+                names.len(),
+                bundle.cameras.len()
+            ),
+        ));
+    }
+
+    Ok((bundle, names))
+}
+
+fn read_views(
+    vfs: BrushVfs,
+    cameras: Vec<BundlerCamera>,
+    names: Vec<String>,
+    load_args: &LoadDataseConfig,
+    progress: &LoadProgress,
+) -> Vec<impl Future<Output = Result<SceneView>>> {
+    let images = ViewImageLoader::new(&vfs, load_args);
+
+    let mut views: Vec<_> = cameras
+        .into_iter()
+        .zip(names)
+        .filter(|(camera, name)| {
+            if camera.focal <= 0.0 {
+                log::warn!("Skipping unregistered Bundler camera {name}");
+            }
+            camera.focal > 0.0
+        })
+        .collect();
+    views.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let distorted = views
+        .iter()
+        .any(|(cam, _)| cam.k1.abs() > 1e-3 || cam.k2.abs() > 1e-3);
+    if distorted {
+        log::warn!("Bundler cameras have radial distortion, which is ignored. Results may be off.");
+    }
+    progress.add_camera_model("Bundler radial", distorted);
+
+    log::info!("Loading Bundler dataset with {} cameras", views.len());
+
+    views
+        .into_iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .map(move |(bundler_cam, name)| {
+            let mut vfs = vfs.clone();
+            let images = images.clone();
+
+            async move {
+                let name_path = PathBuf::from(&name);
+                let img_paths: Vec<_> = vfs
+                    .file_names()
+                    .filter(|p| p.ends_with(&name_path) || p.file_name() == name_path.file_name())
+                    .collect();
+
+                let (path, mask_path) = find_mask_and_img(&vfs, &img_paths).map_err(|_| {
+                    DatasetError::MissingImage {
+                        format: DatasetFormat::Bundler,
+                        path: name_path.clone(),
+                    }
+                })?;
+
+                let (image, img_type, img_size, metadata) = images
+                    .load(&mut vfs, &path, mask_path.as_deref())
+                    .await
+                    .map_err(|e| DatasetError::invalid_image(DatasetFormat::Bundler, &path, e))?;
+
+                // Bundler doesn't store image sizes, so get the field of view from the full size image.
+                let fovx = focal_to_fov(bundler_cam.focal as f64, img_size.x);
+                let fovy = focal_to_fov(bundler_cam.focal as f64, img_size.y);
+
+                // Bundler cameras look down -Z with +Y up, flip to +Z forward and +Y down.
+                let flip = Mat3::from_diagonal(Vec3::new(1.0, -1.0, -1.0));
+                let world_to_cam = glam::Affine3A::from_mat3_translation(
+                    flip * bundler_cam.rotation,
+                    flip * bundler_cam.translation,
+                );
+                let (_, quat, translation) = world_to_cam.inverse().to_scale_rotation_translation();
+
+                // Bundler assumes the principal point is at the image center.
+                let camera = Camera::new(translation, quat, fovx, fovy, glam::vec2(0.5, 0.5));
+
+                Ok(SceneView {
+                    path: path.to_string_lossy().to_string(),
+                    camera,
+                    image,
+                    img_type,
+                    metadata,
+                })
+            }
+        })
+        .collect()
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    let (bundle, names) = read_bundle(&mut vfs).await?;
+
+    let mut handles = read_views(vfs, bundle.cameras, names, load_args, progress);
+
+    if let Some(subsample) = load_args.subsample_frames {
+        handles = handles.into_iter().step_by(subsample as usize).collect();
+    }
+
+    let selector = split.selector(handles.len(), 0);
+    let views = stream_fut_parallel(handles, progress)
+        .map(|view| view.context("Failed to load Bundler view"));
+    let stream = split_views(views, selector);
+
+    let subsample_points = load_args.subsample_points.unwrap_or(1).max(1) as usize;
+    let points: Vec<_> = bundle
+        .points
+        .into_iter()
+        .step_by(subsample_points)
+        .collect();
+    let device = device.clone();
+
+    let init_stream = try_fn_stream(|emitter| async move {
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        log::info!("Starting from Bundler points {}", points.len());
+
+        let positions: Vec<Vec3> = points.iter().map(|p| p.position).collect();
+        let colors: Vec<f32> = points
+            .iter()
+            .flat_map(|p| {
+                let sh = rgb_to_sh(Vec3::from_array(p.color.map(|c| c as f32 / 255.0)));
+                [sh.x, sh.y, sh.z]
+            })
+            .collect();
+
+        let init_splat = Splats::from_raw(&positions, None, None, Some(&colors), None, &device);
+        emitter
+            .emit(SplatMessage {
+                meta: crate::splat_import::ParseMetadata {
+                    up_axis: None,
+                    total_splats: init_splat.num_splats(),
+                    frame_count: 1,
+                    current_frame: 0,
+                },
+                splats: init_splat,
+                extra_properties: ExtraProperties::default(),
+            })
+            .await;
+
+        Ok(())
+    });
+
+    Ok((Box::pin(init_stream), Box::pin(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_bundle;
+    use glam::{Mat3, Vec3};
+
+    const BUNDLE: &str = "# Bundle file v0.3
+2 2
+500 0.01 -0.02
+0 -1 0
+1 0 0
+0 0 1
+1 2 3
+0 0 0
+0 0 0
+0 0 0
+0 0 0
+0 0 0
+0.5 1.5 -2
+255 128 0
+2 0 12 10.5 -3.25 1 7 -1 2
+-1 0 4
+10 20 30
+0
+";
+
+    #[test]
+    fn parse() {
+        let bundle = parse_bundle(BUNDLE).expect("Failed to parse bundle");
+        assert_eq!(bundle.cameras.len(), 2, "Wrong camera count");
+        assert_eq!(bundle.points.len(), 2, "Wrong point count");
+
+        let camera = &bundle.cameras[0];
+        assert_eq!(
+            (camera.focal, camera.k1, camera.k2),
+            (500.0, 0.01, -0.02),
+            "Wrong intrinsics"
+        );
+        // Rows are stored first, so the first column is (0, 1, 0).
+        let rotation = Mat3::from_cols(Vec3::Y, -Vec3::X, Vec3::Z);
+        assert_eq!(camera.rotation, rotation, "Rotation should be row major");
+        assert_eq!(
+            camera.translation,
+            Vec3::new(1.0, 2.0, 3.0),
+            "Wrong translation"
+        );
+        assert_eq!(
+            bundle.cameras[1].focal, 0.0,
+            "Unregistered camera has no focal"
+        );
+
+        // The view list of the first point is skipped.
+        let point = &bundle.points[0];
+        assert_eq!(point.position, Vec3::new(0.5, 1.5, -2.0), "Wrong position");
+        assert_eq!(point.color, [255, 128, 0], "Wrong color");
+        let point = &bundle.points[1];
+        assert_eq!(point.position, Vec3::new(-1.0, 0.0, 4.0), "Wrong position");
+        assert_eq!(point.color, [10, 20, 30], "Wrong color");
+    }
+
+    #[test]
+    fn invalid_counts() {
+        assert!(
+            parse_bundle("1.5 0").is_err(),
+            "Fractional camera count should fail"
+        );
+        assert!(
+            parse_bundle("-1 0").is_err(),
+            "Negative camera count should fail"
+        );
+        assert!(
+            parse_bundle("0 1\n0 0 0\n1 2 3\n4611686018427387904").is_err(),
+            "Too large view count should fail"
+        );
+        assert!(
+            parse_bundle("1 0\n500 0 0\n1 0 0").is_err(),
+            "Truncated camera should fail"
+        );
+        let empty = parse_bundle("0 0").expect("Empty bundle should parse");
+        assert!(
+            empty.cameras.is_empty() && empty.points.is_empty(),
+            "Bundle should be empty"
+        );
+    }
+}
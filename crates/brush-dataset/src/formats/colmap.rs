@@ -1,14 +1,15 @@
 use std::{
     future::Future,
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
-use super::DataStream;
+use super::{DataStream, split_views};
 use crate::{
-    Dataset, LoadDataseConfig,
+    Dataset, LoadDataseConfig, LoadProgress,
     brush_vfs::BrushVfs,
-    formats::{clamp_img_to_max_size, find_mask_path, load_image},
+    eval_split::EvalSplit,
+    extra_properties::ExtraProperties,
+    formats::{DatasetError, DatasetFormat, ViewImageLoader, find_mask_and_img},
     splat_import::SplatMessage,
     stream_fut_parallel,
 };
@@ -19,10 +20,9 @@ use brush_render::{
     gaussian_splats::Splats,
     sh::rgb_to_sh,
 };
-use brush_train::scene::SceneView;
+use brush_train::scene::{SceneView, ViewMetadata};
 use burn::prelude::Backend;
 use glam::Vec3;
-use std::collections::HashMap;
 use tokio_stream::StreamExt;
 
 fn find_base_path(archive: &BrushVfs, search_path: &str) -> Option<PathBuf> {
@@ -39,34 +39,40 @@ fn find_base_path(archive: &BrushVfs, search_path: &str) -> Option<PathBuf> {
     None
 }
 
-fn find_mask_and_img(vfs: &BrushVfs, paths: &[PathBuf]) -> Result<(PathBuf, Option<PathBuf>)> {
-    let mut path_masks = HashMap::new();
-    let mut masks = vec![];
-
-    // First pass: collect images & masks.
-    for path in paths {
-        let mask = find_mask_path(vfs, path);
-        path_masks.insert(path.clone(), mask.clone());
-        if let Some(mask_path) = mask {
-            masks.push(mask_path);
-        }
+/// Describe a failure to read a COLMAP file, picking out unsupported camera models.
+fn file_error(path: &Path, err: anyhow::Error) -> DatasetError {
+    let model = err
+        .downcast_ref::<std::io::Error>()
+        .and_then(|e| e.get_ref())
+        .and_then(|e| e.downcast_ref::<colmap_reader::UnsupportedCameraModel>());
+    match model {
+        Some(model) => DatasetError::UnsupportedCameraModel {
+            format: DatasetFormat::Colmap,
+            path: path.to_path_buf(),
+            model: model.0.clone(),
+        },
+        None => DatasetError::invalid_file(DatasetFormat::Colmap, path, err),
     }
+}
 
-    // Remove masks from candidates - shouldn't count as an input image.
-    for mask in masks {
-        path_masks.remove(&mask);
-    }
+/// Whether a camera has lens distortion, which Brush ignores.
+fn is_distorted(camera: &colmap_reader::Camera) -> bool {
+    use colmap_reader::CameraModel;
 
-    // Sort and return the first candidate (alphabetically).
-    path_masks
-        .into_iter()
-        .min_by_key(|kv| kv.0.clone())
-        .context("No candidates found")
+    let distortion = match camera.model {
+        CameraModel::SimplePinhole | CameraModel::Pinhole => return false,
+        CameraModel::SimpleRadial | CameraModel::Radial => camera.params.get(3..),
+        CameraModel::OpenCV | CameraModel::FullOpenCV => camera.params.get(4..),
+        // Fisheye projections are distorted, whatever their parameters.
+        _ => return true,
+    };
+    distortion.is_some_and(|d| d.iter().any(|&p| p != 0.0))
 }
 
 async fn read_views(
     vfs: BrushVfs,
     load_args: LoadDataseConfig,
+    progress: &LoadProgress,
 ) -> Result<Vec<impl Future<Output = Result<SceneView>>>> {
     log::info!("Loading colmap dataset");
     let mut vfs = vfs;
@@ -76,7 +82,10 @@ async fn read_views(
     } else if let Some(path) = find_base_path(&vfs, "cameras.txt") {
         (false, path)
     } else {
-        anyhow::bail!("No COLMAP data found (either text or binary.)")
+        Err(DatasetError::not_found(
+            DatasetFormat::Colmap,
+            "No cameras.bin or cameras.txt found",
+        ))?
     };
 
     let (cam_path, img_path) = if is_binary {
@@ -85,16 +94,24 @@ async fn read_views(
         (base_path.join("cameras.txt"), base_path.join("images.txt"))
     };
 
-    let cam_model_data = {
+    let cam_model_data = async {
         let mut cam_file = vfs.open_path(&cam_path).await?;
-        colmap_reader::read_cameras(&mut cam_file, is_binary).await?
-    };
+        anyhow::Ok(colmap_reader::read_cameras(&mut cam_file, is_binary).await?)
+    }
+    .await
+    .map_err(|e| file_error(&cam_path, e))?;
 
-    let img_infos = {
+    for camera in cam_model_data.values() {
+        progress.add_camera_model(camera.model.name(), is_distorted(camera));
+    }
+
+    let img_infos = async {
         let img_file = vfs.open_path(&img_path).await?;
         let mut buf_reader = tokio::io::BufReader::new(img_file);
-        colmap_reader::read_images(&mut buf_reader, is_binary).await?
-    };
+        anyhow::Ok(colmap_reader::read_images(&mut buf_reader, is_binary).await?)
+    }
+    .await
+    .map_err(|e| file_error(&img_path, e))?;
 
     let mut img_info_list = img_infos.into_iter().collect::<Vec<_>>();
 
@@ -103,12 +120,15 @@ async fn read_views(
     // Sort by image name. This is important to match the exact eval images mipnerf uses.
     img_info_list.sort_by_key(|key_img| key_img.1.name.clone());
 
+    let images = ViewImageLoader::new(&vfs, &load_args);
+
     let handles = img_info_list
         .into_iter()
         .take(load_args.max_frames.unwrap_or(usize::MAX))
         .map(move |(_, img_info)| {
             let cam_data = cam_model_data[&img_info.camera_id].clone();
             let mut vfs = vfs.clone();
+            let images = images.clone();
 
             // Create a future to handle loading the image.
             async move {
@@ -127,14 +147,17 @@ async fn read_views(
                     .filter(|p| p.ends_with(&img_info.name))
                     .collect();
 
-                let (path, mask_path) = find_mask_and_img(&vfs, &img_paths)
-                    .with_context(|| format!("Failed to find image {}", img_info.name))?;
+                let (path, mask_path) = find_mask_and_img(&vfs, &img_paths).map_err(|_| {
+                    DatasetError::MissingImage {
+                        format: DatasetFormat::Colmap,
+                        path: img_info.name.clone().into(),
+                    }
+                })?;
 
-                let (image, img_type) = load_image(&mut vfs, &path, mask_path.as_deref())
+                let (image, img_type, _, metadata) = images
+                    .load(&mut vfs, &path, mask_path.as_deref())
                     .await
-                    .with_context(|| format!("Failed to load image {}", img_info.name))?;
-
-                let image = clamp_img_to_max_size(Arc::new(image), load_args.max_resolution);
+                    .map_err(|e| DatasetError::invalid_image(DatasetFormat::Colmap, &path, e))?;
 
                 // Convert w2c to c2w.
                 let world_to_cam =
@@ -149,6 +172,10 @@ async fn read_views(
                     camera,
                     image,
                     img_type,
+                    metadata: ViewMetadata {
+                        camera_id: u32::try_from(img_info.camera_id).ok(),
+                        ..metadata
+                    },
                 };
                 Ok(view)
             }
@@ -161,37 +188,23 @@ async fn read_views(
 pub(crate) async fn load_dataset<B: Backend>(
     mut vfs: BrushVfs,
     load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
     device: &B::Device,
 ) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
-    let mut handles = read_views(vfs.clone(), load_args.clone()).await?;
+    let mut handles = read_views(vfs.clone(), load_args.clone(), progress).await?;
 
     if let Some(subsample) = load_args.subsample_frames {
         handles = handles.into_iter().step_by(subsample as usize).collect();
     }
 
-    let mut train_views = vec![];
-    let mut eval_views = vec![];
-
     let load_args = load_args.clone();
     let device = device.clone();
 
-    let mut i = 0;
-    let stream = stream_fut_parallel(handles).map(move |view| {
-        let view = view.context("Failed to load COLMAP view")?;
-
-        if let Some(eval_period) = load_args.eval_split_every {
-            if i % eval_period == 0 {
-                eval_views.push(view);
-            } else {
-                train_views.push(view);
-            }
-        } else {
-            train_views.push(view);
-        }
-
-        i += 1;
-        Ok(Dataset::from_views(train_views.clone(), eval_views.clone()))
-    });
+    let selector = split.selector(handles.len(), 0);
+    let views = stream_fut_parallel(handles, progress)
+        .map(|view| view.context("Failed to load COLMAP view"));
+    let stream = split_views(views, selector);
 
     let init_stream = try_fn_stream(|emitter| async move {
         let points_path = vfs.file_names().find(|p| {
@@ -256,6 +269,7 @@ pub(crate) async fn load_dataset<B: Backend>(
                             current_frame: 0,
                         },
                         splats: init_splat,
+                        extra_properties: ExtraProperties::default(),
                     })
                     .await;
             }
@@ -0,0 +1,232 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// The dataset formats Brush can load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    Nerfstudio,
+    Colmap,
+    Metashape,
+    Bundler,
+    SfmJson,
+    PhoneCapture,
+}
+
+impl fmt::Display for DatasetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Nerfstudio => "Nerfstudio json",
+            Self::Colmap => "COLMAP",
+            Self::Metashape => "Metashape",
+            Self::Bundler => "Bundler",
+            Self::SfmJson => "OpenMVG/AliceVision",
+            Self::PhoneCapture => "Polycam/Record3D",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DatasetError {
+    /// The files this format needs aren't in the data.
+    #[error("No {format} data found: {reason}")]
+    NotFound {
+        format: DatasetFormat,
+        reason: String,
+    },
+    /// A file of the dataset couldn't be read or parsed.
+    #[error("Invalid {format} file {path:?}: {reason}")]
+    InvalidFile {
+        format: DatasetFormat,
+        path: PathBuf,
+        reason: String,
+    },
+    /// The dataset uses a camera model Brush can't handle.
+    #[error("Unsupported camera model {model} in {path:?}")]
+    UnsupportedCameraModel {
+        format: DatasetFormat,
+        path: PathBuf,
+        model: String,
+    },
+    /// An image the dataset refers to isn't in the data.
+    #[error("Missing image {path:?} in {format} dataset")]
+    MissingImage {
+        format: DatasetFormat,
+        path: PathBuf,
+    },
+    /// An image of the dataset couldn't be decoded.
+    #[error("Failed to load image {path:?}: {reason}")]
+    InvalidImage {
+        format: DatasetFormat,
+        path: PathBuf,
+        reason: String,
+    },
+    /// Loading failed for some other reason.
+    #[error("Failed to load {format} dataset: {reason}")]
+    Other {
+        format: DatasetFormat,
+        reason: String,
+    },
+    /// The eval split options are invalid, or the split file couldn't be read.
+    #[error("Invalid eval split: {reason}")]
+    InvalidEvalSplit { reason: String },
+    /// None of the formats were found in the data. Lists why each format didn't match, one per
+    /// line.
+    #[error("No supported dataset format found{}", list_attempts(attempts))]
+    UnknownFormat { attempts: Vec<Self> },
+}
+
+fn list_attempts(attempts: &[DatasetError]) -> String {
+    attempts.iter().map(|e| format!("\n  {e}")).collect()
+}
+
+impl DatasetError {
+    pub(crate) fn not_found(format: DatasetFormat, reason: impl Into<String>) -> Self {
+        Self::NotFound {
+            format,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn invalid_file(
+        format: DatasetFormat,
+        path: impl Into<PathBuf>,
+        reason: impl fmt::Display,
+    ) -> Self {
+        Self::InvalidFile {
+            format,
+            path: path.into(),
+            reason: format!("{reason:#}"),
+        }
+    }
+
+    pub(crate) fn invalid_image(
+        format: DatasetFormat,
+        path: impl Into<PathBuf>,
+        reason: impl fmt::Display,
+    ) -> Self {
+        Self::InvalidImage {
+            format,
+            path: path.into(),
+            reason: format!("{reason:#}"),
+        }
+    }
+
+    /// Find a dataset error in an error chain, or describe the error as a failure of this format.
+    pub(crate) fn from_anyhow(format: DatasetFormat, err: anyhow::Error) -> Self {
+        err.downcast::<Self>().unwrap_or_else(|err| Self::Other {
+            format,
+            reason: format!("{err:#}"),
+        })
+    }
+
+    /// The most relevant error out of the errors of each format that was tried.
+    pub(crate) fn from_attempts(mut attempts: Vec<Self>) -> Self {
+        let is_failure = |e: &Self| !matches!(e, Self::NotFound { .. });
+        // The first format with its files present is most likely the intended format.
+        let Some(index) = attempts.iter().position(is_failure) else {
+            return Self::UnknownFormat { attempts };
+        };
+        let err = attempts.remove(index);
+        for other in attempts.iter().filter(|e| is_failure(e)) {
+            log::warn!("Also failed to load dataset: {other}");
+        }
+        err
+    }
+
+    /// The format that failed to load, if it's known.
+    pub fn format(&self) -> Option<DatasetFormat> {
+        match self {
+            Self::NotFound { format, .. }
+            | Self::InvalidFile { format, .. }
+            | Self::UnsupportedCameraModel { format, .. }
+            | Self::MissingImage { format, .. }
+            | Self::InvalidImage { format, .. }
+            | Self::Other { format, .. } => Some(*format),
+            Self::InvalidEvalSplit { .. } | Self::UnknownFormat { .. } => None,
+        }
+    }
+
+    /// The file that caused the error, if there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::InvalidFile { path, .. }
+            | Self::UnsupportedCameraModel { path, .. }
+            | Self::MissingImage { path, .. }
+            | Self::InvalidImage { path, .. } => Some(path),
+            Self::NotFound { .. }
+            | Self::Other { .. }
+            | Self::InvalidEvalSplit { .. }
+            | Self::UnknownFormat { .. } => None,
+        }
+    }
+
+    /// Find a dataset error anywhere in an error chain.
+    pub fn find_in(err: &anyhow::Error) -> Option<&Self> {
+        err.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DatasetError, DatasetFormat};
+
+    #[test]
+    fn most_relevant_attempt() {
+        let attempts = vec![
+            DatasetError::not_found(DatasetFormat::Nerfstudio, "No json file found"),
+            DatasetError::invalid_file(DatasetFormat::Colmap, "sparse/0/cameras.bin", "EOF"),
+            DatasetError::not_found(DatasetFormat::Bundler, "No .out file found"),
+        ];
+        let err = DatasetError::from_attempts(attempts);
+        assert!(
+            matches!(
+                err,
+                DatasetError::InvalidFile {
+                    format: DatasetFormat::Colmap,
+                    ..
+                }
+            ),
+            "Should report the format whose files were found, got {err}"
+        );
+
+        let err = DatasetError::from_attempts(vec![DatasetError::not_found(
+            DatasetFormat::Colmap,
+            "No cameras file",
+        )]);
+        assert!(
+            matches!(err, DatasetError::UnknownFormat { .. }),
+            "Should report an unknown format when nothing was found, got {err}"
+        );
+        assert!(
+            err.to_string().contains("No cameras file"),
+            "Should say why each format didn't match, got {err}"
+        );
+    }
+
+    #[test]
+    fn find_through_context() {
+        let err = anyhow::Error::from(DatasetError::invalid_image(
+            DatasetFormat::Colmap,
+            "images/a.png",
+            "Bad PNG",
+        ))
+        .context("Failed to load COLMAP view");
+        let found = DatasetError::find_in(&err).expect("Should find the dataset error");
+        assert_eq!(
+            found.path(),
+            Some(std::path::Path::new("images/a.png")),
+            "Should keep the failing path"
+        );
+        assert!(
+            matches!(
+                DatasetError::from_anyhow(DatasetFormat::Colmap, err),
+                DatasetError::InvalidImage { .. }
+            ),
+            "Should recover the dataset error from a context chain"
+        );
+    }
+}
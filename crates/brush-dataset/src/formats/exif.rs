@@ -0,0 +1,281 @@
+use brush_train::scene::ViewMetadata;
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_EXPOSURE_TIME: u16 = 0x829a;
+const TAG_F_NUMBER: u16 = 0x829d;
+const TAG_ISO: u16 = 0x8827;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_SUB_SEC_TIME_ORIGINAL: u16 = 0x9291;
+
+/// An entry of an IFD, pointing at its value.
+#[derive(Clone, Copy)]
+struct Entry {
+    kind: u16,
+    count: usize,
+    offset: usize,
+}
+
+/// The TIFF structure EXIF data is stored in.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.data
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn find(&self, ifd: usize, tag: u16) -> Option<Entry> {
+        let num_entries = self.u16(ifd)? as usize;
+        let pos = (0..num_entries)
+            .map_while(|i| ifd.checked_add(2 + i * 12))
+            .find(|&pos| self.u16(pos) == Some(tag))?;
+
+        let kind = self.u16(pos.checked_add(2)?)?;
+        let count = self.u32(pos.checked_add(4)?)? as usize;
+        let size = match kind {
+            TYPE_ASCII => 1_usize,
+            TYPE_SHORT => 2,
+            TYPE_LONG => 4,
+            TYPE_RATIONAL => 8,
+            _ => return None,
+        }
+        .checked_mul(count)?;
+        // Values that fit are stored in the entry itself.
+        let offset = if size <= 4 {
+            pos.checked_add(8)?
+        } else {
+            self.u32(pos.checked_add(8)?)? as usize
+        };
+        Some(Entry {
+            kind,
+            count,
+            offset,
+        })
+    }
+
+    fn uint(&self, entry: Entry) -> Option<u32> {
+        match entry.kind {
+            TYPE_SHORT => self.u16(entry.offset).map(u32::from),
+            TYPE_LONG => self.u32(entry.offset),
+            _ => None,
+        }
+    }
+
+    fn rational(&self, entry: Entry) -> Option<f64> {
+        if entry.kind != TYPE_RATIONAL {
+            return None;
+        }
+        let numerator = self.u32(entry.offset)?;
+        let denominator = self.u32(entry.offset.checked_add(4)?)?;
+        (denominator != 0).then(|| numerator as f64 / denominator as f64)
+    }
+
+    fn ascii(&self, entry: Entry) -> Option<&'a str> {
+        if entry.kind != TYPE_ASCII {
+            return None;
+        }
+        let bytes = self
+            .data
+            .get(entry.offset..entry.offset.checked_add(entry.count)?)?;
+        let text = std::str::from_utf8(bytes).ok()?;
+        Some(text.trim_end_matches('\0').trim())
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parse an EXIF "YYYY:MM:DD HH:MM:SS" date to seconds since 1970. EXIF dates are in the local
+/// time of the camera, which is treated as UTC.
+fn parse_date_time(text: &str, sub_sec: Option<&str>) -> Option<f64> {
+    let (date, time) = text.split_once(' ')?;
+    let mut date = date.split(':').map(|p| p.parse::<i64>().ok());
+    let mut time = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    // Cameras write all zeros or spaces when the date isn't set.
+    if year == 0 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    let fraction = sub_sec
+        .filter(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+        .and_then(|s| format!("0.{s}").parse::<f64>().ok())
+        .unwrap_or(0.0);
+    Some(seconds as f64 + fraction)
+}
+
+/// Read the capture time and exposure settings from a raw EXIF chunk, as returned by
+/// [`image::ImageDecoder::exif_metadata`].
+pub(crate) fn read_metadata(chunk: &[u8]) -> ViewMetadata {
+    let Some(tiff) = Tiff::new(chunk) else {
+        return ViewMetadata::default();
+    };
+    // The capture settings are in the EXIF IFD, which IFD0 points to.
+    let exif_ifd = tiff
+        .u32(4)
+        .and_then(|ifd0| tiff.find(ifd0 as usize, TAG_EXIF_IFD))
+        .and_then(|entry| tiff.uint(entry));
+    let Some(ifd) = exif_ifd.map(|ifd| ifd as usize) else {
+        return ViewMetadata::default();
+    };
+
+    let sub_sec = tiff
+        .find(ifd, TAG_SUB_SEC_TIME_ORIGINAL)
+        .and_then(|e| tiff.ascii(e));
+    ViewMetadata {
+        timestamp: tiff
+            .find(ifd, TAG_DATE_TIME_ORIGINAL)
+            .and_then(|e| tiff.ascii(e))
+            .and_then(|date| parse_date_time(date, sub_sec)),
+        camera_id: None,
+        exposure_time: tiff
+            .find(ifd, TAG_EXPOSURE_TIME)
+            .and_then(|e| tiff.rational(e))
+            .map(|t| t as f32),
+        iso: tiff.find(ifd, TAG_ISO).and_then(|e| tiff.uint(e)),
+        f_number: tiff
+            .find(ifd, TAG_F_NUMBER)
+            .and_then(|e| tiff.rational(e))
+            .map(|f| f as f32),
+        source_size: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TYPE_LONG, Tiff, parse_date_time, read_metadata};
+
+    /// A big endian EXIF chunk with IFD0 pointing to an EXIF IFD.
+    fn exif_chunk() -> Vec<u8> {
+        let mut data = b"MM\0*".to_vec();
+        data.extend(8u32.to_be_bytes());
+        // IFD0 with only the EXIF IFD pointer, at 26.
+        data.extend(1u16.to_be_bytes());
+        data.extend([0x87, 0x69, 0, 4]);
+        data.extend(1u32.to_be_bytes());
+        data.extend(26u32.to_be_bytes());
+        data.extend(0u32.to_be_bytes());
+        // EXIF IFD with 4 entries, values start at 26 + 2 + 4 * 12 + 4 = 80.
+        data.extend(4u16.to_be_bytes());
+        for (tag, kind, count, value) in [
+            (0x829a_u16, 5_u16, 1_u32, 80_u32),
+            (0x829d, 5, 1, 88),
+            (0x8827, 3, 1, 400 << 16),
+            (0x9003, 2, 20, 96),
+        ] {
+            data.extend(tag.to_be_bytes());
+            data.extend(kind.to_be_bytes());
+            data.extend(count.to_be_bytes());
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(0u32.to_be_bytes());
+        for (numerator, denominator) in [(1u32, 250u32), (28, 10)] {
+            data.extend(numerator.to_be_bytes());
+            data.extend(denominator.to_be_bytes());
+        }
+        data.extend(b"2024:02:29 12:30:15\0");
+        data
+    }
+
+    #[test]
+    fn read_exif_chunk() {
+        let metadata = read_metadata(&exif_chunk());
+        assert_eq!(metadata.exposure_time, Some(1.0 / 250.0), "Exposure time");
+        assert_eq!(metadata.f_number, Some(2.8), "Aperture");
+        assert_eq!(metadata.iso, Some(400), "ISO");
+        assert_eq!(metadata.timestamp, Some(1_709_209_815.0), "Capture time");
+
+        assert_eq!(
+            read_metadata(b"not exif"),
+            Default::default(),
+            "Invalid data should have no metadata"
+        );
+    }
+
+    #[test]
+    fn out_of_range_offsets() {
+        let data = exif_chunk();
+        let tiff = Tiff::new(&data).expect("Valid TIFF header");
+        assert_eq!(tiff.u32(usize::MAX - 1), None, "Offsets past the end");
+        assert!(tiff.find(usize::MAX, 0x8769).is_none(), "IFD past the end");
+
+        // An entry claiming more values than fit in memory.
+        let mut data = b"II*\0".to_vec();
+        data.extend(1u16.to_le_bytes());
+        data.extend(0x829a_u16.to_le_bytes());
+        data.extend(TYPE_LONG.to_le_bytes());
+        data.extend(u32::MAX.to_le_bytes());
+        data.extend(u32::MAX.to_le_bytes());
+        let tiff = Tiff::new(&data).expect("Valid TIFF header");
+        let entry = tiff.find(4, 0x829a).expect("Entry should be found");
+        assert_eq!(tiff.uint(entry), None, "Values past the end");
+    }
+
+    #[test]
+    fn exif_dates() {
+        assert_eq!(
+            parse_date_time("1970:01:01 00:00:00", None),
+            Some(0.0),
+            "Unix epoch"
+        );
+        assert_eq!(
+            parse_date_time("2000:03:01 00:00:01", Some("25")),
+            Some(951_868_801.25),
+            "Sub second time should be added"
+        );
+        assert_eq!(
+            parse_date_time("0000:00:00 00:00:00", None),
+            None,
+            "Unset dates should be ignored"
+        );
+    }
+}
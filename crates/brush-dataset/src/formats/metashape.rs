@@ -0,0 +1,449 @@
+use std::{collections::HashMap, future::Future, path::PathBuf};
+
+use super::{
+    DataStream, DatasetError, DatasetFormat, ViewImageLoader, find_mask_and_img, read_text,
+    split_views,
+};
+use crate::{
+    Dataset, LoadDataseConfig, LoadProgress, brush_vfs::BrushVfs, eval_split::EvalSplit,
+    splat_import::SplatMessage, stream_fut_parallel,
+};
+use anyhow::{Context, Result};
+use brush_render::camera::{Camera, focal_to_fov};
+use brush_train::scene::{SceneView, ViewMetadata};
+use burn::prelude::Backend;
+use glam::{DAffine3, DMat3, DMat4, DVec3};
+use roxmltree::{Document, Node};
+use tokio_stream::StreamExt;
+
+#[derive(Clone)]
+struct Sensor {
+    width: u32,
+    height: u32,
+    /// Focal length in pixels.
+    focal: (f64, f64),
+    /// Principal point in pixels.
+    center: (f64, f64),
+    /// The sensor type, eg. frame or fisheye.
+    model: String,
+    /// Whether there are distortion coefficients, which are ignored.
+    distorted: bool,
+}
+
+struct MetashapeCamera {
+    label: String,
+    sensor_id: String,
+    /// Transform from camera space to world space (including the chunk & component transform).
+    cam_to_world: DMat4,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn parse_floats(node: Node<'_, '_>) -> Result<Vec<f64>> {
+    node.text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|v| {
+            v.parse()
+                .with_context(|| format!("Invalid number {v} in <{}>", node.tag_name().name()))
+        })
+        .collect()
+}
+
+fn parse_float(node: Node<'_, '_>, name: &str) -> Result<Option<f64>> {
+    child(node, name)
+        .map(|n| anyhow::Ok(*parse_floats(n)?.first().context("Empty value")?))
+        .transpose()
+}
+
+fn parse_attr<T: std::str::FromStr>(node: Node<'_, '_>, name: &str) -> Result<T> {
+    let value = node
+        .attribute(name)
+        .with_context(|| format!("Missing attribute {name} on <{}>", node.tag_name().name()))?;
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("Invalid attribute {name}={value}"))
+}
+
+/// Parse a `<transform>` with a rotation, translation and scale, as used by chunks and components.
+fn parse_similarity(transform: Node<'_, '_>) -> Result<DMat4> {
+    let rotation = child(transform, "rotation")
+        .map(parse_floats)
+        .transpose()?
+        .map_or(Ok(DMat3::IDENTITY), |r| {
+            let r: [f64; 9] = r.try_into().ok().context("Rotation needs 9 values")?;
+            // Stored row major.
+            anyhow::Ok(DMat3::from_cols_array(&r).transpose())
+        })?;
+    let translation = child(transform, "translation")
+        .map(parse_floats)
+        .transpose()?
+        .map_or(Ok(DVec3::ZERO), |t| {
+            let t: [f64; 3] = t.try_into().ok().context("Translation needs 3 values")?;
+            anyhow::Ok(DVec3::from_array(t))
+        })?;
+    let scale = parse_float(transform, "scale")?.unwrap_or(1.0);
+
+    Ok(DMat4::from(DAffine3::from_mat3_translation(
+        rotation * scale,
+        translation,
+    )))
+}
+
+fn parse_sensor(sensor: Node<'_, '_>) -> Result<Sensor> {
+    // Prefer the adjusted calibration over the initial one.
+    let calibration = sensor
+        .children()
+        .filter(|n| n.has_tag_name("calibration"))
+        .max_by_key(|n| n.attribute("class") == Some("adjusted"))
+        .context("Sensor has no calibration")?;
+
+    let resolution = child(calibration, "resolution")
+        .or_else(|| child(sensor, "resolution"))
+        .context("Sensor has no resolution")?;
+    let width: u32 = parse_attr(resolution, "width")?;
+    let height: u32 = parse_attr(resolution, "height")?;
+
+    let f = parse_float(calibration, "f")?.context("Calibration has no focal length")?;
+    // b1 is the affinity term, which makes the x focal length different.
+    let b1 = parse_float(calibration, "b1")?.unwrap_or(0.0);
+    // Principal point is stored as offset from the image center.
+    let cx = parse_float(calibration, "cx")?.unwrap_or(0.0);
+    let cy = parse_float(calibration, "cy")?.unwrap_or(0.0);
+
+    let model = calibration
+        .attribute("type")
+        .or_else(|| sensor.attribute("type"))
+        .unwrap_or("frame")
+        .to_owned();
+    let mut distorted = model != "frame";
+    for coefficient in ["k1", "k2", "k3", "k4", "p1", "p2"] {
+        distorted |= parse_float(calibration, coefficient)?.is_some_and(|c| c != 0.0);
+    }
+
+    Ok(Sensor {
+        width,
+        height,
+        focal: (f + b1, f),
+        center: (width as f64 / 2.0 + cx, height as f64 / 2.0 + cy),
+        model,
+        distorted,
+    })
+}
+
+fn parse_cameras_xml(xml: &str) -> Result<(HashMap<String, Sensor>, Vec<MetashapeCamera>)> {
+    let doc = Document::parse(xml).context("Invalid XML")?;
+    let chunk = doc
+        .descendants()
+        .find(|n| n.has_tag_name("chunk"))
+        .context("No <chunk> in Metashape XML")?;
+
+    let mut sensors = HashMap::new();
+    for sensor in child(chunk, "sensors")
+        .into_iter()
+        .flat_map(|s| s.children().filter(|n| n.has_tag_name("sensor")))
+    {
+        let id: String = parse_attr(sensor, "id")?;
+        match parse_sensor(sensor) {
+            Ok(parsed) => {
+                sensors.insert(id, parsed);
+            }
+            // Sensors without a calibration can't be used, but that's fine if no camera uses them.
+            Err(e) => log::warn!("Skipping Metashape sensor {id}: {e:#}"),
+        }
+    }
+
+    let mut components = HashMap::new();
+    for component in child(chunk, "components")
+        .into_iter()
+        .flat_map(|c| c.children().filter(|n| n.has_tag_name("component")))
+    {
+        let id: String = parse_attr(component, "id")?;
+        let transform = child(component, "transform")
+            .map(parse_similarity)
+            .transpose()?
+            .unwrap_or(DMat4::IDENTITY);
+        components.insert(id, transform);
+    }
+
+    let chunk_transform = child(chunk, "transform")
+        .map(parse_similarity)
+        .transpose()?
+        .unwrap_or(DMat4::IDENTITY);
+
+    // Cameras can be nested in groups, so look at all descendants.
+    let mut cameras = vec![];
+    for camera in child(chunk, "cameras")
+        .context("No <cameras> in Metashape XML")?
+        .descendants()
+        .filter(|n| n.has_tag_name("camera"))
+    {
+        let label: String = parse_attr(camera, "label")?;
+
+        // Cameras that failed to align have no transform.
+        let Some(transform) = child(camera, "transform") else {
+            log::warn!("Skipping unaligned Metashape camera {label}");
+            continue;
+        };
+        let transform: [f64; 16] = parse_floats(transform)?
+            .try_into()
+            .ok()
+            .with_context(|| format!("Camera {label} transform needs 16 values"))?;
+        // Stored row major.
+        let transform = DMat4::from_cols_array(&transform).transpose();
+
+        let component = camera
+            .attribute("component_id")
+            .and_then(|id| components.get(id))
+            .copied()
+            .unwrap_or(DMat4::IDENTITY);
+
+        cameras.push(MetashapeCamera {
+            sensor_id: parse_attr(camera, "sensor_id")?,
+            label,
+            cam_to_world: chunk_transform * component * transform,
+        });
+    }
+
+    Ok((sensors, cameras))
+}
+
+async fn read_views(
+    vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    progress: &LoadProgress,
+) -> Result<Vec<impl Future<Output = Result<SceneView>>>> {
+    let mut vfs = vfs;
+
+    let xml_paths: Vec<_> = vfs
+        .file_names()
+        .filter(|p| p.extension().is_some_and(|ext| ext == "xml"))
+        .collect();
+
+    let mut parsed = None;
+    for path in xml_paths {
+        let xml = read_text(&mut vfs, DatasetFormat::Metashape, &path).await?;
+
+        // Other xml files can be in a dataset, only use ones that look like Metashape output.
+        if !xml.contains("<chunk") {
+            continue;
+        }
+
+        let (sensors, cameras) = parse_cameras_xml(&xml)
+            .map_err(|e| DatasetError::invalid_file(DatasetFormat::Metashape, &path, e))?;
+        parsed = Some((path, sensors, cameras));
+        break;
+    }
+
+    let (xml_path, sensors, mut cameras) = parsed.ok_or_else(|| {
+        DatasetError::not_found(DatasetFormat::Metashape, "No Metashape cameras XML found")
+    })?;
+
+    log::info!("Loading Metashape dataset with {} cameras", cameras.len());
+
+    for sensor in sensors.values() {
+        progress.add_camera_model(&sensor.model, sensor.distorted);
+    }
+
+    cameras.sort_by(|a, b| a.label.cmp(&b.label));
+
+    let images = ViewImageLoader::new(&vfs, load_args);
+
+    cameras
+        .into_iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .map(|camera| {
+            let sensor = sensors
+                .get(&camera.sensor_id)
+                .ok_or_else(|| {
+                    DatasetError::invalid_file(
+                        DatasetFormat::Metashape,
+                        &xml_path,
+                        format!(
+                            "Camera {} references unknown sensor {}",
+                            camera.label, camera.sensor_id
+                        ),
+                    )
+                })?
+                .clone();
+            let mut vfs = vfs.clone();
+            let images = images.clone();
+
+            Ok(async move {
+                let camera_id = camera.sensor_id.parse().ok();
+
+                // Labels are usually the image name without extension, but can include it.
+                let img_paths: Vec<PathBuf> = vfs
+                    .file_names()
+                    .filter(|p| {
+                        p.file_stem().is_some_and(|s| *s == *camera.label)
+                            || p.file_name().is_some_and(|s| *s == *camera.label)
+                    })
+                    .filter(|p| p.extension().is_none_or(|ext| ext != "xml"))
+                    .collect();
+
+                let (path, mask_path) = find_mask_and_img(&vfs, &img_paths).map_err(|_| {
+                    DatasetError::MissingImage {
+                        format: DatasetFormat::Metashape,
+                        path: camera.label.clone().into(),
+                    }
+                })?;
+
+                let (image, img_type, _, metadata) = images
+                    .load(&mut vfs, &path, mask_path.as_deref())
+                    .await
+                    .map_err(|e| DatasetError::invalid_image(DatasetFormat::Metashape, &path, e))?;
+
+                let fovx = focal_to_fov(sensor.focal.0, sensor.width);
+                let fovy = focal_to_fov(sensor.focal.1, sensor.height);
+                let center_uv = glam::vec2(
+                    (sensor.center.0 / sensor.width as f64) as f32,
+                    (sensor.center.1 / sensor.height as f64) as f32,
+                );
+
+                // Metashape cameras use the same convention as Brush (+Z forward, +Y down), but the
+                // chunk transform can include a scale, which is removed here.
+                let (_, rotation, translation) =
+                    DAffine3::from_mat4(camera.cam_to_world).to_scale_rotation_translation();
+                let camera = Camera::new(
+                    translation.as_vec3(),
+                    rotation.as_quat(),
+                    fovx,
+                    fovy,
+                    center_uv,
+                );
+
+                Ok(SceneView {
+                    path: path.to_string_lossy().to_string(),
+                    camera,
+                    image,
+                    img_type,
+                    metadata: ViewMetadata {
+                        camera_id,
+                        ..metadata
+                    },
+                })
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    let mut handles = read_views(vfs, load_args, progress).await?;
+
+    if let Some(subsample) = load_args.subsample_frames {
+        handles = handles.into_iter().step_by(subsample as usize).collect();
+    }
+
+    let selector = split.selector(handles.len(), 0);
+    let views = stream_fut_parallel(handles, progress)
+        .map(|view| view.context("Failed to load Metashape view"));
+    let stream = split_views(views, selector);
+
+    // Metashape camera exports don't include any points. A ply or point cloud in the
+    // dataset is used as initialization instead if present.
+    Ok((Box::pin(tokio_stream::empty()), Box::pin(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cameras_xml;
+    use glam::{DMat4, DQuat, DVec3};
+
+    const CAMERAS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<document version="1.5.0">
+  <chunk label="Chunk 1" enabled="true">
+    <sensors next_id="2">
+      <sensor id="0" label="unknown" type="frame">
+        <resolution width="800" height="600"/>
+        <calibration type="frame" class="initial">
+          <resolution width="800" height="600"/>
+          <f>900</f>
+        </calibration>
+        <calibration type="frame" class="adjusted">
+          <resolution width="800" height="600"/>
+          <f>1000</f>
+          <cx>10</cx>
+          <cy>-5</cy>
+          <b1>2</b1>
+          <k1>0</k1>
+        </calibration>
+      </sensor>
+      <sensor id="1" label="uncalibrated" type="frame"/>
+    </sensors>
+    <cameras next_id="2">
+      <group label="Folder">
+        <camera id="0" sensor_id="0" label="IMG_0001">
+          <transform>0 -1 0 1 1 0 0 2 0 0 1 3 0 0 0 1</transform>
+        </camera>
+      </group>
+      <camera id="1" sensor_id="0" label="IMG_0002"/>
+    </cameras>
+    <transform>
+      <rotation>1 0 0 0 1 0 0 0 1</rotation>
+      <translation>10 20 30</translation>
+      <scale>2</scale>
+    </transform>
+  </chunk>
+</document>"#;
+
+    #[test]
+    fn parse_cameras() {
+        let (sensors, cameras) = parse_cameras_xml(CAMERAS_XML).expect("Failed to parse XML");
+
+        assert_eq!(
+            sensors.len(),
+            1,
+            "Sensor without calibration should be skipped"
+        );
+        let sensor = &sensors["0"];
+        assert_eq!(
+            (sensor.width, sensor.height),
+            (800, 600),
+            "Wrong resolution"
+        );
+        assert_eq!(
+            sensor.focal,
+            (1002.0, 1000.0),
+            "Focal should use adjusted f and b1"
+        );
+        assert_eq!(
+            sensor.center,
+            (410.0, 295.0),
+            "Center should be offset by cx, cy"
+        );
+        assert!(!sensor.distorted, "Zero coefficients aren't a distortion");
+
+        assert_eq!(cameras.len(), 1, "Unaligned camera should be skipped");
+        let camera = &cameras[0];
+        assert_eq!(camera.label, "IMG_0001", "Wrong label");
+        assert_eq!(camera.sensor_id, "0", "Wrong sensor id");
+
+        // The row major camera transform, followed by the chunk similarity transform.
+        let rotation = DQuat::from_rotation_z(std::f64::consts::FRAC_PI_2);
+        let cam = DMat4::from_rotation_translation(rotation, DVec3::new(1.0, 2.0, 3.0));
+        let chunk = DMat4::from_scale_rotation_translation(
+            DVec3::splat(2.0),
+            DQuat::IDENTITY,
+            DVec3::new(10.0, 20.0, 30.0),
+        );
+        assert!(
+            camera.cam_to_world.abs_diff_eq(chunk * cam, 1e-9),
+            "Wrong transform {:?}",
+            camera.cam_to_world
+        );
+        let position = camera.cam_to_world.transform_point3(DVec3::ZERO);
+        assert!(
+            position.abs_diff_eq(DVec3::new(12.0, 24.0, 36.0), 1e-9),
+            "Wrong camera position {position}"
+        );
+    }
+}
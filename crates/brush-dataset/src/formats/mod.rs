@@ -1,79 +1,234 @@
 use crate::{
-    Dataset, LoadDataseConfig, WasmNotSend,
+    Dataset, LoadDataseConfig, LoadProgress, WasmNotSend,
     brush_vfs::BrushVfs,
-    splat_import::{SplatMessage, load_splat_from_ply},
+    eval_split::{EvalSelector, EvalSplit},
+    point_cloud,
+    splat_import::{SplatMessage, load_splat_from_ply, load_splat_from_safetensors},
 };
-use brush_train::scene::ViewImageType;
+use anyhow::Context;
+use brush_train::scene::{SceneView, ViewImageType};
 use burn::prelude::Backend;
 use image::DynamicImage;
 use path_clean::PathClean;
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
 };
 use tokio::io::AsyncReadExt;
-use tokio_stream::Stream;
+use tokio_stream::{Stream, StreamExt};
 
+pub mod bundler;
 pub mod colmap;
+mod error;
+mod exif;
+pub mod metashape;
 pub mod nerfstudio;
+pub mod phone_capture;
+pub mod sfm_json;
+mod view_images;
+
+pub use error::{DatasetError, DatasetFormat};
+pub(crate) use view_images::ViewImageLoader;
 
 pub trait DynStream<Item>: Stream<Item = Item> + WasmNotSend {}
 impl<Item, T: Stream<Item = Item> + WasmNotSend> DynStream<Item> for T {}
 pub type DataStream<T> = Pin<Box<dyn DynStream<anyhow::Result<T>> + 'static>>;
 
+/// Keep the result of loading a format, or record why it failed.
+fn attempt<T>(
+    format: DatasetFormat,
+    result: anyhow::Result<T>,
+    errors: &mut Vec<DatasetError>,
+) -> Option<(DatasetFormat, T)> {
+    match result {
+        Ok(result) => Some((format, result)),
+        Err(e) => {
+            errors.push(DatasetError::from_anyhow(format, e));
+            None
+        }
+    }
+}
+
+/// Sort views into train and eval views as they load, emitting the dataset so far for each view.
+pub(crate) fn split_views(
+    views: impl Stream<Item = anyhow::Result<SceneView>>,
+    selector: EvalSelector,
+) -> impl Stream<Item = anyhow::Result<Dataset>> {
+    let mut train_views = vec![];
+    let mut eval_views = vec![];
+
+    let mut i = 0;
+    views.map(move |view| {
+        // Count failed views too, so the split stays the same when some views fail to load.
+        let index = i;
+        i += 1;
+        let view = view?;
+        if selector.is_eval(index, &view.path) {
+            eval_views.push(view);
+        } else {
+            train_views.push(view);
+        }
+        Ok(Dataset::from_views(train_views.clone(), eval_views.clone()))
+    })
+}
+
+/// Load a dataset in any of the supported formats. Views are loaded while the returned stream is
+/// read, `progress` counts how many of them are loaded.
+///
+/// When no format loads, the error of the first format whose files were found is returned.
 pub async fn load_dataset<B: Backend>(
-    mut vfs: BrushVfs,
+    vfs: BrushVfs,
     load_args: &LoadDataseConfig,
+    progress: &LoadProgress,
     device: &B::Device,
-) -> anyhow::Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
-    let mut err_context = anyhow::anyhow!("Attempting to load dataset.");
-
-    let stream = nerfstudio::read_dataset(vfs.clone(), load_args, device).await;
-
-    let stream = match stream {
-        Ok(s) => Ok(s),
-        Err(e) => {
-            err_context = err_context
-                .context(e)
-                .context("Failed to load as json format.");
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>), DatasetError> {
+    let (_, init_stream, data_stream) =
+        load_dataset_with_format(vfs, load_args, progress, device).await?;
+    Ok((init_stream, data_stream))
+}
 
-            colmap::load_dataset::<B>(vfs.clone(), load_args, device).await
-        }
-    };
+/// Like [`load_dataset`], but also returns which format was loaded.
+pub async fn load_dataset_with_format<B: Backend>(
+    mut vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    progress: &LoadProgress,
+    device: &B::Device,
+) -> Result<
+    (
+        DatasetFormat,
+        DataStream<SplatMessage<B>>,
+        DataStream<Dataset>,
+    ),
+    DatasetError,
+> {
+    let split = EvalSplit::from_config(load_args, &mut vfs)
+        .await
+        .map_err(|e| DatasetError::InvalidEvalSplit {
+            reason: format!("{e:#}"),
+        })?;
 
-    let stream = match stream {
-        Ok(stream) => stream,
-        Err(e) => {
-            err_context = err_context
-                .context(e)
-                .context("Failed to load as COLMAP format.");
+    let mut errors = vec![];
 
-            Err(err_context.context("Failed to load dataset as any format."))?
-        }
+    let mut stream = attempt(
+        DatasetFormat::Nerfstudio,
+        nerfstudio::read_dataset(vfs.clone(), load_args, &split, progress, device).await,
+        &mut errors,
+    );
+    if stream.is_none() {
+        stream = attempt(
+            DatasetFormat::Colmap,
+            colmap::load_dataset::<B>(vfs.clone(), load_args, &split, progress, device).await,
+            &mut errors,
+        );
+    }
+    if stream.is_none() {
+        stream = attempt(
+            DatasetFormat::Metashape,
+            metashape::load_dataset::<B>(vfs.clone(), load_args, &split, progress).await,
+            &mut errors,
+        );
+    }
+    if stream.is_none() {
+        stream = attempt(
+            DatasetFormat::Bundler,
+            bundler::load_dataset::<B>(vfs.clone(), load_args, &split, progress, device).await,
+            &mut errors,
+        );
+    }
+    if stream.is_none() {
+        stream = attempt(
+            DatasetFormat::SfmJson,
+            sfm_json::load_dataset::<B>(vfs.clone(), load_args, &split, progress, device).await,
+            &mut errors,
+        );
+    }
+    if stream.is_none() {
+        stream = attempt(
+            DatasetFormat::PhoneCapture,
+            phone_capture::load_dataset::<B>(vfs.clone(), load_args, &split, progress, device)
+                .await,
+            &mut errors,
+        );
+    }
+    let Some((format, stream)) = stream else {
+        return Err(DatasetError::from_attempts(errors));
     };
 
-    // If there's an initial ply file, override the init stream with that.
+    // If there's an initial splat file or point cloud, override the init stream with that.
     let path: Vec<_> = vfs
         .file_names()
-        .filter(|x| x.extension().is_some_and(|ext| ext == "ply"))
+        .filter(|x| {
+            x.extension()
+                .is_some_and(|ext| ext == "ply" || ext == "safetensors")
+                || point_cloud::is_point_cloud_path(x)
+        })
         .collect();
 
-    let init_stream = if path.len() == 1 {
+    let init_stream: DataStream<SplatMessage<B>> = if path.len() == 1 {
         let main_path = path.first().expect("unreachable");
-        log::info!("Using ply {main_path:?} as initial point cloud.");
-
-        let reader = vfs.open_path(main_path).await?;
-        Box::pin(load_splat_from_ply(
-            reader,
-            load_args.subsample_points,
-            device.clone(),
-        ))
+        log::info!("Using {main_path:?} as initial point cloud.");
+
+        let reader = vfs
+            .open_path(main_path)
+            .await
+            .map_err(|e| DatasetError::invalid_file(format, main_path, e))?;
+        if main_path
+            .extension()
+            .is_some_and(|ext| ext == "safetensors")
+        {
+            Box::pin(load_splat_from_safetensors(reader, device.clone()))
+        } else if point_cloud::is_point_cloud_path(main_path) {
+            Box::pin(point_cloud::load_point_cloud(
+                reader,
+                main_path,
+                load_args.subsample_points,
+                device.clone(),
+            ))
+        } else {
+            Box::pin(load_splat_from_ply(
+                reader,
+                load_args.subsample_points,
+                device.clone(),
+            ))
+        }
     } else {
         stream.0
     };
 
-    Ok((init_stream, stream.1))
+    Ok((format, init_stream, stream.1))
+}
+
+/// Read a text file of a dataset.
+pub(crate) async fn read_text(
+    vfs: &mut BrushVfs,
+    format: DatasetFormat,
+    path: &Path,
+) -> Result<String, DatasetError> {
+    let mut text = String::new();
+    vfs.open_path(path)
+        .await
+        .map_err(|e| DatasetError::invalid_file(format, path, e))?
+        .read_to_string(&mut text)
+        .await
+        .map_err(|e| DatasetError::invalid_file(format, path, e))?;
+    Ok(text)
+}
+
+/// Describe a failure to load an image, telling a missing image apart from a broken one.
+pub(crate) fn image_error(
+    vfs: &BrushVfs,
+    format: DatasetFormat,
+    path: &Path,
+    err: anyhow::Error,
+) -> DatasetError {
+    let path = path.clean();
+    if vfs.file_names().any(|p| p == path) {
+        DatasetError::invalid_image(format, path, err)
+    } else {
+        DatasetError::MissingImage { format, path }
+    }
 }
 
 fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
@@ -96,6 +251,35 @@ fn find_mask_path(vfs: &BrushVfs, path: &Path) -> Option<PathBuf> {
     })
 }
 
+/// Pick the image out of a list of candidate paths, and find its mask if there is one.
+pub(crate) fn find_mask_and_img(
+    vfs: &BrushVfs,
+    paths: &[PathBuf],
+) -> anyhow::Result<(PathBuf, Option<PathBuf>)> {
+    let mut path_masks = HashMap::new();
+    let mut masks = vec![];
+
+    // First pass: collect images & masks.
+    for path in paths {
+        let mask = find_mask_path(vfs, path);
+        path_masks.insert(path.clone(), mask.clone());
+        if let Some(mask_path) = mask {
+            masks.push(mask_path);
+        }
+    }
+
+    // Remove masks from candidates - shouldn't count as an input image.
+    for mask in masks {
+        path_masks.remove(&mask);
+    }
+
+    // Sort and return the first candidate (alphabetically).
+    path_masks
+        .into_iter()
+        .min_by_key(|kv| kv.0.clone())
+        .context("No candidates found")
+}
+
 pub fn clamp_img_to_max_size(image: Arc<DynamicImage>, max_size: u32) -> Arc<DynamicImage> {
     if image.width() <= max_size && image.height() <= max_size {
         return image;
@@ -109,39 +293,69 @@ pub(crate) async fn load_image(
     mask_path: Option<&Path>,
 ) -> anyhow::Result<(DynamicImage, ViewImageType)> {
     let mut img_bytes = vec![];
-
     vfs.open_path(img_path)
         .await?
         .read_to_end(&mut img_bytes)
         .await?;
-    let mut img = image::load_from_memory(&img_bytes)?;
 
-    // Copy over mask
-    if let Some(mask_path) = mask_path {
+    let mask_bytes = if let Some(mask_path) = mask_path {
         let mut mask_bytes = vec![];
-
         vfs.open_path(mask_path)
             .await?
             .read_to_end(&mut mask_bytes)
             .await?;
+        Some(mask_bytes)
+    } else {
+        None
+    };
 
-        let mask_img = image::load_from_memory(&mask_bytes)?;
+    decode_image(&img_bytes, mask_bytes.as_deref())
+}
 
-        let mut img_masked = img.to_rgba8();
+/// Decode an image, and copy over the mask into the alpha channel if there is one.
+pub(crate) fn decode_image(
+    img_bytes: &[u8],
+    mask_bytes: Option<&[u8]>,
+) -> anyhow::Result<(DynamicImage, ViewImageType)> {
+    let mut img = image::load_from_memory(img_bytes)?;
 
-        if mask_img.color().has_alpha() {
-            let mask_img = mask_img.to_rgba8();
-            for (buf, mask) in img_masked.pixels_mut().zip(mask_img.pixels()) {
-                buf[3] = mask[0];
-            }
+    // Copy over mask
+    if let Some(mask_bytes) = mask_bytes {
+        let mask_img = image::load_from_memory(mask_bytes)?;
+
+        let mask: Vec<f32> = if mask_img.color().has_alpha() {
+            mask_img.to_rgba32f().pixels().map(|p| p[0]).collect()
         } else {
-            let mask_img = mask_img.grayscale().to_rgb8();
-            for (buf, mask) in img_masked.pixels_mut().zip(mask_img.pixels()) {
-                buf[3] = mask[0];
-            }
-        }
+            mask_img.grayscale().to_luma32f().into_raw()
+        };
 
-        img = img_masked.into();
+        // Keep the precision of 16 bit and float images.
+        img = match img {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                let mut img_masked = img.to_rgba32f();
+                for (buf, mask) in img_masked.pixels_mut().zip(&mask) {
+                    buf[3] = *mask;
+                }
+                img_masked.into()
+            }
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => {
+                let mut img_masked = img.to_rgba16();
+                for (buf, mask) in img_masked.pixels_mut().zip(&mask) {
+                    buf[3] = (mask * u16::MAX as f32).round() as u16;
+                }
+                img_masked.into()
+            }
+            _ => {
+                let mut img_masked = img.to_rgba8();
+                for (buf, mask) in img_masked.pixels_mut().zip(&mask) {
+                    buf[3] = (mask * u8::MAX as f32).round() as u8;
+                }
+                img_masked.into()
+            }
+        };
 
         Ok((img, ViewImageType::Masked))
     } else {
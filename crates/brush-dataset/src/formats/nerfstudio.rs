@@ -1,10 +1,13 @@
 use super::DataStream;
-use super::clamp_img_to_max_size;
+use super::ViewImageLoader;
 use super::find_mask_path;
-use super::load_image;
+use super::split_views;
+use super::{DatasetError, DatasetFormat, image_error, read_text};
 use crate::Dataset;
 use crate::LoadDataseConfig;
+use crate::LoadProgress;
 use crate::brush_vfs::BrushVfs;
+use crate::eval_split::EvalSplit;
 use crate::splat_import::SplatMessage;
 use crate::splat_import::load_splat_from_ply;
 use crate::stream_fut_parallel;
@@ -13,12 +16,10 @@ use anyhow::Result;
 use async_fn_stream::try_fn_stream;
 use brush_render::camera::fov_to_focal;
 use brush_render::camera::{Camera, focal_to_fov};
-use brush_train::scene::SceneView;
+use brush_train::scene::{SceneView, ViewMetadata};
 use burn::prelude::Backend;
 use std::future::Future;
 use std::path::Path;
-use std::sync::Arc;
-use tokio::io::AsyncReadExt;
 use tokio_stream::StreamExt;
 
 #[derive(serde::Deserialize, Clone)]
@@ -34,7 +35,7 @@ struct JsonScene {
     /// Focal length y
     fl_y: Option<f64>,
 
-    // Not really used atm.
+    // Only used for reporting atm.
     camera_model: Option<String>,
     // Nerfstudio doesn't mention this in their format? But fine to include really.
     ply_file_path: Option<String>,
@@ -100,23 +101,104 @@ struct FrameData {
     /// Second tangential distortion parameter used by [`OPENCV`]
     p2: Option<f64>,
 
+    /// Capture time, written for datasets of dynamic scenes.
+    time: Option<f64>,
+
     transform_matrix: Vec<Vec<f32>>,
     file_path: String,
 }
 
+/// The intrinsics a frame sets itself, to give frames with the same intrinsics the same camera id.
+type FrameIntrinsics = [Option<u64>; 8];
+
+impl FrameData {
+    fn intrinsics(&self) -> FrameIntrinsics {
+        [
+            self.camera_angle_x,
+            self.camera_angle_y,
+            self.fl_x,
+            self.fl_y,
+            self.cx,
+            self.cy,
+            self.w,
+            self.h,
+        ]
+        .map(|v| v.map(f64::to_bits))
+    }
+}
+
+fn has_distortion(params: [Option<f64>; 6]) -> bool {
+    params.into_iter().flatten().any(|p| p != 0.0)
+}
+
+impl JsonScene {
+    /// Whether any of the cameras has lens distortion, which Brush ignores.
+    fn is_distorted(&self) -> bool {
+        let model = self.camera_model.as_deref().unwrap_or_default();
+        model.contains("FISHEYE")
+            || has_distortion([self.k1, self.k2, self.k3, self.k4, self.p1, self.p2])
+            || self
+                .frames
+                .iter()
+                .any(|f| has_distortion([f.k1, f.k2, f.k3, f.k4, f.p1, f.p2]))
+    }
+}
+
+async fn read_scene(vfs: &mut BrushVfs, path: &Path) -> Result<JsonScene, DatasetError> {
+    let json = read_text(vfs, DatasetFormat::Nerfstudio, path).await?;
+    serde_json::from_str(&json).map_err(|e| {
+        let is_transforms = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with("transforms"));
+        // Other formats also come with json files, only complain about actual transforms files.
+        if is_transforms {
+            DatasetError::invalid_file(DatasetFormat::Nerfstudio, path, e)
+        } else {
+            DatasetError::not_found(
+                DatasetFormat::Nerfstudio,
+                format!("{path:?} is not a transforms file: {e}"),
+            )
+        }
+    })
+}
+
+/// Read the views of a transforms file. Frames using the intrinsics of the scene share camera id
+/// 0, frames with their own intrinsics get an id for each distinct set in `camera_intrinsics`.
 fn read_transforms_file(
     scene: JsonScene,
     transforms_path: &Path,
     vfs: BrushVfs,
+    images: &ViewImageLoader,
     load_args: &LoadDataseConfig,
+    camera_intrinsics: &mut Vec<FrameIntrinsics>,
 ) -> Vec<impl Future<Output = anyhow::Result<SceneView>> + use<>> {
+    let camera_ids: Vec<u32> = scene
+        .frames
+        .iter()
+        .map(|frame| {
+            let intrinsics = frame.intrinsics();
+            if intrinsics.iter().all(Option::is_none) {
+                return 0;
+            }
+            let index = camera_intrinsics
+                .iter()
+                .position(|i| *i == intrinsics)
+                .unwrap_or_else(|| {
+                    camera_intrinsics.push(intrinsics);
+                    camera_intrinsics.len() - 1
+                });
+            index as u32 + 1
+        })
+        .collect();
+
     let iter = scene
         .frames
         .into_iter()
+        .zip(camera_ids)
         .take(load_args.max_frames.unwrap_or(usize::MAX))
-        .map(move |frame| {
+        .map(move |(frame, camera_id)| {
             let mut archive = vfs.clone();
-            let load_args = load_args.clone();
+            let images = images.clone();
             let transforms_path = transforms_path.to_path_buf();
 
             async move {
@@ -142,16 +224,13 @@ fn read_transforms_file(
                 }
 
                 let mask_path = find_mask_path(&archive, &path);
-                let (image, img_type) = load_image(&mut archive, &path, mask_path.as_deref())
+                let (image, img_type, img_size, metadata) = images
+                    .load(&mut archive, &path, mask_path.as_deref())
                     .await
-                    .with_context(|| format!("Failed to load image {}", frame.file_path))?;
-
-                let image = Arc::new(image);
+                    .map_err(|e| image_error(&archive, DatasetFormat::Nerfstudio, &path, e))?;
 
-                let w = frame.w.or(scene.w).unwrap_or(image.width() as f64) as u32;
-                let h = frame.h.or(scene.h).unwrap_or(image.height() as f64) as u32;
-
-                let image = clamp_img_to_max_size(image, load_args.max_resolution);
+                let w = frame.w.or(scene.w).unwrap_or(img_size.x as f64) as u32;
+                let h = frame.h.or(scene.h).unwrap_or(img_size.y as f64) as u32;
 
                 let fovx = frame
                     .camera_angle_x
@@ -166,7 +245,11 @@ fn read_transforms_file(
                     .or(scene.fl_y.map(|fy| focal_to_fov(fy, h)));
 
                 let (fovx, fovy) = match (fovx, fovy) {
-                    (None, None) => anyhow::bail!("Must have some kind of focal length"),
+                    (None, None) => Err(DatasetError::invalid_file(
+                        DatasetFormat::Nerfstudio,
+                        &transforms_path,
+                        format!("Frame {} has no focal length", frame.file_path),
+                    ))?,
                     (None, Some(fovy)) => {
                         let fovx = focal_to_fov(fov_to_focal(fovy, h), w);
                         (fovx, fovy)
@@ -188,6 +271,11 @@ fn read_transforms_file(
                     camera: Camera::new(translation, rotation, fovx, fovy, cuv),
                     image,
                     img_type,
+                    metadata: ViewMetadata {
+                        timestamp: frame.time.or(metadata.timestamp),
+                        camera_id: Some(camera_id),
+                        ..metadata
+                    },
                 };
                 anyhow::Result::<SceneView>::Ok(view)
             }
@@ -199,6 +287,8 @@ fn read_transforms_file(
 pub async fn read_dataset<B: Backend>(
     mut vfs: BrushVfs,
     load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
     device: &B::Device,
 ) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
     log::info!("Loading nerfstudio dataset");
@@ -211,28 +301,45 @@ pub async fn read_dataset<B: Backend>(
     let transforms_path = if json_files.len() == 1 {
         json_files.first().cloned().expect("Must have 1 json file")
     } else {
-        let train = json_files.iter().find(|x| {
-            x.file_name()
-                .is_some_and(|p| p.to_string_lossy().contains("_train"))
-        });
+        let train = json_files
+            .iter()
+            .find(|x| {
+                x.file_name()
+                    .is_some_and(|p| p.to_string_lossy().contains("_train"))
+            })
+            .or_else(|| {
+                // Exports from Brush have a plain transforms.json next to a transforms_val.json.
+                json_files
+                    .iter()
+                    .find(|x| x.file_name().is_some_and(|p| p == "transforms.json"))
+            });
         let Some(train) = train else {
-            anyhow::bail!("No json file found.");
+            return Err(DatasetError::not_found(
+                DatasetFormat::Nerfstudio,
+                "No transforms json file found",
+            )
+            .into());
         };
         train.clone()
     };
 
-    let mut buf = String::new();
-    vfs.open_path(&transforms_path)
-        .await?
-        .read_to_string(&mut buf)
-        .await?;
-    let train_scene: JsonScene = serde_json::from_str(&buf)?;
+    let train_scene = read_scene(&mut vfs, &transforms_path).await?;
+    progress.add_camera_model(
+        train_scene.camera_model.as_deref().unwrap_or("PINHOLE"),
+        train_scene.is_distorted(),
+    );
+
+    // Shared between the train and eval views, so they use one image cache.
+    let images = ViewImageLoader::new(&vfs, load_args);
 
+    let mut camera_intrinsics = vec![];
     let mut train_handles = read_transforms_file(
         train_scene.clone(),
         &transforms_path,
         vfs.clone(),
+        &images,
         load_args,
+        &mut camera_intrinsics,
     );
 
     if let Some(subsample) = load_args.subsample_frames {
@@ -242,87 +349,43 @@ pub async fn read_dataset<B: Backend>(
             .collect();
     }
 
-    let load_args_clone = load_args.clone();
-
-    let mut data_clone = vfs.clone();
-
-    let dataset_stream = try_fn_stream(|emitter| async move {
-        let mut train_views = vec![];
-        let mut eval_views = vec![];
-
-        // Use transforms_val as eval, or _test if no _val is present. (Brush doesn't really have any notion of a test
-        let eval_trans_path = json_files
-            .iter()
-            .find(|x| {
+    // Use transforms_val as eval, or _test if no _val is present. (Brush doesn't really have any notion of a test
+    let eval_trans_path = json_files
+        .iter()
+        .find(|x| {
+            x.file_name()
+                .is_some_and(|p| p.to_string_lossy().contains("_val"))
+        })
+        .or_else(|| {
+            json_files.iter().find(|x| {
                 x.file_name()
-                    .is_some_and(|p| p.to_string_lossy().contains("_val"))
+                    .is_some_and(|p| p.to_string_lossy().contains("_test"))
             })
-            .or_else(|| {
-                json_files.iter().find(|x| {
-                    x.file_name()
-                        .is_some_and(|p| p.to_string_lossy().contains("_test"))
-                })
-            });
-
-        // If a separate eval file is specified, read it.
-        let val_stream = if let Some(eval_trans_path) = eval_trans_path {
-            let mut json_str = String::new();
-            data_clone
-                .open_path(eval_trans_path)
-                .await?
-                .read_to_string(&mut json_str)
-                .await?;
-            let val_scene = serde_json::from_str(&json_str)?;
-            Some(read_transforms_file(
-                val_scene,
-                eval_trans_path,
-                data_clone,
-                &load_args_clone,
-            ))
-        } else {
-            None
-        };
-
-        let train_handles = stream_fut_parallel(train_handles);
-        let mut train_handles = std::pin::pin!(train_handles);
-
-        let mut i = 0;
-        while let Some(view) = train_handles.next().await {
-            let view = view.context("Failed to load training view from json")?;
-
-            if let Some(eval_period) = load_args_clone.eval_split_every {
-                // Include extra eval images only when the dataset doesn't have them.
-                if i % eval_period == 0 && val_stream.is_some() {
-                    eval_views.push(view);
-                } else {
-                    train_views.push(view);
-                }
-            } else {
-                train_views.push(view);
-            }
-
-            emitter
-                .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
-                .await;
-
-            i += 1;
-        }
-
-        if let Some(val_stream) = val_stream {
-            let val_handles = stream_fut_parallel(val_stream);
-            let mut val_handles = std::pin::pin!(val_handles);
-            while let Some(view) = val_handles.next().await {
-                let view = view.context("Failed to load eval view from json")?;
+        });
 
-                eval_views.push(view);
-                emitter
-                    .emit(Dataset::from_views(train_views.clone(), eval_views.clone()))
-                    .await;
-            }
-        }
+    // If a separate eval file is specified, read it.
+    let eval_handles = if let Some(eval_trans_path) = eval_trans_path {
+        let val_scene = read_scene(&mut vfs, eval_trans_path).await?;
+        read_transforms_file(
+            val_scene,
+            eval_trans_path,
+            vfs.clone(),
+            &images,
+            load_args,
+            &mut camera_intrinsics,
+        )
+    } else {
+        vec![]
+    };
 
-        Ok(())
-    });
+    // The eval views of the dataset go last, to use them when splitting by the dataset. Any other
+    // split picks its eval views out of all views.
+    let num_dataset_eval = eval_handles.len();
+    let handles: Vec<_> = train_handles.into_iter().chain(eval_handles).collect();
+    let selector = split.selector(handles.len(), num_dataset_eval);
+    let views = stream_fut_parallel(handles, progress)
+        .map(|view| view.context("Failed to load view from json"));
+    let dataset_stream = split_views(views, selector);
 
     let device = device.clone();
     let load_args = load_args.clone();
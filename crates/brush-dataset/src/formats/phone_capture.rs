@@ -0,0 +1,643 @@
+//! Captures from phone apps (Polycam, `Record3D`) that export `ARKit` poses and `LiDAR` depth
+//! per frame, without any `SfM` points.
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    DataStream, DatasetError, DatasetFormat, ViewImageLoader, find_mask_and_img, image_error,
+    load_image, read_text, split_views,
+};
+use crate::{
+    Dataset, LoadDataseConfig, LoadProgress, brush_vfs::BrushVfs, eval_split::EvalSplit,
+    extra_properties::ExtraProperties, splat_import::SplatMessage, stream_fut_parallel,
+};
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::{
+    camera::{Camera, focal_to_fov},
+    gaussian_splats::Splats,
+    sh::rgb_to_sh,
+};
+use brush_train::scene::{SceneView, ViewMetadata};
+use burn::prelude::Backend;
+use glam::{Affine3A, Mat3, Quat, Vec3};
+use image::DynamicImage;
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio_stream::StreamExt;
+
+/// Max nr. of frames used to back-project the initial point cloud.
+const MAX_INIT_FRAMES: usize = 64;
+/// Roughly the nr. of points in the initial point cloud.
+const TARGET_INIT_POINTS: usize = 300_000;
+
+#[derive(Clone)]
+struct CaptureFrame {
+    image_path: PathBuf,
+    depth_path: Option<PathBuf>,
+    confidence_path: Option<PathBuf>,
+    width: u32,
+    height: u32,
+    /// Focal length in pixels, at the image resolution.
+    focal: (f64, f64),
+    /// Principal point in pixels, at the image resolution.
+    center: (f64, f64),
+    /// Camera to world transform in Brush's convention (+Z forward, +Y down).
+    cam_to_world: Affine3A,
+    /// Seconds since the start of the capture.
+    timestamp: Option<f64>,
+}
+
+/// Convert an `ARKit` camera to world transform (-Z forward, +Y up) to Brush's convention.
+fn from_arkit(mut cam_to_world: Affine3A) -> Affine3A {
+    cam_to_world.matrix3.y_axis *= -1.0;
+    cam_to_world.matrix3.z_axis *= -1.0;
+    cam_to_world
+}
+
+/// Find a file in a directory with one of the given names, with the given stem and extension.
+fn find_file(vfs: &BrushVfs, dirs: &[&str], stem: &str, extensions: &[&str]) -> Option<PathBuf> {
+    vfs.file_names().find(|p| {
+        let in_dir = p
+            .parent()
+            .and_then(|d| d.file_name())
+            .and_then(|d| d.to_str())
+            .is_some_and(|d| dirs.contains(&d));
+        let stem_matches = p.file_stem().and_then(|s| s.to_str()) == Some(stem);
+        let ext_matches = p
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| extensions.contains(&e.to_lowercase().as_str()));
+        in_dir && stem_matches && ext_matches
+    })
+}
+
+fn parent_dir_name(path: &Path) -> Option<&str> {
+    path.parent()?.file_name()?.to_str()
+}
+
+/// A Polycam camera json, one per keyframe.
+#[derive(Deserialize)]
+struct PolycamCamera {
+    fx: f64,
+    fy: f64,
+    cx: f64,
+    cy: f64,
+    width: u32,
+    height: u32,
+    // Row major 3x4 camera to world matrix.
+    t_00: f32,
+    t_01: f32,
+    t_02: f32,
+    t_03: f32,
+    t_10: f32,
+    t_11: f32,
+    t_12: f32,
+    t_13: f32,
+    t_20: f32,
+    t_21: f32,
+    t_22: f32,
+    t_23: f32,
+}
+
+/// Read a Polycam "raw data" export, which has a keyframes folder with images, cameras and depth.
+async fn read_polycam(vfs: &mut BrushVfs) -> Result<Vec<CaptureFrame>, DatasetError> {
+    let camera_paths: Vec<_> = vfs
+        .file_names()
+        .filter(|p| {
+            p.extension().is_some_and(|e| e == "json")
+                && matches!(parent_dir_name(p), Some("cameras" | "corrected_cameras"))
+        })
+        .collect();
+
+    // Prefer the corrected (undistorted and refined) keyframes if they exist.
+    let corrected = camera_paths
+        .iter()
+        .any(|p| parent_dir_name(p) == Some("corrected_cameras"));
+    let (camera_dir, image_dir) = if corrected {
+        ("corrected_cameras", "corrected_images")
+    } else {
+        ("cameras", "images")
+    };
+
+    let mut frames = vec![];
+    for path in camera_paths
+        .iter()
+        .filter(|p| parent_dir_name(p) == Some(camera_dir))
+    {
+        let json = read_text(vfs, DatasetFormat::PhoneCapture, path).await?;
+        let cam: PolycamCamera = serde_json::from_str(&json)
+            .map_err(|e| DatasetError::invalid_file(DatasetFormat::PhoneCapture, path, e))?;
+
+        let stem = path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| {
+            DatasetError::invalid_file(DatasetFormat::PhoneCapture, path, "Invalid camera path")
+        })?;
+
+        let Some(image_path) = find_file(vfs, &[image_dir], stem, &["jpg", "jpeg", "png"]) else {
+            log::warn!("No image found for Polycam camera {path:?}");
+            continue;
+        };
+
+        let rotation = Mat3::from_cols_array(&[
+            cam.t_00, cam.t_10, cam.t_20, cam.t_01, cam.t_11, cam.t_21, cam.t_02, cam.t_12,
+            cam.t_22,
+        ]);
+        let translation = Vec3::new(cam.t_03, cam.t_13, cam.t_23);
+
+        frames.push(CaptureFrame {
+            image_path,
+            depth_path: find_file(vfs, &["depth"], stem, &["png"]),
+            confidence_path: find_file(vfs, &["confidence"], stem, &["png"]),
+            width: cam.width,
+            height: cam.height,
+            focal: (cam.fx, cam.fy),
+            center: (cam.cx, cam.cy),
+            cam_to_world: from_arkit(Affine3A::from_mat3_translation(rotation, translation)),
+            timestamp: None,
+        });
+    }
+
+    if frames.is_empty() {
+        return Err(DatasetError::not_found(
+            DatasetFormat::PhoneCapture,
+            "No Polycam keyframes found",
+        ));
+    }
+    log::info!("Loading Polycam capture with {} keyframes", frames.len());
+    Ok(frames)
+}
+
+#[derive(Deserialize)]
+struct Record3dMetadata {
+    /// Width of the RGB images.
+    w: u32,
+    /// Height of the RGB images.
+    h: u32,
+    /// Column major intrinsics matrix.
+    #[serde(rename = "K")]
+    k: [f64; 9],
+    /// Per frame pose, as [qx, qy, qz, qw, tx, ty, tz].
+    poses: Vec<[f32; 7]>,
+    /// Frame rate of the capture.
+    fps: Option<f64>,
+}
+
+fn is_record3d_metadata(value: &serde_json::Value) -> bool {
+    ["w", "h", "K", "poses"]
+        .iter()
+        .all(|key| value.get(key).is_some())
+}
+
+/// Read a `Record3D` export, which has a metadata json and numbered RGB & depth frames.
+async fn read_record3d(vfs: &mut BrushVfs) -> Result<Vec<CaptureFrame>, DatasetError> {
+    // Other apps write metadata files too, only use one with the Record3D keys.
+    let candidates: Vec<_> = vfs
+        .file_names()
+        .filter(|p| {
+            p.file_stem().is_some_and(|s| s == "metadata")
+                && p.extension().is_none_or(|e| e == "json")
+        })
+        .collect();
+
+    let mut found = None;
+    for path in candidates {
+        let Ok(json) = read_text(vfs, DatasetFormat::PhoneCapture, &path).await else {
+            continue;
+        };
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&json) else {
+            continue;
+        };
+        if is_record3d_metadata(&value) {
+            found = Some((path, value));
+            break;
+        }
+    }
+
+    let (metadata_path, value) = found.ok_or_else(|| {
+        DatasetError::not_found(DatasetFormat::PhoneCapture, "No Record3D metadata found")
+    })?;
+    let metadata: Record3dMetadata = serde_json::from_value(value)
+        .map_err(|e| DatasetError::invalid_file(DatasetFormat::PhoneCapture, &metadata_path, e))?;
+
+    let k = metadata.k;
+    let (focal, center) = ((k[0], k[4]), (k[6], k[7]));
+
+    let mut warned_lzfse = false;
+    let mut frames = vec![];
+    for (i, pose) in metadata.poses.iter().enumerate() {
+        let stem = i.to_string();
+        let Some(image_path) = find_file(vfs, &["rgbd", "rgb"], &stem, &["jpg", "jpeg", "png"])
+        else {
+            continue;
+        };
+
+        // The raw .r3d export stores LZFSE compressed depth which can't be read here, the
+        // EXR + JPG export works.
+        let depth_path = find_file(vfs, &["rgbd", "depth"], &stem, &["exr", "png"]);
+        if depth_path.is_none()
+            && !warned_lzfse
+            && find_file(vfs, &["rgbd"], &stem, &["depth"]).is_some()
+        {
+            log::warn!(
+                "Record3D .depth files are not supported, export as EXR + JPG to use depth."
+            );
+            warned_lzfse = true;
+        }
+
+        let [qx, qy, qz, qw, tx, ty, tz] = *pose;
+        let cam_to_world = Affine3A::from_rotation_translation(
+            Quat::from_xyzw(qx, qy, qz, qw).normalize(),
+            Vec3::new(tx, ty, tz),
+        );
+
+        frames.push(CaptureFrame {
+            image_path,
+            depth_path,
+            confidence_path: find_file(vfs, &["conf", "confidence"], &stem, &["png"]),
+            width: metadata.w,
+            height: metadata.h,
+            focal,
+            center,
+            cam_to_world: from_arkit(cam_to_world),
+            timestamp: metadata
+                .fps
+                .filter(|fps| *fps > 0.0)
+                .map(|fps| i as f64 / fps),
+        });
+    }
+
+    if frames.is_empty() {
+        return Err(DatasetError::invalid_file(
+            DatasetFormat::PhoneCapture,
+            metadata_path,
+            "No Record3D frames found",
+        ));
+    }
+    log::info!("Loading Record3D capture with {} frames", frames.len());
+    Ok(frames)
+}
+
+/// Read a depth image as metric depth. 16 bit integer depth is in millimeters, float depth in meters.
+fn depth_values(depth: DynamicImage) -> Vec<f32> {
+    match depth {
+        DynamicImage::ImageRgb32F(img) => img.pixels().map(|p| p[0]).collect(),
+        DynamicImage::ImageRgba32F(img) => img.pixels().map(|p| p[0]).collect(),
+        depth => depth
+            .into_luma16()
+            .pixels()
+            .map(|p| p[0] as f32 / 1000.0)
+            .collect(),
+    }
+}
+
+/// Back-project a depth map to world space points, with colors sampled from the image.
+fn back_project(
+    frame: &CaptureFrame,
+    image: &DynamicImage,
+    depth: DynamicImage,
+    confidence: Option<DynamicImage>,
+    stride: u32,
+) -> Vec<(Vec3, Vec3)> {
+    let (dw, dh) = (depth.width(), depth.height());
+    let depth = depth_values(depth);
+    let confidence = confidence.map(|c| c.into_luma8());
+    // Only keep the most confident depth values, confidence levels differ per app.
+    let max_confidence = confidence
+        .as_ref()
+        .and_then(|c| c.pixels().map(|p| p[0]).max());
+
+    let image = image.to_rgb8();
+    let (iw, ih) = (image.width(), image.height());
+
+    // Intrinsics at the depth resolution.
+    let sx = dw as f64 / frame.width as f64;
+    let sy = dh as f64 / frame.height as f64;
+    let (fx, fy) = (frame.focal.0 * sx, frame.focal.1 * sy);
+    let (cx, cy) = (frame.center.0 * sx, frame.center.1 * sy);
+
+    let mut points = vec![];
+    for y in (0..dh).step_by(stride as usize) {
+        for x in (0..dw).step_by(stride as usize) {
+            let d = depth[(y * dw + x) as usize];
+            if !d.is_finite() || d <= 0.0 {
+                continue;
+            }
+
+            if let (Some(conf), Some(max)) = (&confidence, max_confidence) {
+                // Confidence maps might not be the same size as the depth map.
+                let cx = x * conf.width() / dw;
+                let cy = y * conf.height() / dh;
+                if conf.get_pixel(cx, cy)[0] < max {
+                    continue;
+                }
+            }
+
+            let local = Vec3::new(
+                ((x as f64 + 0.5 - cx) / fx) as f32 * d,
+                ((y as f64 + 0.5 - cy) / fy) as f32 * d,
+                d,
+            );
+            let world = frame.cam_to_world.transform_point3(local);
+
+            let px = ((x as f32 + 0.5) / dw as f32 * iw as f32) as u32;
+            let py = ((y as f32 + 0.5) / dh as f32 * ih as f32) as u32;
+            let rgb = image.get_pixel(px.min(iw - 1), py.min(ih - 1));
+            let color = Vec3::new(rgb[0] as f32, rgb[1] as f32, rgb[2] as f32) / 255.0;
+
+            points.push((world, color));
+        }
+    }
+    points
+}
+
+/// Load the depth of a frame and back-project roughly `target_points` points from it.
+async fn frame_points(
+    vfs: &mut BrushVfs,
+    frame: &CaptureFrame,
+    target_points: usize,
+    subsample: u32,
+) -> Result<Vec<(Vec3, Vec3)>> {
+    let depth_path = frame.depth_path.as_ref().context("Frame has no depth")?;
+    let (image, _) = load_image(vfs, &frame.image_path, None).await?;
+
+    let mut depth_bytes = vec![];
+    vfs.open_path(depth_path)
+        .await?
+        .read_to_end(&mut depth_bytes)
+        .await?;
+    let depth = image::load_from_memory(&depth_bytes)
+        .with_context(|| format!("Failed to read depth {depth_path:?}"))?;
+
+    let confidence = if let Some(conf_path) = &frame.confidence_path {
+        let mut conf_bytes = vec![];
+        vfs.open_path(conf_path)
+            .await?
+            .read_to_end(&mut conf_bytes)
+            .await?;
+        image::load_from_memory(&conf_bytes).ok()
+    } else {
+        None
+    };
+
+    let pixels = (depth.width() * depth.height()) as usize;
+    let stride =
+        ((pixels as f32 / target_points.max(1) as f32).sqrt().ceil() as u32).max(1) * subsample;
+    Ok(back_project(frame, &image, depth, confidence, stride))
+}
+
+fn read_views(
+    vfs: BrushVfs,
+    frames: &[CaptureFrame],
+    load_args: &LoadDataseConfig,
+) -> Vec<impl Future<Output = Result<SceneView>> + use<>> {
+    let images = ViewImageLoader::new(&vfs, load_args);
+
+    frames
+        .iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .cloned()
+        .map(move |frame| {
+            let mut vfs = vfs.clone();
+            let images = images.clone();
+
+            async move {
+                let (path, mask_path) =
+                    find_mask_and_img(&vfs, std::slice::from_ref(&frame.image_path)).map_err(
+                        |_| DatasetError::MissingImage {
+                            format: DatasetFormat::PhoneCapture,
+                            path: frame.image_path.clone(),
+                        },
+                    )?;
+                let (image, img_type, _, metadata) = images
+                    .load(&mut vfs, &path, mask_path.as_deref())
+                    .await
+                    .map_err(|e| image_error(&vfs, DatasetFormat::PhoneCapture, &path, e))?;
+
+                let fovx = focal_to_fov(frame.focal.0, frame.width);
+                let fovy = focal_to_fov(frame.focal.1, frame.height);
+                let center_uv = glam::vec2(
+                    (frame.center.0 / frame.width as f64) as f32,
+                    (frame.center.1 / frame.height as f64) as f32,
+                );
+
+                let (_, rotation, translation) = frame.cam_to_world.to_scale_rotation_translation();
+                let camera = Camera::new(translation, rotation, fovx, fovy, center_uv);
+
+                Ok(SceneView {
+                    path: path.to_string_lossy().to_string(),
+                    camera,
+                    image,
+                    img_type,
+                    metadata: ViewMetadata {
+                        timestamp: frame.timestamp.or(metadata.timestamp),
+                        ..metadata
+                    },
+                })
+            }
+        })
+        .collect()
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    let mut frames = match read_polycam(&mut vfs).await {
+        Err(DatasetError::NotFound { .. }) => {
+            read_record3d(&mut vfs).await.map_err(|e| match e {
+                DatasetError::NotFound { .. } => DatasetError::not_found(
+                    DatasetFormat::PhoneCapture,
+                    "No Polycam keyframes or Record3D metadata found",
+                ),
+                e => e,
+            })?
+        }
+        frames => frames?,
+    };
+
+    frames.sort_by(|a, b| a.image_path.cmp(&b.image_path));
+    // Both apps write pinhole intrinsics without distortion.
+    progress.add_camera_model("PINHOLE", false);
+
+    if let Some(subsample) = load_args.subsample_frames {
+        frames = frames.into_iter().step_by(subsample as usize).collect();
+    }
+
+    let handles = read_views(vfs.clone(), &frames, load_args);
+
+    let selector = split.selector(handles.len(), 0);
+    let views = stream_fut_parallel(handles, progress)
+        .map(|view| view.context("Failed to load capture view"));
+    let stream = split_views(views, selector);
+
+    let depth_frames: Vec<_> = frames
+        .into_iter()
+        .filter(|f| f.depth_path.is_some())
+        .collect();
+    let frame_step = depth_frames.len().div_ceil(MAX_INIT_FRAMES).max(1);
+    let depth_frames: Vec<_> = depth_frames.into_iter().step_by(frame_step).collect();
+    let subsample_points = load_args.subsample_points.unwrap_or(1).max(1);
+    let device = device.clone();
+
+    let init_stream = try_fn_stream(|emitter| async move {
+        if depth_frames.is_empty() {
+            return Ok(());
+        }
+
+        let points_per_frame = TARGET_INIT_POINTS / depth_frames.len();
+
+        let mut positions = vec![];
+        let mut colors = vec![];
+
+        for frame in &depth_frames {
+            // A single broken depth frame shouldn't prevent initializing from the others.
+            match frame_points(&mut vfs, frame, points_per_frame, subsample_points).await {
+                Ok(points) => {
+                    for (pos, color) in points {
+                        positions.push(pos);
+                        let sh = rgb_to_sh(color);
+                        colors.extend([sh.x, sh.y, sh.z]);
+                    }
+                }
+                Err(e) => log::warn!("Skipping depth of {:?}: {e:#}", frame.image_path),
+            }
+        }
+
+        if positions.is_empty() {
+            return Ok(());
+        }
+
+        log::info!(
+            "Starting from {} points back-projected from depth",
+            positions.len()
+        );
+
+        let init_splat = Splats::from_raw(&positions, None, None, Some(&colors), None, &device);
+        emitter
+            .emit(SplatMessage {
+                meta: crate::splat_import::ParseMetadata {
+                    up_axis: None,
+                    total_splats: init_splat.num_splats(),
+                    frame_count: 1,
+                    current_frame: 0,
+                },
+                splats: init_splat,
+                extra_properties: ExtraProperties::default(),
+            })
+            .await;
+
+        Ok(())
+    });
+
+    Ok((Box::pin(init_stream), Box::pin(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptureFrame, back_project, depth_values};
+    use glam::{Affine3A, Vec3};
+    use image::{DynamicImage, ImageBuffer, Luma, Rgb, RgbImage};
+
+    fn frame() -> CaptureFrame {
+        CaptureFrame {
+            image_path: "0.jpg".into(),
+            depth_path: None,
+            confidence_path: None,
+            width: 4,
+            height: 4,
+            focal: (2.0, 2.0),
+            center: (2.0, 2.0),
+            cam_to_world: Affine3A::from_translation(Vec3::Z),
+            timestamp: None,
+        }
+    }
+
+    /// A half resolution depth map with one missing and one invalid value.
+    fn depth() -> DynamicImage {
+        let values = [1.0, 0.0, 2.0, f32::NAN];
+        DynamicImage::ImageRgb32F(ImageBuffer::from_fn(2, 2, |x, y| {
+            Rgb([values[(y * 2 + x) as usize]; 3])
+        }))
+    }
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(4, 4, |x, y| {
+            Rgb([x as u8 * 60, y as u8 * 60, 255])
+        }))
+    }
+
+    #[test]
+    fn depth_units() {
+        let millimeters = ImageBuffer::from_raw(2, 1, vec![1500u16, 0]).expect("Invalid image");
+        assert_eq!(
+            depth_values(DynamicImage::ImageLuma16(millimeters)),
+            vec![1.5, 0.0],
+            "Integer depth is in millimeters"
+        );
+        let meters = ImageBuffer::from_fn(2, 1, |x, _| Rgb([x as f32 + 0.5, 9.0, 9.0]));
+        assert_eq!(
+            depth_values(DynamicImage::ImageRgb32F(meters)),
+            vec![0.5, 1.5],
+            "Float depth is in meters, from the first channel"
+        );
+    }
+
+    #[test]
+    fn back_project_depth() {
+        let points = back_project(&frame(), &image(), depth(), None, 1);
+        let expected = [
+            (
+                Vec3::new(-0.5, -0.5, 2.0),
+                Vec3::new(60.0, 60.0, 255.0) / 255.0,
+            ),
+            (
+                Vec3::new(-1.0, 1.0, 3.0),
+                Vec3::new(60.0, 180.0, 255.0) / 255.0,
+            ),
+        ];
+        assert_eq!(
+            points.len(),
+            2,
+            "Missing and invalid depth should be skipped"
+        );
+        for ((pos, color), (expected_pos, expected_color)) in points.iter().zip(expected) {
+            assert!(
+                pos.abs_diff_eq(expected_pos, 1e-6),
+                "Wrong position {pos}, expected {expected_pos}"
+            );
+            assert!(
+                color.abs_diff_eq(expected_color, 1e-6),
+                "Wrong color {color}, expected {expected_color}"
+            );
+        }
+
+        let points = back_project(&frame(), &image(), depth(), None, 2);
+        assert_eq!(points.len(), 1, "Stride should skip pixels");
+
+        let confidence =
+            ImageBuffer::from_fn(2, 2, |x, y| Luma([if (x, y) == (0, 1) { 2u8 } else { 1 }]));
+        let points = back_project(
+            &frame(),
+            &image(),
+            depth(),
+            Some(DynamicImage::ImageLuma8(confidence)),
+            1,
+        );
+        assert_eq!(
+            points.len(),
+            1,
+            "Only the most confident depth should be kept"
+        );
+        assert!(
+            points[0].0.abs_diff_eq(Vec3::new(-1.0, 1.0, 3.0), 1e-6),
+            "Wrong confident point {}",
+            points[0].0
+        );
+    }
+}
@@ -0,0 +1,779 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    DataStream, DatasetError, DatasetFormat, ViewImageLoader, find_mask_and_img, read_text,
+    split_views,
+};
+use crate::{
+    Dataset, LoadDataseConfig, LoadProgress, brush_vfs::BrushVfs, eval_split::EvalSplit,
+    extra_properties::ExtraProperties, splat_import::SplatMessage, stream_fut_parallel,
+};
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::{
+    camera::{Camera, focal_to_fov},
+    gaussian_splats::Splats,
+    sh::rgb_to_sh,
+};
+use brush_train::scene::{SceneView, ViewMetadata};
+use burn::prelude::Backend;
+use glam::{DMat3, DVec3, Vec3};
+use serde::Deserialize;
+use tokio_stream::StreamExt;
+
+/// `AliceVision` writes all numbers as strings, so accept both.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "NumberOrString")]
+struct Num(f64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(f64),
+    String(String),
+}
+
+impl TryFrom<NumberOrString> for Num {
+    type Error = std::num::ParseFloatError;
+
+    fn try_from(value: NumberOrString) -> Result<Self, Self::Error> {
+        match value {
+            NumberOrString::Number(v) => Ok(Self(v)),
+            NumberOrString::String(s) => s.trim().parse().map(Self),
+        }
+    }
+}
+
+// OpenMVG sfm_data.json. Everything is stored as key/value lists, and polymorphic types
+// (intrinsics) are wrapped in a pointer wrapper.
+
+#[derive(Deserialize)]
+struct MvgEntry<T> {
+    key: u64,
+    value: T,
+}
+
+#[derive(Deserialize)]
+struct MvgPtr<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct MvgViewValue {
+    ptr_wrapper: MvgPtr<MvgView>,
+}
+
+#[derive(Deserialize)]
+struct MvgView {
+    #[serde(default)]
+    local_path: String,
+    filename: String,
+    id_intrinsic: u64,
+    id_pose: u64,
+}
+
+#[derive(Deserialize)]
+struct MvgIntrinsicValue {
+    polymorphic_id: u64,
+    // Only written for the first intrinsic of each type.
+    polymorphic_name: Option<String>,
+    ptr_wrapper: MvgPtr<MvgIntrinsic>,
+}
+
+#[derive(Deserialize)]
+struct MvgIntrinsic {
+    width: f64,
+    height: f64,
+    focal_length: f64,
+    principal_point: [f64; 2],
+    disto_k1: Option<Vec<f64>>,
+    disto_k3: Option<Vec<f64>>,
+    disto_t2: Option<Vec<f64>>,
+}
+
+#[derive(Deserialize)]
+struct MvgPose {
+    /// World to camera rotation, row major.
+    rotation: [[f64; 3]; 3],
+    center: [f64; 3],
+}
+
+#[derive(Deserialize)]
+struct MvgLandmark {
+    #[serde(rename = "X")]
+    position: [f64; 3],
+}
+
+#[derive(Deserialize)]
+struct MvgSfm {
+    views: Vec<MvgEntry<MvgViewValue>>,
+    intrinsics: Vec<MvgEntry<MvgIntrinsicValue>>,
+    extrinsics: Vec<MvgEntry<MvgPose>>,
+    #[serde(default)]
+    structure: Vec<MvgEntry<MvgLandmark>>,
+}
+
+// AliceVision (Meshroom) cameras.sfm / sfm.json.
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AvView {
+    pose_id: String,
+    intrinsic_id: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AvFocal {
+    Single(Num),
+    Pair([Num; 2]),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AvIntrinsic {
+    intrinsic_id: String,
+    width: Num,
+    height: Num,
+    #[serde(rename = "type")]
+    kind: String,
+    /// Focal length in pixels, used before version 1.2.0.
+    px_focal_length: Option<AvFocal>,
+    /// Focal length in mm, used since version 1.2.0.
+    focal_length: Option<Num>,
+    sensor_width: Option<Num>,
+    pixel_ratio: Option<Num>,
+    principal_point: [Num; 2],
+    #[serde(default)]
+    distortion_params: Vec<Num>,
+}
+
+#[derive(Deserialize)]
+struct AvTransform {
+    /// World to camera rotation, column major.
+    rotation: [Num; 9],
+    center: [Num; 3],
+}
+
+#[derive(Deserialize)]
+struct AvPoseData {
+    transform: AvTransform,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AvPose {
+    pose_id: String,
+    pose: AvPoseData,
+}
+
+#[derive(Deserialize)]
+struct AvLandmark {
+    #[serde(rename = "X")]
+    position: [Num; 3],
+    color: Option<[Num; 3]>,
+}
+
+#[derive(Deserialize)]
+struct AvSfm {
+    #[serde(default)]
+    version: Vec<Num>,
+    #[serde(default)]
+    views: Vec<AvView>,
+    #[serde(default)]
+    intrinsics: Vec<AvIntrinsic>,
+    #[serde(default)]
+    poses: Vec<AvPose>,
+    #[serde(default)]
+    structure: Vec<AvLandmark>,
+}
+
+/// Common representation of both flavours of sfm data.
+#[derive(Clone)]
+struct SfmIntrinsic {
+    width: u32,
+    height: u32,
+    focal: (f64, f64),
+    center: (f64, f64),
+    model: String,
+    distorted: bool,
+}
+
+#[derive(Clone, Copy)]
+struct SfmPose {
+    world_to_cam: DMat3,
+    center: DVec3,
+}
+
+struct SfmView {
+    /// Path of the image, relative to some unknown root.
+    path: PathBuf,
+    intrinsic: String,
+    pose: String,
+}
+
+struct SfmScene {
+    intrinsics: HashMap<String, SfmIntrinsic>,
+    poses: HashMap<String, SfmPose>,
+    views: Vec<SfmView>,
+    points: Vec<Vec3>,
+    colors: Option<Vec<Vec3>>,
+}
+
+/// Whether a camera model can be represented as a pinhole camera, ignoring distortion.
+fn is_pinhole_model(name: &str) -> bool {
+    let name = name.to_lowercase();
+    !["fisheye", "equidistant", "spherical", "equirectangular"]
+        .iter()
+        .any(|m| name.contains(m))
+}
+
+fn has_distortion(params: &[f64]) -> bool {
+    params.iter().any(|p| p.abs() > 1e-6)
+}
+
+fn convert_openmvg(sfm: MvgSfm) -> SfmScene {
+    // Polymorphic names are only written once, later intrinsics of the same type refer to the id.
+    let mut type_names = HashMap::new();
+    let mut intrinsics = HashMap::new();
+
+    for entry in sfm.intrinsics {
+        let type_id = entry.value.polymorphic_id & 0x7fff_ffff;
+        if let Some(name) = entry.value.polymorphic_name {
+            type_names.insert(type_id, name);
+        }
+        let name = type_names.get(&type_id).cloned().unwrap_or_default();
+
+        if !is_pinhole_model(&name) {
+            log::warn!(
+                "Skipping OpenMVG intrinsic {} with unsupported model {name}",
+                entry.key
+            );
+            continue;
+        }
+
+        let data = entry.value.ptr_wrapper.data;
+        let distortion: Vec<f64> = [data.disto_k1, data.disto_k3, data.disto_t2]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+
+        intrinsics.insert(
+            entry.key.to_string(),
+            SfmIntrinsic {
+                width: data.width as u32,
+                height: data.height as u32,
+                focal: (data.focal_length, data.focal_length),
+                center: (data.principal_point[0], data.principal_point[1]),
+                model: name,
+                distorted: has_distortion(&distortion),
+            },
+        );
+    }
+
+    let poses = sfm
+        .extrinsics
+        .into_iter()
+        .map(|entry| {
+            let r = entry.value.rotation;
+            let pose = SfmPose {
+                world_to_cam: DMat3::from_cols_array_2d(&r).transpose(),
+                center: DVec3::from_array(entry.value.center),
+            };
+            (entry.key.to_string(), pose)
+        })
+        .collect();
+
+    let views = sfm
+        .views
+        .into_iter()
+        .map(|entry| {
+            let view = entry.value.ptr_wrapper.data;
+            SfmView {
+                path: Path::new(&view.local_path).join(&view.filename),
+                intrinsic: view.id_intrinsic.to_string(),
+                pose: view.id_pose.to_string(),
+            }
+        })
+        .collect();
+
+    let points = sfm
+        .structure
+        .iter()
+        .map(|entry| DVec3::from_array(entry.value.position).as_vec3())
+        .collect();
+
+    SfmScene {
+        intrinsics,
+        poses,
+        views,
+        points,
+        // OpenMVG doesn't store colors in the structure.
+        colors: None,
+    }
+}
+
+fn convert_alicevision(sfm: AvSfm) -> SfmScene {
+    let version: Vec<u32> = sfm.version.iter().map(|v| v.0 as u32).collect();
+    // Since 1.2.0 the principal point is stored as an offset from the image center.
+    let centered_principal_point = version.as_slice() >= [1, 2, 0].as_slice();
+
+    let mut intrinsics = HashMap::new();
+    for intrinsic in sfm.intrinsics {
+        if !is_pinhole_model(&intrinsic.kind) {
+            log::warn!(
+                "Skipping AliceVision intrinsic {} with unsupported model {}",
+                intrinsic.intrinsic_id,
+                intrinsic.kind
+            );
+            continue;
+        }
+
+        let width = intrinsic.width.0;
+        let height = intrinsic.height.0;
+
+        let focal = match (intrinsic.px_focal_length, intrinsic.focal_length) {
+            (Some(AvFocal::Single(f)), _) => (f.0, f.0),
+            (Some(AvFocal::Pair([fx, fy])), _) => (fx.0, fy.0),
+            (None, Some(focal_mm)) => {
+                let sensor_width = intrinsic.sensor_width.map_or(36.0, |s| s.0);
+                let fx = focal_mm.0 * width / sensor_width;
+                let pixel_ratio = intrinsic.pixel_ratio.map_or(1.0, |r| r.0);
+                (fx, fx / pixel_ratio)
+            }
+            (None, None) => {
+                log::warn!(
+                    "Skipping AliceVision intrinsic {} without focal length",
+                    intrinsic.intrinsic_id
+                );
+                continue;
+            }
+        };
+
+        let [cx, cy] = intrinsic.principal_point.map(|p| p.0);
+        let center = if centered_principal_point {
+            (width / 2.0 + cx, height / 2.0 + cy)
+        } else {
+            (cx, cy)
+        };
+
+        let distortion: Vec<f64> = intrinsic.distortion_params.iter().map(|d| d.0).collect();
+
+        intrinsics.insert(
+            intrinsic.intrinsic_id,
+            SfmIntrinsic {
+                width: width as u32,
+                height: height as u32,
+                focal,
+                center,
+                model: intrinsic.kind,
+                distorted: has_distortion(&distortion),
+            },
+        );
+    }
+
+    let poses = sfm
+        .poses
+        .into_iter()
+        .map(|pose| {
+            let transform = pose.pose.transform;
+            let pose_data = SfmPose {
+                world_to_cam: DMat3::from_cols_array(&transform.rotation.map(|r| r.0)),
+                center: DVec3::from_array(transform.center.map(|c| c.0)),
+            };
+            (pose.pose_id, pose_data)
+        })
+        .collect();
+
+    let views = sfm
+        .views
+        .into_iter()
+        .map(|view| SfmView {
+            // Paths are absolute on the machine that ran the reconstruction. Only the file name
+            // is useful here.
+            path: Path::new(&view.path)
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+            intrinsic: view.intrinsic_id,
+            pose: view.pose_id,
+        })
+        .collect();
+
+    let points = sfm
+        .structure
+        .iter()
+        .map(|l| DVec3::from_array(l.position.map(|p| p.0)).as_vec3())
+        .collect();
+
+    let colors = sfm
+        .structure
+        .iter()
+        .map(|l| {
+            l.color
+                .map(|c| Vec3::from_array(c.map(|v| (v.0 / 255.0) as f32)))
+        })
+        .collect();
+
+    SfmScene {
+        intrinsics,
+        poses,
+        views,
+        points,
+        colors,
+    }
+}
+
+fn parse_sfm_json(json: &str) -> Result<SfmScene> {
+    let value: serde_json::Value = serde_json::from_str(json).context("Invalid JSON")?;
+
+    if value.get("sfm_data_version").is_some() {
+        let sfm: MvgSfm = serde_json::from_value(value).context("Invalid OpenMVG sfm data")?;
+        Ok(convert_openmvg(sfm))
+    } else if value.get("poses").is_some() || value.get("featuresFolders").is_some() {
+        let sfm: AvSfm = serde_json::from_value(value).context("Invalid AliceVision sfm data")?;
+        Ok(convert_alicevision(sfm))
+    } else {
+        anyhow::bail!("Not an OpenMVG or AliceVision sfm file")
+    }
+}
+
+async fn read_scene(vfs: &mut BrushVfs) -> Result<SfmScene, DatasetError> {
+    let candidates: Vec<_> = vfs
+        .file_names()
+        .filter(|p| {
+            p.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("sfm") || ext.eq_ignore_ascii_case("json")
+            })
+        })
+        .collect();
+
+    let mut errors = vec![];
+    for path in candidates {
+        let json = read_text(vfs, DatasetFormat::SfmJson, &path).await?;
+
+        // Quick check to skip unrelated json files.
+        if !json.contains("\"views\"") || !json.contains("\"intrinsics\"") {
+            continue;
+        }
+
+        match parse_sfm_json(&json) {
+            Ok(scene) => {
+                log::info!("Loading sfm data from {path:?}");
+                return Ok(scene);
+            }
+            Err(e) => errors.push(DatasetError::invalid_file(DatasetFormat::SfmJson, path, e)),
+        }
+    }
+
+    let mut errors = errors.into_iter();
+    let Some(first) = errors.next() else {
+        return Err(DatasetError::not_found(
+            DatasetFormat::SfmJson,
+            "No OpenMVG or AliceVision sfm file found",
+        ));
+    };
+    for other in errors {
+        log::warn!("Also failed to parse sfm file: {other}");
+    }
+    Err(first)
+}
+
+fn read_views(
+    vfs: BrushVfs,
+    scene: &SfmScene,
+    load_args: &LoadDataseConfig,
+    progress: &LoadProgress,
+) -> Vec<impl Future<Output = Result<SceneView>>> {
+    let images = ViewImageLoader::new(&vfs, load_args);
+
+    for intrinsic in scene.intrinsics.values() {
+        progress.add_camera_model(&intrinsic.model, intrinsic.distorted);
+    }
+
+    let mut views: Vec<_> = scene
+        .views
+        .iter()
+        .filter_map(|view| {
+            // Views without a pose weren't reconstructed.
+            let pose = scene.poses.get(&view.pose)?;
+            let intrinsic = scene.intrinsics.get(&view.intrinsic)?;
+            Some((
+                view.path.clone(),
+                *pose,
+                intrinsic.clone(),
+                view.intrinsic.parse().ok(),
+            ))
+        })
+        .collect();
+
+    if views.len() < scene.views.len() {
+        log::warn!(
+            "Skipping {} sfm views without pose or supported intrinsics",
+            scene.views.len() - views.len()
+        );
+    }
+
+    if views.iter().any(|(_, _, intrinsic, _)| intrinsic.distorted) {
+        log::warn!("Sfm cameras have lens distortion, which is ignored. Results may be off.");
+    }
+
+    views.sort_by(|a, b| a.0.cmp(&b.0));
+
+    log::info!("Loading sfm dataset with {} views", views.len());
+
+    views
+        .into_iter()
+        .take(load_args.max_frames.unwrap_or(usize::MAX))
+        .map(move |(img_path, pose, intrinsic, camera_id)| {
+            let mut vfs = vfs.clone();
+            let images = images.clone();
+
+            async move {
+                let img_paths: Vec<_> = vfs
+                    .file_names()
+                    .filter(|p| p.ends_with(&img_path) || p.file_name() == img_path.file_name())
+                    .collect();
+
+                let (path, mask_path) = find_mask_and_img(&vfs, &img_paths).map_err(|_| {
+                    DatasetError::MissingImage {
+                        format: DatasetFormat::SfmJson,
+                        path: img_path.clone(),
+                    }
+                })?;
+
+                let (image, img_type, _, metadata) = images
+                    .load(&mut vfs, &path, mask_path.as_deref())
+                    .await
+                    .map_err(|e| DatasetError::invalid_image(DatasetFormat::SfmJson, &path, e))?;
+
+                let fovx = focal_to_fov(intrinsic.focal.0, intrinsic.width);
+                let fovy = focal_to_fov(intrinsic.focal.1, intrinsic.height);
+                let center_uv = glam::vec2(
+                    (intrinsic.center.0 / intrinsic.width as f64) as f32,
+                    (intrinsic.center.1 / intrinsic.height as f64) as f32,
+                );
+
+                // Both OpenMVG and AliceVision use the same camera convention as Brush.
+                let rotation = glam::DQuat::from_mat3(&pose.world_to_cam.transpose()).as_quat();
+                let camera = Camera::new(pose.center.as_vec3(), rotation, fovx, fovy, center_uv);
+
+                Ok(SceneView {
+                    path: path.to_string_lossy().to_string(),
+                    camera,
+                    image,
+                    img_type,
+                    metadata: ViewMetadata {
+                        camera_id,
+                        ..metadata
+                    },
+                })
+            }
+        })
+        .collect()
+}
+
+pub(crate) async fn load_dataset<B: Backend>(
+    mut vfs: BrushVfs,
+    load_args: &LoadDataseConfig,
+    split: &EvalSplit,
+    progress: &LoadProgress,
+    device: &B::Device,
+) -> Result<(DataStream<SplatMessage<B>>, DataStream<Dataset>)> {
+    let scene = read_scene(&mut vfs).await?;
+
+    let mut handles = read_views(vfs, &scene, load_args, progress);
+
+    if let Some(subsample) = load_args.subsample_frames {
+        handles = handles.into_iter().step_by(subsample as usize).collect();
+    }
+
+    let selector = split.selector(handles.len(), 0);
+    let views =
+        stream_fut_parallel(handles, progress).map(|view| view.context("Failed to load sfm view"));
+    let stream = split_views(views, selector);
+
+    let subsample_points = load_args.subsample_points.unwrap_or(1).max(1) as usize;
+    let points: Vec<Vec3> = scene.points.into_iter().step_by(subsample_points).collect();
+    let colors: Option<Vec<Vec3>> = scene
+        .colors
+        .map(|c| c.into_iter().step_by(subsample_points).collect());
+    let device = device.clone();
+
+    let init_stream = try_fn_stream(|emitter| async move {
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        log::info!("Starting from sfm structure points {}", points.len());
+
+        let sh_coeffs: Option<Vec<f32>> = colors.map(|colors| {
+            colors
+                .into_iter()
+                .flat_map(|c| {
+                    let sh = rgb_to_sh(c);
+                    [sh.x, sh.y, sh.z]
+                })
+                .collect()
+        });
+
+        let init_splat = Splats::from_raw(&points, None, None, sh_coeffs.as_deref(), None, &device);
+        emitter
+            .emit(SplatMessage {
+                meta: crate::splat_import::ParseMetadata {
+                    up_axis: None,
+                    total_splats: init_splat.num_splats(),
+                    frame_count: 1,
+                    current_frame: 0,
+                },
+                splats: init_splat,
+                extra_properties: ExtraProperties::default(),
+            })
+            .await;
+
+        Ok(())
+    });
+
+    Ok((Box::pin(init_stream), Box::pin(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_sfm_json;
+    use glam::DVec3;
+
+    const OPENMVG: &str = r#"{
+        "sfm_data_version": "0.3",
+        "root_path": "/data/images",
+        "views": [
+            {"key": 0, "value": {"polymorphic_id": 1073741824, "ptr_wrapper": {"id": 2147483649, "data": {
+                "local_path": "", "filename": "a.jpg", "width": 800, "height": 600,
+                "id_view": 0, "id_intrinsic": 0, "id_pose": 0}}}},
+            {"key": 1, "value": {"polymorphic_id": 1073741824, "ptr_wrapper": {"id": 2147483650, "data": {
+                "local_path": "sub", "filename": "b.jpg", "width": 800, "height": 600,
+                "id_view": 1, "id_intrinsic": 1, "id_pose": 1}}}}
+        ],
+        "intrinsics": [
+            {"key": 0, "value": {"polymorphic_id": 2147483649, "polymorphic_name": "pinhole_radial_k3",
+                "ptr_wrapper": {"id": 2147483651, "data": {"width": 800, "height": 600,
+                "focal_length": 1000.0, "principal_point": [400.0, 300.0], "disto_k3": [0.0, 0.0, 0.0]}}}},
+            {"key": 1, "value": {"polymorphic_id": 1,
+                "ptr_wrapper": {"id": 2147483652, "data": {"width": 640, "height": 480,
+                "focal_length": 500.0, "principal_point": [320.0, 240.0], "disto_k3": [0.1, 0.0, 0.0]}}}},
+            {"key": 2, "value": {"polymorphic_id": 2147483650, "polymorphic_name": "fisheye_1",
+                "ptr_wrapper": {"id": 2147483653, "data": {"width": 640, "height": 480,
+                "focal_length": 500.0, "principal_point": [320.0, 240.0]}}}}
+        ],
+        "extrinsics": [
+            {"key": 0, "value": {"rotation": [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+                "center": [1.0, 2.0, 3.0]}}
+        ],
+        "structure": [
+            {"key": 0, "value": {"X": [0.5, 1.5, 2.5], "observations": []}}
+        ]
+    }"#;
+
+    const ALICEVISION: &str = r#"{
+        "version": ["1", "2", "0"],
+        "featuresFolders": [],
+        "views": [
+            {"viewId": "10", "poseId": "20", "intrinsicId": "30", "path": "/home/user/images/a.jpg",
+                "width": "800", "height": "600"}
+        ],
+        "intrinsics": [
+            {"intrinsicId": "30", "width": "800", "height": "600", "sensorWidth": "36",
+                "sensorHeight": "24", "type": "radial3", "focalLength": "45", "pixelRatio": "1",
+                "principalPoint": ["10", "-5"], "distortionParams": ["0", "0", "0"]}
+        ],
+        "poses": [
+            {"poseId": "20", "pose": {"transform": {
+                "rotation": ["0", "1", "0", "-1", "0", "0", "0", "0", "1"],
+                "center": ["1", "2", "3"]}, "locked": "0"}}
+        ],
+        "structure": [
+            {"landmarkId": "0", "descType": "sift", "color": ["255", "0", "51"], "X": ["0.5", "1.5", "2.5"]}
+        ]
+    }"#;
+
+    #[test]
+    fn openmvg() {
+        let scene = parse_sfm_json(OPENMVG).expect("Failed to parse OpenMVG");
+
+        // The second intrinsic only has the masked type id of the first.
+        assert_eq!(
+            scene.intrinsics.len(),
+            2,
+            "Fisheye intrinsic should be skipped"
+        );
+        let first = &scene.intrinsics["0"];
+        assert_eq!(first.model, "pinhole_radial_k3", "Wrong model");
+        assert_eq!(first.focal, (1000.0, 1000.0), "Wrong focal");
+        assert_eq!(first.center, (400.0, 300.0), "Wrong center");
+        assert!(!first.distorted, "Zero coefficients aren't a distortion");
+        let second = &scene.intrinsics["1"];
+        assert_eq!(
+            second.model, "pinhole_radial_k3",
+            "Model should be looked up by id"
+        );
+        assert_eq!((second.width, second.height), (640, 480), "Wrong size");
+        assert!(second.distorted, "Second intrinsic has distortion");
+
+        // Rotation rows are stored as nested arrays.
+        let pose = &scene.poses["0"];
+        assert_eq!(
+            pose.world_to_cam * DVec3::X,
+            DVec3::Y,
+            "Rotation should be row major"
+        );
+        assert_eq!(pose.center, DVec3::new(1.0, 2.0, 3.0), "Wrong center");
+
+        assert_eq!(scene.views.len(), 2, "Wrong view count");
+        assert_eq!(
+            scene.views[1].path.to_str(),
+            Some("sub/b.jpg"),
+            "Wrong path"
+        );
+        assert_eq!(scene.points.len(), 1, "Wrong point count");
+        assert!(scene.colors.is_none(), "OpenMVG has no colors");
+    }
+
+    #[test]
+    fn alicevision() {
+        let scene = parse_sfm_json(ALICEVISION).expect("Failed to parse AliceVision");
+
+        let intrinsic = &scene.intrinsics["30"];
+        assert_eq!(
+            intrinsic.focal,
+            (1000.0, 1000.0),
+            "Focal should be converted from mm"
+        );
+        assert_eq!(
+            intrinsic.center,
+            (410.0, 295.0),
+            "Principal point is an offset from the center since 1.2.0"
+        );
+
+        // The same rotation as the OpenMVG test, stored flat and column major.
+        let pose = &scene.poses["20"];
+        assert_eq!(
+            pose.world_to_cam * DVec3::X,
+            DVec3::Y,
+            "Rotation should be column major"
+        );
+        assert_eq!(pose.center, DVec3::new(1.0, 2.0, 3.0), "Wrong center");
+
+        assert_eq!(
+            scene.views[0].path.to_str(),
+            Some("a.jpg"),
+            "Only file name should be kept"
+        );
+        let colors = scene.colors.expect("AliceVision should have colors");
+        assert_eq!(colors[0], glam::vec3(1.0, 0.0, 0.2), "Wrong color");
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use brush_train::scene::{ImageCache, ImageLoader, ViewImage, ViewImageType, ViewMetadata};
+use glam::UVec2;
+use image::{ColorType, DynamicImage, ImageDecoder, ImageReader};
+#[cfg(not(target_family = "wasm"))]
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use super::{clamp_img_to_max_size, decode_image, exif};
+use crate::{
+    LoadDataseConfig,
+    brush_vfs::{BlockingReader, BrushVfs},
+};
+
+/// Size of an image after [`clamp_img_to_max_size`], without having to decode it.
+fn clamped_size(width: u32, height: u32, max_size: u32) -> UVec2 {
+    if width <= max_size && height <= max_size {
+        return UVec2::new(width, height);
+    }
+    // Matches the rounding of `DynamicImage::resize`.
+    let ratio = f64::min(
+        max_size as f64 / width as f64,
+        max_size as f64 / height as f64,
+    );
+    UVec2::new(
+        ((width as f64 * ratio).round() as u32).max(1),
+        ((height as f64 * ratio).round() as u32).max(1),
+    )
+}
+
+/// How much of an image to read for lazy loading. Enough for the header and EXIF data of
+/// common formats, if that fails the whole file is read.
+const HEADER_BYTES: u64 = 256 * 1024;
+
+async fn read_file(vfs: &mut BrushVfs, path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    vfs.open_path(path).await?.read_to_end(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// Decode only the header of an image, to know its size and read the metadata.
+fn read_header(bytes: &[u8], path: &Path) -> Result<(ViewMetadata, (u32, u32), ColorType)> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()
+        .with_context(|| format!("Failed to read image header of {path:?}"))?;
+    let metadata = decoder
+        .exif_metadata()
+        .ok()
+        .flatten()
+        .map(|chunk| exif::read_metadata(&chunk))
+        .unwrap_or_default();
+    Ok((metadata, decoder.dimensions(), decoder.color_type()))
+}
+
+/// Decodes an image from the VFS, and resizes it to the max resolution.
+struct VfsImage {
+    reader: BlockingReader,
+    img_path: PathBuf,
+    mask_path: Option<PathBuf>,
+    max_resolution: u32,
+    /// Directory to keep the resized image in, if anywhere.
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    disk_cache: Option<PathBuf>,
+    /// PNG can't hold HDR images, those are kept as EXR.
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    hdr: bool,
+    /// Where the resized image is kept, once the source has been read to hash it.
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    disk_path: OnceLock<PathBuf>,
+}
+
+impl VfsImage {
+    fn read_sources(&self) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let img_bytes = self.reader.read(&self.img_path)?;
+        let mask_bytes = self
+            .mask_path
+            .as_ref()
+            .map(|p| self.reader.read(p))
+            .transpose()?;
+        Ok((img_bytes, mask_bytes))
+    }
+
+    fn decode(&self, img_bytes: &[u8], mask_bytes: Option<&[u8]>) -> Result<DynamicImage> {
+        let (image, _) = decode_image(img_bytes, mask_bytes)?;
+        let image = clamp_img_to_max_size(Arc::new(image), self.max_resolution);
+        Ok(Arc::unwrap_or_clone(image))
+    }
+
+    /// Key on the contents, so datasets with the same file names can share a directory.
+    /// The hash has to be stable across runs and versions, unlike the std hasher.
+    #[cfg(not(target_family = "wasm"))]
+    fn cache_path(&self, dir: &Path, img_bytes: &[u8], mask_bytes: Option<&[u8]>) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update((img_bytes.len() as u64).to_le_bytes());
+        hasher.update(img_bytes);
+        if let Some(mask_bytes) = mask_bytes {
+            hasher.update(mask_bytes);
+        }
+        hasher.update(self.max_resolution.to_le_bytes());
+        let extension = if self.hdr { "exr" } else { "png" };
+        dir.join(format!("{:x}.{extension}", hasher.finalize()))
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn save_to_disk(image: &DynamicImage, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image.save(path)?;
+    Ok(())
+}
+
+impl ImageLoader for VfsImage {
+    fn load(&self) -> Result<DynamicImage> {
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(dir) = &self.disk_cache {
+            // Once the image is cached, the source doesn't have to be read again.
+            if let Some(disk_path) = self.disk_path.get() {
+                if let Ok(image) = image::open(disk_path) {
+                    return Ok(image);
+                }
+            }
+
+            let (img_bytes, mask_bytes) = self.read_sources()?;
+            let disk_path = self
+                .disk_path
+                .get_or_init(|| self.cache_path(dir, &img_bytes, mask_bytes.as_deref()));
+            if let Ok(image) = image::open(disk_path) {
+                return Ok(image);
+            }
+
+            let image = self.decode(&img_bytes, mask_bytes.as_deref())?;
+            if let Err(e) = save_to_disk(&image, disk_path) {
+                log::warn!("Failed to cache image at {disk_path:?}: {e}");
+            }
+            return Ok(image);
+        }
+
+        let (img_bytes, mask_bytes) = self.read_sources()?;
+        self.decode(&img_bytes, mask_bytes.as_deref())
+    }
+}
+
+/// Loads the images of views. Images are either decoded right away, or with lazy loading
+/// only their size is read, and they're decoded on demand into a shared cache.
+#[derive(Clone)]
+pub(crate) struct ViewImageLoader {
+    max_resolution: u32,
+    lazy: Option<(BlockingReader, Arc<ImageCache>)>,
+    disk_cache: Option<PathBuf>,
+}
+
+impl ViewImageLoader {
+    pub(crate) fn new(vfs: &BrushVfs, load_args: &LoadDataseConfig) -> Self {
+        let lazy = if load_args.lazy_images {
+            let reader = vfs.blocking_reader();
+            if reader.is_none() {
+                log::warn!("Lazy image loading isn't supported for this data source.");
+            }
+            let cache_bytes = load_args.image_cache_mb as usize * 1024 * 1024;
+            reader.map(|r| (r, Arc::new(ImageCache::new(cache_bytes))))
+        } else {
+            None
+        };
+
+        Self {
+            max_resolution: load_args.max_resolution,
+            lazy,
+            disk_cache: load_args.image_cache_dir.as_ref().map(PathBuf::from),
+        }
+    }
+
+    /// Load the image of a view, resized to the max resolution. Also returns the size of the
+    /// original image, as camera intrinsics are often relative to that, and the capture settings
+    /// from the EXIF data of the image.
+    pub(crate) async fn load(
+        &self,
+        vfs: &mut BrushVfs,
+        img_path: &Path,
+        mask_path: Option<&Path>,
+    ) -> Result<(ViewImage, ViewImageType, UVec2, ViewMetadata)> {
+        let Some((reader, cache)) = &self.lazy else {
+            let img_bytes = read_file(vfs, img_path).await?;
+            let mask_bytes = match mask_path {
+                Some(mask_path) => Some(read_file(vfs, mask_path).await?),
+                None => None,
+            };
+            let (mut metadata, _, _) = read_header(&img_bytes, img_path)?;
+            let (image, img_type) = decode_image(&img_bytes, mask_bytes.as_deref())?;
+            let size = UVec2::new(image.width(), image.height());
+            // Kept with the view, so exports can refer back to the original image.
+            metadata.source_size = Some(size);
+            let image = clamp_img_to_max_size(Arc::new(image), self.max_resolution);
+            return Ok((ViewImage::loaded(image), img_type, size, metadata));
+        };
+
+        // Only read the start of the file for now. The image (and mask) are read in full when
+        // they're decoded.
+        let start = reader.read_start_async(img_path, HEADER_BYTES).await?;
+        let header = match read_header(&start, img_path) {
+            Err(_) if start.len() as u64 == HEADER_BYTES => {
+                let img_bytes = reader.read_start_async(img_path, u64::MAX).await?;
+                read_header(&img_bytes, img_path)?
+            }
+            header => header?,
+        };
+        let (mut metadata, (width, height), color_type) = header;
+        metadata.source_size = Some(UVec2::new(width, height));
+
+        let has_alpha = mask_path.is_some() || color_type.has_alpha();
+        let img_type = if mask_path.is_some() {
+            ViewImageType::Masked
+        } else {
+            ViewImageType::Alpha
+        };
+
+        let loader = VfsImage {
+            reader: reader.clone(),
+            img_path: img_path.to_path_buf(),
+            mask_path: mask_path.map(Path::to_path_buf),
+            max_resolution: self.max_resolution,
+            disk_cache: self.disk_cache.clone(),
+            hdr: matches!(color_type, ColorType::Rgb32F | ColorType::Rgba32F),
+            disk_path: OnceLock::new(),
+        };
+        let size = clamped_size(width, height, self.max_resolution);
+        let image = ViewImage::lazy(size.x, size.y, has_alpha, Arc::new(loader), cache.clone());
+
+        Ok((image, img_type, UVec2::new(width, height), metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_header;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::{io::Cursor, path::Path};
+
+    #[test]
+    fn header_from_start_of_file() {
+        let image = RgbImage::from_fn(300, 200, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let mut bytes = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut bytes, ImageFormat::Png)
+            .expect("Failed to encode png");
+        let bytes = bytes.into_inner();
+
+        // The pixel data isn't needed to know the size.
+        let (_, size, _) =
+            read_header(&bytes[..256], Path::new("img.png")).expect("Failed to read header");
+        assert_eq!(size, (300, 200), "Header should have the image size");
+    }
+}
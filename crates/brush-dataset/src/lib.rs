@@ -1,19 +1,34 @@
 pub mod brush_vfs;
+pub mod dataset_report;
+pub mod eval_split;
+pub mod extra_properties;
 mod formats;
+pub mod nerfstudio_export;
 mod parsed_gaussian;
+pub mod point_cloud;
 mod quant;
 pub mod scene_loader;
+pub mod scene_normalization;
+pub mod sh_codebook;
 pub mod splat_export;
 pub mod splat_import;
 
 use burn::config::Config;
+pub use formats::DataStream;
 pub use formats::clamp_img_to_max_size;
-pub use formats::load_dataset;
+pub use formats::{load_dataset, load_dataset_with_format};
+pub use formats::{DatasetError, DatasetFormat};
 
 use async_fn_stream::fn_stream;
 use brush_train::scene::{Scene, SceneView};
 use core::f32;
+use scene_normalization::{SceneCenter, WorldTransform};
+use std::collections::BTreeMap;
 use std::future::Future;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
 
 use clap::Args;
 use glam::{Mat3, Mat4, Vec3};
@@ -29,15 +44,45 @@ pub struct LoadDataseConfig {
     #[arg(long, help_heading = "Dataset Options", default_value = "1800")]
     #[config(default = 1920)]
     pub max_resolution: u32,
-    /// Create an eval dataset by selecting every nth image
-    #[arg(long, help_heading = "Dataset Options")]
+    /// Create an eval dataset by selecting every nth image. Without any of the eval split options,
+    /// the eval images that come with the dataset are used, if any.
+    #[arg(long, help_heading = "Dataset Options", group = "eval_split")]
     pub eval_split_every: Option<usize>,
+    /// Create an eval dataset from the images listed in a text file, one name per line. The file is
+    /// looked for in the dataset first.
+    #[arg(long, help_heading = "Dataset Options", group = "eval_split")]
+    pub eval_split_file: Option<String>,
+    /// Create an eval dataset from the images matching a glob pattern, eg. "*_eval.png".
+    #[arg(long, help_heading = "Dataset Options", group = "eval_split")]
+    pub eval_split_glob: Option<String>,
+    /// Create an eval dataset from a random percentage of the images.
+    #[arg(long, help_heading = "Dataset Options", group = "eval_split")]
+    pub eval_split_percent: Option<f32>,
+    /// Seed used to pick the images with --eval-split-percent.
+    #[arg(long, help_heading = "Dataset Options", default_value = "42")]
+    #[config(default = 42)]
+    pub eval_split_seed: u64,
     /// Load only every nth frame
     #[arg(long, help_heading = "Dataset Options")]
     pub subsample_frames: Option<u32>,
-    /// Load only every nth point from the initial sfm data
+    /// Load only every nth point from the initial sfm data or point cloud
     #[arg(long, help_heading = "Dataset Options")]
     pub subsample_points: Option<u32>,
+    /// Normalize the scene to a unit radius around the cameras or the median of the initial points,
+    /// with the up axis along +Y. Exported splats are mapped back to the original coordinates.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub normalize_scene: Option<SceneCenter>,
+    /// Decode images on demand while training, instead of keeping all of them in memory.
+    #[arg(long, help_heading = "Dataset Options", default_value = "false")]
+    #[config(default = false)]
+    pub lazy_images: bool,
+    /// Max memory used for decoded images with lazy loading, in MB.
+    #[arg(long, help_heading = "Dataset Options", default_value = "2048")]
+    #[config(default = 2048)]
+    pub image_cache_mb: u32,
+    /// Directory to keep resized images in with lazy loading, to speed up later runs.
+    #[arg(long, help_heading = "Dataset Options")]
+    pub image_cache_dir: Option<String>,
 }
 
 #[derive(Config, Debug, Args)]
@@ -48,6 +93,26 @@ pub struct ModelConfig {
     pub sh_degree: u32,
 }
 
+#[derive(Config, Debug, Args)]
+pub struct CompressionConfig {
+    /// Export splats with the higher order SH bands stored in a k-means codebook.
+    #[arg(long, help_heading = "Compression Options", default_value = "false")]
+    #[config(default = false)]
+    pub export_sh_codebook: bool,
+    /// Nr. of entries in the SH codebook.
+    #[arg(long, help_heading = "Compression Options", default_value = "4096")]
+    #[config(default = 4096)]
+    pub sh_codebook_size: u32,
+    /// Nr. of k-means iterations used to fit the SH codebook.
+    #[arg(long, help_heading = "Compression Options", default_value = "10")]
+    #[config(default = 10)]
+    pub sh_codebook_iters: u32,
+    /// Also quantize means, scales, rotations, opacity and base color in the compressed export.
+    #[arg(long, help_heading = "Compression Options", default_value = "false")]
+    #[config(default = false)]
+    pub quantize_attributes: bool,
+}
+
 fn solve_cubic(a: f32, b: f32, c: f32, d: f32) -> (f32, f32, f32) {
     // Convert to depressed cubic t^3 + pt + q = 0
     let p = (3.0 * a * c - b * b) / (3.0 * a * a);
@@ -151,6 +216,8 @@ pub fn compute_sorted_eigenvectors(matrix: Mat3) -> (Vec3, Vec3, Vec3) {
 pub struct Dataset {
     pub train: Scene,
     pub eval: Option<Scene>,
+    /// Transform from the original coordinates of the dataset to the coordinates of the views.
+    pub world_transform: WorldTransform,
 }
 
 impl Dataset {
@@ -158,6 +225,7 @@ impl Dataset {
         Self {
             train: Scene::new(vec![]),
             eval: None,
+            world_transform: WorldTransform::IDENTITY,
         }
     }
 
@@ -169,6 +237,7 @@ impl Dataset {
             } else {
                 Some(Scene::new(eval_views))
             },
+            world_transform: WorldTransform::IDENTITY,
         }
     }
 
@@ -218,8 +287,44 @@ impl Dataset {
     }
 }
 
+/// How many views of a dataset are loaded, out of the views found so far. Some formats find more
+/// views (eg. a separate eval set) while loading, so the total can grow.
+///
+/// Loaders also note the camera models they come across, which is useful to report.
+#[derive(Clone, Debug, Default)]
+pub struct LoadProgress {
+    views_done: Arc<AtomicUsize>,
+    views_total: Arc<AtomicUsize>,
+    camera_models: Arc<Mutex<BTreeMap<String, bool>>>,
+}
+
+impl LoadProgress {
+    pub(crate) fn add_camera_model(&self, model: &str, distorted: bool) {
+        *self
+            .camera_models
+            .lock()
+            .expect("Lock poisoned")
+            .entry(model.to_owned())
+            .or_default() |= distorted;
+    }
+
+    /// The camera models found so far, and whether they have lens distortion, which Brush ignores.
+    pub fn camera_models(&self) -> BTreeMap<String, bool> {
+        self.camera_models.lock().expect("Lock poisoned").clone()
+    }
+
+    pub fn views_done(&self) -> usize {
+        self.views_done.load(Ordering::Relaxed)
+    }
+
+    pub fn views_total(&self) -> usize {
+        self.views_total.load(Ordering::Relaxed)
+    }
+}
+
 pub(crate) fn stream_fut_parallel<T: Send + 'static>(
     futures: Vec<impl Future<Output = T> + WasmNotSend + 'static>,
+    progress: &LoadProgress,
 ) -> impl Stream<Item = T> {
     let parallel = if cfg!(target_family = "wasm") {
         1
@@ -231,6 +336,11 @@ pub(crate) fn stream_fut_parallel<T: Send + 'static>(
 
     log::info!("Loading stream with {parallel} threads");
 
+    progress
+        .views_total
+        .fetch_add(futures.len(), Ordering::Relaxed);
+    let progress = progress.clone();
+
     let mut futures = futures;
     fn_stream(|emitter| async move {
         while !futures.is_empty() {
@@ -241,9 +351,9 @@ pub(crate) fn stream_fut_parallel<T: Send + 'static>(
                 .collect();
             // Stream each of them.
             for handle in handles {
-                emitter
-                    .emit(handle.await.expect("Underlying stream panicked"))
-                    .await;
+                let item = handle.await.expect("Underlying stream panicked");
+                progress.views_done.fetch_add(1, Ordering::Relaxed);
+                emitter.emit(item).await;
             }
         }
     })
@@ -265,3 +375,41 @@ mod wasm_send {
 }
 
 pub use wasm_send::*;
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadProgress, stream_fut_parallel};
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn load_progress_counts() {
+        let progress = LoadProgress::default();
+        progress.add_camera_model("PINHOLE", false);
+        progress.add_camera_model("OPENCV", true);
+        progress.add_camera_model("OPENCV", false);
+
+        let futures: Vec<_> = (0..20).map(|i| async move { i }).collect();
+        let stream = stream_fut_parallel(futures, &progress);
+        assert_eq!(progress.views_total(), 20, "Total should be known upfront");
+        assert_eq!(progress.views_done(), 0, "Nothing should be done yet");
+
+        let items: Vec<_> = stream.collect().await;
+        assert_eq!(
+            items,
+            (0..20).collect::<Vec<_>>(),
+            "Items should keep their order"
+        );
+        assert_eq!(progress.views_done(), 20, "All views should be done");
+
+        // Totals add up over multiple streams.
+        let _ = stream_fut_parallel(vec![async {}], &progress);
+        assert_eq!(progress.views_total(), 21, "Totals should add up");
+
+        let models: Vec<_> = progress.camera_models().into_iter().collect();
+        assert_eq!(
+            models,
+            [("OPENCV".to_owned(), true), ("PINHOLE".to_owned(), false)],
+            "Any distorted camera should mark the model as distorted"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::{collections::HashSet, io::Cursor, path::Path};
+
+use anyhow::{Context, Result};
+use brush_render::camera::fov_to_focal;
+use brush_train::scene::Scene;
+use image::{DynamicImage, ImageFormat, codecs::jpeg::JpegEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::Dataset;
+
+/// How to handle images when exporting a dataset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum DatasetImageExport {
+    /// Reference the original image paths. The transforms need to be placed in the root of the
+    /// original dataset.
+    #[default]
+    Reference,
+    /// Write the loaded (and possibly resized) images as png.
+    Png,
+    /// Write the loaded (and possibly resized) images as jpeg.
+    Jpeg,
+}
+
+const JPEG_QUALITY: u8 = 95;
+
+#[derive(Serialize)]
+struct TransformsFrame {
+    file_path: String,
+    /// Camera to world matrix in the nerfstudio convention (+Y up, -Z forward), as rows.
+    transform_matrix: [[f32; 4]; 4],
+    camera_angle_x: f64,
+    camera_angle_y: f64,
+    fl_x: f64,
+    fl_y: f64,
+    cx: f64,
+    cy: f64,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Serialize)]
+struct TransformsFile {
+    camera_model: &'static str,
+    frames: Vec<TransformsFrame>,
+}
+
+/// A file produced by an export, with a path relative to the export directory.
+pub struct ExportedFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+fn encode_image(image: &DynamicImage, format: DatasetImageExport) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    match format {
+        DatasetImageExport::Reference => {}
+        DatasetImageExport::Png => image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?,
+        DatasetImageExport::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY);
+            image.to_rgb8().write_with_encoder(encoder)?;
+        }
+    }
+    Ok(data)
+}
+
+fn export_scene(
+    scene: &Scene,
+    images: DatasetImageExport,
+    used_names: &mut HashSet<String>,
+    write: &mut impl FnMut(ExportedFile) -> Result<()>,
+) -> Result<TransformsFile> {
+    let frames = scene
+        .views
+        .iter()
+        .map(|view| {
+            // Referenced images are at their original size, which the intrinsics need to match.
+            let (w, h) = match view.metadata.source_size {
+                Some(size) if images == DatasetImageExport::Reference => (size.x, size.y),
+                _ => (view.image.width(), view.image.height()),
+            };
+            let cam = &view.camera;
+
+            let mut transform = glam::Mat4::from(cam.local_to_world());
+            // Brush cameras are +Z forward, +Y down, nerfstudio is the opposite.
+            transform.y_axis *= -1.0;
+            transform.z_axis *= -1.0;
+
+            let file_path = if images == DatasetImageExport::Reference {
+                view.path.clone()
+            } else {
+                let stem = Path::new(&view.path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "image".to_owned());
+                let ext = if images == DatasetImageExport::Png {
+                    "png"
+                } else {
+                    "jpg"
+                };
+
+                // Images from different folders can share a name.
+                let mut name = format!("images/{stem}.{ext}");
+                let mut i = 1;
+                while !used_names.insert(name.clone()) {
+                    name = format!("images/{stem}_{i}.{ext}");
+                    i += 1;
+                }
+
+                let data = encode_image(&*view.image.load()?, images)
+                    .with_context(|| format!("Failed to encode image {}", view.path))?;
+                write(ExportedFile {
+                    path: name.clone(),
+                    data,
+                })?;
+                name
+            };
+
+            Ok(TransformsFrame {
+                file_path,
+                transform_matrix: transform.transpose().to_cols_array_2d(),
+                camera_angle_x: cam.fov_x,
+                camera_angle_y: cam.fov_y,
+                fl_x: fov_to_focal(cam.fov_x, w),
+                fl_y: fov_to_focal(cam.fov_y, h),
+                cx: cam.center_uv.x as f64 * w as f64,
+                cy: cam.center_uv.y as f64 * h as f64,
+                w,
+                h,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(TransformsFile {
+        // Distortion isn't supported, so cameras are always plain pinhole.
+        camera_model: "PINHOLE",
+        frames,
+    })
+}
+
+/// Convert a dataset to nerfstudio transforms files. This produces `transforms.json` for the
+/// training views, `transforms_val.json` for the eval views if there are any, and the
+/// images if they're not referenced. Files are passed to `write` as soon as they're encoded.
+pub fn dataset_to_nerfstudio(
+    dataset: &Dataset,
+    images: DatasetImageExport,
+    mut write: impl FnMut(ExportedFile) -> Result<()>,
+) -> Result<()> {
+    let mut used_names = HashSet::new();
+
+    let train = export_scene(&dataset.train, images, &mut used_names, &mut write)?;
+    write(ExportedFile {
+        path: "transforms.json".to_owned(),
+        data: serde_json::to_vec_pretty(&train)?,
+    })?;
+
+    if let Some(eval) = &dataset.eval {
+        let eval = export_scene(eval, images, &mut used_names, &mut write)?;
+        write(ExportedFile {
+            path: "transforms_val.json".to_owned(),
+            data: serde_json::to_vec_pretty(&eval)?,
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{DatasetImageExport, ExportedFile, dataset_to_nerfstudio};
+    use crate::Dataset;
+    use brush_render::camera::{Camera, focal_to_fov};
+    use brush_train::scene::{SceneView, ViewImage, ViewImageType, ViewMetadata};
+    use image::{DynamicImage, RgbImage};
+
+    /// A view with a focal length of 8 pixels at its original 8x4 size, loaded at half size.
+    fn view(path: &str) -> SceneView {
+        SceneView {
+            path: path.to_owned(),
+            camera: Camera::new(
+                glam::Vec3::ZERO,
+                glam::Quat::IDENTITY,
+                focal_to_fov(8.0, 8),
+                focal_to_fov(8.0, 4),
+                glam::vec2(0.5, 0.5),
+            ),
+            image: ViewImage::loaded(Arc::new(DynamicImage::ImageRgb8(RgbImage::new(4, 2)))),
+            img_type: ViewImageType::Alpha,
+            metadata: ViewMetadata {
+                source_size: Some(glam::uvec2(8, 4)),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn export(images: DatasetImageExport) -> Vec<ExportedFile> {
+        let dataset = Dataset::from_views(vec![view("a/img.jpg"), view("b/img.jpg")], vec![]);
+        let mut files = vec![];
+        dataset_to_nerfstudio(&dataset, images, |file| {
+            files.push(file);
+            Ok(())
+        })
+        .expect("Failed to export");
+        files
+    }
+
+    fn frames(file: &ExportedFile) -> Vec<serde_json::Value> {
+        let transforms: serde_json::Value =
+            serde_json::from_slice(&file.data).expect("Invalid transforms json");
+        assert_eq!(transforms["camera_model"], "PINHOLE", "Wrong camera model");
+        transforms["frames"].as_array().expect("No frames").clone()
+    }
+
+    fn assert_intrinsics(frame: &serde_json::Value, size: f64) {
+        let value = |key: &str| frame[key].as_f64().expect("Missing value");
+        assert_eq!((value("w"), value("h")), (size, size / 2.0), "Wrong size");
+        assert!((value("fl_x") - size).abs() < 1e-6, "Wrong fl_x {frame}");
+        assert!((value("fl_y") - size).abs() < 1e-6, "Wrong fl_y {frame}");
+        assert!((value("cx") - size / 2.0).abs() < 1e-6, "Wrong cx {frame}");
+    }
+
+    #[test]
+    fn reference_uses_original_size() {
+        let files = export(DatasetImageExport::Reference);
+        assert_eq!(files.len(), 1, "Only transforms should be written");
+        assert_eq!(files[0].path, "transforms.json", "Wrong file");
+
+        let frames = frames(&files[0]);
+        assert_eq!(
+            frames[0]["file_path"], "a/img.jpg",
+            "Should reference the image"
+        );
+        assert_intrinsics(&frames[0], 8.0);
+    }
+
+    #[test]
+    fn write_images() {
+        let files = export(DatasetImageExport::Png);
+        let paths: Vec<_> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["images/img.png", "images/img_1.png", "transforms.json"],
+            "Images should be written first, with unique names"
+        );
+        let image = image::load_from_memory(&files[0].data).expect("Invalid png");
+        assert_eq!((image.width(), image.height()), (4, 2), "Wrong image size");
+
+        let frames = frames(&files[2]);
+        assert_eq!(
+            frames[1]["file_path"], "images/img_1.png",
+            "Wrong image path"
+        );
+        assert_intrinsics(&frames[1], 4.0);
+    }
+}
@@ -1,8 +1,11 @@
-use brush_render::sh::channel_to_sh;
+use brush_render::{gaussian_splats::inverse_sigmoid, sh::channel_to_sh};
 use glam::{Quat, Vec3};
 use ply_rs::ply::{Property, PropertyAccess};
 
-use crate::quant::{decode_quat, decode_vec_8_8_8_8, decode_vec_11_10_11};
+use crate::{
+    extra_properties::scalar_value,
+    quant::{decode_quat, decode_vec_8_8_8_8, decode_vec_11_10_11},
+};
 
 /// A gaussian as it parsed in the ply.
 ///
@@ -18,6 +21,61 @@ pub(crate) struct ParsedGaussian<const QUANT_PARSE: bool> {
     // NB: This is in the inria format, aka [channels, coeffs]
     // not [coeffs, channels].
     pub(crate) sh_coeffs_rest: Vec<f32>,
+    // Values of any scalar properties that aren't one of the above, in the order they were parsed.
+    pub(crate) extra: Vec<f64>,
+}
+
+/// Whether this is a property [`ParsedGaussian`] knows about. Any other properties
+/// end up in [`ParsedGaussian::extra`].
+pub(crate) fn is_known_property(key: &str) -> bool {
+    matches!(
+        key,
+        "x" | "y"
+            | "z"
+            | "scale_0"
+            | "scale_1"
+            | "scale_2"
+            | "opacity"
+            | "rot_0"
+            | "rot_1"
+            | "rot_2"
+            | "rot_3"
+            | "f_dc_0"
+            | "f_dc_1"
+            | "f_dc_2"
+            | "red"
+            | "green"
+            | "blue"
+    ) || key.starts_with("f_rest_")
+}
+
+/// Convert a scalar ply property to a float.
+///
+/// Integer types are treated as quantized values, and normalized to [0, 1] ([-1, 1] for signed types),
+/// which is what colors and quantized values expect.
+pub(crate) fn normalized_value(property: &Property) -> Option<f32> {
+    let value = match *property {
+        Property::Char(value) => (value as f32 / i8::MAX as f32).max(-1.0),
+        Property::UChar(value) => value as f32 / u8::MAX as f32,
+        Property::Short(value) => (value as f32 / i16::MAX as f32).max(-1.0),
+        Property::UShort(value) => value as f32 / u16::MAX as f32,
+        Property::Int(value) => (value as f64 / i32::MAX as f64).max(-1.0) as f32,
+        Property::UInt(value) => (value as f64 / u32::MAX as f64) as f32,
+        Property::Float(value) => value,
+        Property::Double(value) => value as f32,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Integer opacities are quantized activated values, whereas float opacities are stored as logits
+/// already. Clamped so fully opaque or transparent values don't turn into infinities.
+fn opacity_logit(property: &Property, value: f32) -> f32 {
+    if matches!(property, Property::Float(_) | Property::Double(_)) {
+        value
+    } else {
+        inverse_sigmoid(value.clamp(1e-4, 1.0 - 1e-4))
+    }
 }
 
 impl<const QUANT: bool> ParsedGaussian<QUANT> {
@@ -37,13 +95,22 @@ impl PropertyAccess for ParsedGaussian<false> {
     }
 
     fn set_property(&mut self, key: &str, property: Property) {
+        if !is_known_property(key) {
+            if let Some(value) = scalar_value(&property) {
+                self.extra.push(value);
+            }
+            return;
+        }
+
         let ascii = key.as_bytes();
 
-        let value = match property {
-            Property::Float(value) => value,
-            Property::UChar(value) => (value as f32) / (u8::MAX as f32 - 1.0),
-            Property::UShort(value) => (value as f32) / (u16::MAX as f32 - 1.0),
-            _ => return,
+        // Integer colors & opacities are quantized, other properties are plain numbers.
+        let value = match ascii {
+            b"red" | b"green" | b"blue" | b"opacity" => normalized_value(&property),
+            _ => scalar_value(&property).map(|v| v as f32),
+        };
+        let Some(value) = value else {
+            return;
         };
 
         // Floating point values.
@@ -54,7 +121,7 @@ impl PropertyAccess for ParsedGaussian<false> {
             b"scale_0" => self.log_scale[0] = value,
             b"scale_1" => self.log_scale[1] = value,
             b"scale_2" => self.log_scale[2] = value,
-            b"opacity" => self.opacity = value,
+            b"opacity" => self.opacity = opacity_logit(&property, value),
 
             // Rotations are saved in scalar from.
             b"rot_0" => self.rotation.w = value,
@@ -110,6 +177,20 @@ impl PropertyAccess for ParsedGaussian<false> {
     }
 }
 
+/// Parses all integer properties as quantized values, normalized like [`normalized_value`].
+pub(crate) struct Normalized<P>(pub(crate) P);
+
+impl<P: PropertyAccess> PropertyAccess for Normalized<P> {
+    fn new() -> Self {
+        Self(P::new())
+    }
+
+    fn set_property(&mut self, key: &str, property: Property) {
+        let property = normalized_value(&property).map_or(property, Property::Float);
+        self.0.set_property(key, property);
+    }
+}
+
 impl PropertyAccess for ParsedGaussian<true> {
     fn new() -> Self {
         Self::default()
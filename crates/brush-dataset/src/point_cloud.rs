@@ -0,0 +1,289 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_fn_stream::try_fn_stream;
+use brush_render::{gaussian_splats::Splats, sh::rgb_to_sh};
+use burn::prelude::Backend;
+use glam::Vec3;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::Stream;
+
+use crate::{
+    extra_properties::ExtraProperties,
+    splat_import::{ParseMetadata, SplatMessage},
+};
+
+/// Extensions of point cloud formats that can be used to initialize training.
+pub const POINT_CLOUD_EXTENSIONS: [&str; 4] = ["xyz", "pts", "las", "laz"];
+
+pub fn is_point_cloud_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| POINT_CLOUD_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+struct PointCloud {
+    positions: Vec<Vec3>,
+    // Colors in 0-1 range, if the cloud has any.
+    colors: Option<Vec<Vec3>>,
+}
+
+/// Whether a column of three values looks like normals rather than colors. Normals have
+/// negative components or a unit length, colors don't.
+fn is_normal_column(values: &[Vec3]) -> bool {
+    values.iter().any(|v| v.min_element() < 0.0)
+        || values.iter().all(|v| (v.length() - 1.0).abs() < 1e-2)
+}
+
+/// Parse a text point cloud, one point per line.
+///
+/// .xyz files are `x y z [r g b]`, .pts files are `x y z [intensity [r g b]]`, optionally starting with
+/// the number of points. Normals (`nx ny nz`) can come before or after the colors. Colors written
+/// as integers are 0-255, colors written as decimals are 0-1.
+fn parse_text_points(text: &str, has_intensity: bool, subsample: usize) -> PointCloud {
+    let mut positions = vec![];
+    // The two sets of three values after the position, which can be colors or normals.
+    let mut columns: [Vec<Vec3>; 2] = [vec![], vec![]];
+    // Whether all values of the columns are written as integers.
+    let mut integer_columns = [true; 2];
+
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("//"));
+
+    let mut index = 0;
+    for line in lines {
+        let tokens: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|v| !v.is_empty())
+            .collect();
+        let values: Vec<f32> = tokens.iter().map_while(|v| v.parse().ok()).collect();
+
+        // Skip headers, like the point count in .pts files.
+        if values.len() < 3 {
+            continue;
+        }
+
+        index += 1;
+        if (index - 1) % subsample != 0 {
+            continue;
+        }
+
+        positions.push(Vec3::new(values[0], values[1], values[2]));
+
+        let rest_start = if has_intensity && values.len() >= 7 {
+            4
+        } else {
+            3
+        };
+        for (i, column) in columns.iter_mut().enumerate() {
+            let start = rest_start + i * 3;
+            if let Some(v) = values.get(start..start + 3) {
+                column.push(Vec3::new(v[0], v[1], v[2]));
+                integer_columns[i] &= tokens[start..start + 3]
+                    .iter()
+                    .all(|t| t.parse::<i64>().is_ok());
+            }
+        }
+    }
+
+    // Use the first set of values every point has that aren't normals as colors.
+    let colors = columns
+        .into_iter()
+        .zip(integer_columns)
+        .find(|(c, _)| !c.is_empty() && c.len() == positions.len() && !is_normal_column(c))
+        .map(|(colors, integer)| {
+            // Some tools write 8 bit colors as decimals, those can't fit in 0-1.
+            let max = colors.iter().map(|c| c.max_element()).fold(0.0, f32::max);
+            let scale = if integer || max > 1.0 {
+                1.0 / 255.0
+            } else {
+                1.0
+            };
+            colors.into_iter().map(|c| c * scale).collect()
+        });
+
+    PointCloud { positions, colors }
+}
+
+fn parse_las_points(data: Vec<u8>, subsample: usize) -> Result<PointCloud> {
+    let mut reader =
+        las::Reader::new(std::io::Cursor::new(data)).context("Failed to read LAS header")?;
+
+    let mut positions = vec![];
+    let mut colors = vec![];
+
+    for (i, point) in reader.points().enumerate() {
+        let point = point.context("Failed to read LAS point")?;
+        if i % subsample != 0 {
+            continue;
+        }
+        // Nb: LAS coordinates are doubles. Large (eg. UTM) coordinates lose precision here, but need
+        // to match the camera coordinates anyway.
+        positions.push(Vec3::new(point.x as f32, point.y as f32, point.z as f32));
+        if let Some(color) = point.color {
+            colors.push([color.red, color.green, color.blue]);
+        }
+    }
+
+    let colors = (!colors.is_empty() && colors.len() == positions.len()).then(|| {
+        // The spec says colors are 16 bit, but plenty of software writes 8 bit values.
+        let max = colors.iter().flatten().copied().max().unwrap_or(0);
+        let scale = if max > u8::MAX as u16 {
+            1.0 / u16::MAX as f32
+        } else {
+            1.0 / u8::MAX as f32
+        };
+        colors
+            .into_iter()
+            .map(|[r, g, b]| Vec3::new(r as f32, g as f32, b as f32) * scale)
+            .collect()
+    });
+
+    Ok(PointCloud { positions, colors })
+}
+
+/// Load a point cloud (.xyz, .pts, .las or .laz) as initial splats.
+pub fn load_point_cloud<T: AsyncRead + Unpin + 'static, B: Backend>(
+    reader: T,
+    path: &Path,
+    subsample_points: Option<u32>,
+    device: B::Device,
+) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    let path = path.to_owned();
+
+    try_fn_stream(|emitter| async move {
+        let mut reader = reader;
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+
+        let subsample = subsample_points.unwrap_or(1).max(1) as usize;
+
+        let cloud = match extension.as_str() {
+            "xyz" | "pts" => {
+                let text = String::from_utf8(data)
+                    .with_context(|| format!("Point cloud {path:?} is not a text file"))?;
+                parse_text_points(&text, extension == "pts", subsample)
+            }
+            "las" | "laz" => parse_las_points(data, subsample)
+                .with_context(|| format!("Failed to parse point cloud {path:?}"))?,
+            _ => anyhow::bail!("Unknown point cloud format {path:?}"),
+        };
+
+        anyhow::ensure!(
+            !cloud.positions.is_empty(),
+            "No points found in point cloud {path:?}"
+        );
+
+        log::info!(
+            "Starting from point cloud {path:?} with {} points",
+            cloud.positions.len()
+        );
+
+        let sh_coeffs: Option<Vec<f32>> = cloud.colors.map(|colors| {
+            colors
+                .into_iter()
+                .flat_map(|c| {
+                    let sh = rgb_to_sh(c);
+                    [sh.x, sh.y, sh.z]
+                })
+                .collect()
+        });
+
+        let splats = Splats::from_raw(
+            &cloud.positions,
+            None,
+            None,
+            sh_coeffs.as_deref(),
+            None,
+            &device,
+        );
+
+        emitter
+            .emit(SplatMessage {
+                meta: ParseMetadata {
+                    up_axis: None,
+                    total_splats: splats.num_splats(),
+                    frame_count: 1,
+                    current_frame: 0,
+                },
+                splats,
+                extra_properties: ExtraProperties::default(),
+            })
+            .await;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::parse_text_points;
+
+    #[test]
+    fn text_points() {
+        let cloud = parse_text_points("1 2 3 255 0 51\n-1 0.5 2 0 255 0\n", false, 1);
+        assert_eq!(
+            cloud.positions,
+            [Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.5, 2.0)],
+            "Positions"
+        );
+        let colors = cloud.colors.expect("Cloud should have colors");
+        assert!(
+            colors[0].abs_diff_eq(Vec3::new(1.0, 0.0, 0.2), 1e-6),
+            "8 bit colors should be normalized"
+        );
+
+        let cloud = parse_text_points("0 0 0 1 0 1\n1 1 1 0 1 0\n", false, 1);
+        assert_eq!(
+            cloud.colors.map(|c| c[0]),
+            Some(Vec3::new(1.0, 0.0, 1.0) / 255.0),
+            "Integer colors are 8 bit, even when dark"
+        );
+
+        let cloud = parse_text_points("0 0 0 0 0 1\n1 1 1 0.6 -0.8 0\n", false, 1);
+        assert!(cloud.colors.is_none(), "Normals aren't colors");
+
+        let cloud = parse_text_points("0 0 0 0 0 1 10 20 30\n1 1 1 1 0 0 40 50 60\n", false, 1);
+        let colors = cloud
+            .colors
+            .expect("Cloud should have colors after normals");
+        assert!(
+            colors[1].abs_diff_eq(Vec3::new(40.0, 50.0, 60.0) / 255.0, 1e-6),
+            "Colors after normals"
+        );
+
+        let cloud = parse_text_points("0 0 0 1 1 1\n1 1 1 0.5 0.5 0.5 0 0 -1\n", false, 1);
+        assert!(
+            cloud.colors.is_some_and(|c| c[1] == Vec3::splat(0.5)),
+            "0-1 colors before normals"
+        );
+
+        // A .pts file with a point count and intensity.
+        let text = "3\n0 0 0 -20 255 255 255\n1 0 0 -20 0 0 0\n2 0 0 -20 0 0 0\n";
+        let cloud = parse_text_points(text, true, 2);
+        assert_eq!(
+            cloud.positions.len(),
+            2,
+            "Should subsample every other point"
+        );
+        assert_eq!(
+            cloud.positions[1],
+            Vec3::new(2.0, 0.0, 0.0),
+            "Subsampled point"
+        );
+        assert_eq!(
+            cloud.colors.map(|c| c[0]),
+            Some(Vec3::ONE),
+            "Colors after intensity"
+        );
+    }
+}
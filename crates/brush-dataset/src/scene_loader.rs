@@ -1,57 +1,189 @@
+use anyhow::{Context, Result};
 use brush_train::image::view_to_sample;
-use brush_train::scene::Scene;
+use brush_train::scene::{Scene, SceneView};
 use brush_train::train::SceneBatch;
 use burn::prelude::Backend;
+use burn::tensor::Tensor;
 use rand::{SeedableRng, seq::SliceRandom};
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, UnboundedSender};
 use tokio_with_wasm::alias as tokio_wasm;
 
+/// Stop loading when this many images in a row fail to load, as none of them likely will.
+const MAX_CONSECUTIVE_FAILURES: u32 = 32;
+
+/// Decode the image of a view. Lazily loaded images are decoded here, which is slow, so keep
+/// it off the async threads where possible.
+async fn load_sample<B: Backend>(
+    view: SceneView,
+    linear_space: bool,
+    device: &B::Device,
+) -> Result<Tensor<B, 3>> {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let device = device.clone();
+        tokio::task::spawn_blocking(move || view_to_sample(&view, linear_space, &device))
+            .await
+            .context("Image loading task failed")?
+    }
+    #[cfg(target_family = "wasm")]
+    view_to_sample(&view, linear_space, device)
+}
+
 pub struct SceneLoader<B: Backend> {
-    receiver: Receiver<SceneBatch<B>>,
+    receiver: Receiver<Result<SceneBatch<B>>>,
+    new_views: UnboundedSender<Vec<SceneView>>,
 }
 
 impl<B: Backend> SceneLoader<B> {
-    pub fn new(scene: &Scene, seed: u64, device: &B::Device) -> Self {
-        let scene = scene.clone();
+    /// Load batches of the views of a scene in a random order. The scene can start out empty if
+    /// views are added later with [`Self::add_views`].
+    pub fn new(scene: &Scene, seed: u64, linear_space: bool, device: &B::Device) -> Self {
+        let mut views = scene.views.as_ref().clone();
         // The bounded size == number of batches to prefetch.
         let (tx, rx) = mpsc::channel(5);
+        let (views_tx, mut views_rx) = mpsc::unbounded_channel::<Vec<SceneView>>();
         let device = device.clone();
 
         let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
 
         let fut = async move {
             let mut shuf_indices = vec![];
+            let mut failures = 0;
 
             loop {
+                // New views join the views left in this epoch.
+                while let Ok(new_views) = views_rx.try_recv() {
+                    shuf_indices.extend(views.len()..views.len() + new_views.len());
+                    shuf_indices.shuffle(&mut rng);
+                    views.extend(new_views);
+                }
+
+                if views.is_empty() {
+                    // Wait for the first views, or stop if the loader is gone.
+                    let Some(new_views) = views_rx.recv().await else {
+                        break;
+                    };
+                    shuf_indices.extend(0..new_views.len());
+                    views.extend(new_views);
+                    continue;
+                }
+
                 let (gt_image, gt_view) = {
                     let index = shuf_indices.pop().unwrap_or_else(|| {
-                        shuf_indices = (0..scene.views.len()).collect();
+                        shuf_indices = (0..views.len()).collect();
                         shuf_indices.shuffle(&mut rng);
                         shuf_indices
                             .pop()
                             .expect("Need at least one view in dataset")
                     });
-                    let view = scene.views[index].clone();
-                    (view_to_sample(&view, &device), view)
+                    let view = views[index].clone();
+
+                    // Lazily loaded images can fail to load, skip them rather than stop training,
+                    // unless none of them seem to load.
+                    match load_sample(view.clone(), linear_space, &device).await {
+                        Ok(sample) => {
+                            failures = 0;
+                            (sample, view)
+                        }
+                        Err(e) => {
+                            failures += 1;
+                            if failures >= MAX_CONSECUTIVE_FAILURES {
+                                let e =
+                                    e.context(format!("Failed to load {failures} images in a row"));
+                                let _ = tx.send(Err(e)).await;
+                                break;
+                            }
+                            log::error!("Failed to load image {}: {e:?}", view.path);
+                            continue;
+                        }
+                    }
                 };
 
                 let scene_batch = SceneBatch { gt_image, gt_view };
 
-                if tx.send(scene_batch).await.is_err() {
+                if tx.send(Ok(scene_batch)).await.is_err() {
                     break;
                 }
             }
         };
 
         tokio_wasm::spawn(fut);
-        Self { receiver: rx }
+        Self {
+            receiver: rx,
+            new_views: views_tx,
+        }
+    }
+
+    /// Add views to train on. They're mixed in with the views left in the current epoch.
+    pub fn add_views(&self, views: Vec<SceneView>) {
+        if !views.is_empty() {
+            // The loading task only stops once the loader is dropped.
+            let _ = self.new_views.send(views);
+        }
     }
 
-    pub async fn next_batch(&mut self) -> SceneBatch<B> {
+    pub async fn next_batch(&mut self) -> Result<SceneBatch<B>> {
         self.receiver
             .recv()
             .await
-            .expect("Somehow lost data loading channel!")
+            .context("Lost data loading channel")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::SceneLoader;
+    use brush_render::camera::{Camera, focal_to_fov};
+    use brush_train::scene::{Scene, SceneView, ViewImage, ViewImageType};
+    use burn::backend::{Wgpu, wgpu::WgpuDevice};
+    use image::{DynamicImage, RgbImage};
+
+    fn view(path: &str) -> SceneView {
+        SceneView {
+            path: path.to_owned(),
+            camera: Camera::new(
+                glam::Vec3::ZERO,
+                glam::Quat::IDENTITY,
+                focal_to_fov(4.0, 4),
+                focal_to_fov(4.0, 2),
+                glam::vec2(0.5, 0.5),
+            ),
+            image: ViewImage::loaded(Arc::new(DynamicImage::ImageRgb8(RgbImage::new(4, 2)))),
+            img_type: ViewImageType::Alpha,
+            metadata: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn add_views_to_empty_loader() {
+        let device = WgpuDevice::DefaultDevice;
+        let mut loader = SceneLoader::<Wgpu>::new(&Scene::new(vec![]), 42, false, &device);
+
+        let waiting = tokio::select! {
+            biased;
+            _ = loader.next_batch() => false,
+            () = async {
+                for _ in 0..16 {
+                    tokio::task::yield_now().await;
+                }
+            } => true,
+        };
+        assert!(waiting, "An empty loader shouldn't have batches");
+
+        loader.add_views(vec![view("a.png"), view("b.png")]);
+        let mut paths = vec![];
+        for _ in 0..2 {
+            let batch = loader.next_batch().await.expect("Failed to load batch");
+            paths.push(batch.gt_view.path);
+        }
+        paths.sort();
+        assert_eq!(
+            paths,
+            ["a.png", "b.png"],
+            "Should load each added view once per epoch"
+        );
     }
 }
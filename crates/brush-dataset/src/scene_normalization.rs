@@ -0,0 +1,324 @@
+use anyhow::Result;
+use brush_render::{
+    camera::Camera,
+    gaussian_splats::Splats,
+    sh::{sh_coeffs_for_degree, sh_rotation_matrix},
+};
+use brush_train::scene::{Scene, SceneView};
+use burn::{
+    prelude::Backend,
+    tensor::{Tensor, TensorData},
+};
+use glam::{Mat3, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::Dataset;
+
+/// What to center a scene on when normalizing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum SceneCenter {
+    /// The average position of all cameras.
+    Cameras,
+    /// The median of the initial points, falling back to the cameras if there are none.
+    Points,
+}
+
+/// A similarity transform from the original coordinates of a dataset to the coordinates it's
+/// trained in. Applies as `scale * (rotation * p) + translation`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldTransform {
+    pub scale: f32,
+    pub rotation: Quat,
+    pub translation: Vec3,
+}
+
+impl Default for WorldTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl WorldTransform {
+    pub const IDENTITY: Self = Self {
+        scale: 1.0,
+        rotation: Quat::IDENTITY,
+        translation: Vec3::ZERO,
+    };
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        let scale = 1.0 / self.scale;
+        Self {
+            scale,
+            rotation,
+            translation: -scale * (rotation * self.translation),
+        }
+    }
+
+    /// Apply `other` first, then this transform.
+    pub fn after(&self, other: &Self) -> Self {
+        Self {
+            scale: self.scale * other.scale,
+            rotation: (self.rotation * other.rotation).normalize(),
+            translation: self.transform_point(other.translation),
+        }
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.scale * (self.rotation * point) + self.translation
+    }
+
+    pub fn transform_direction(&self, dir: Vec3) -> Vec3 {
+        self.rotation * dir
+    }
+
+    pub fn transform_camera(&self, camera: &Camera) -> Camera {
+        Camera {
+            position: self.transform_point(camera.position),
+            rotation: (self.rotation * camera.rotation).normalize(),
+            ..camera.clone()
+        }
+    }
+
+    fn transform_scene(&self, scene: &Scene) -> Scene {
+        Scene::new(
+            scene
+                .views
+                .iter()
+                .map(|view| SceneView {
+                    camera: self.transform_camera(&view.camera),
+                    ..view.clone()
+                })
+                .collect(),
+        )
+    }
+
+    /// Transform all views of the dataset. The transform is recorded on the dataset, on top of any
+    /// transform it already had.
+    pub fn transform_dataset(&self, dataset: &Dataset) -> Dataset {
+        Dataset {
+            train: self.transform_scene(&dataset.train),
+            eval: dataset.eval.as_ref().map(|e| self.transform_scene(e)),
+            world_transform: self.after(&dataset.world_transform),
+        }
+    }
+
+    /// Transform splats, including their orientation, size and view dependent color.
+    pub fn transform_splats<B: Backend>(&self, splats: Splats<B>) -> Splats<B> {
+        let device = splats.device();
+        let [n, n_coeffs, _] = splats.sh_coeffs.dims();
+
+        let mat_tensor = |data: Vec<f32>, size: usize| {
+            Tensor::<B, 2>::from_data(TensorData::new(data, [size, size]), &device)
+        };
+
+        // Row vectors are transformed as p * R^T. A column major R is a row major R^T.
+        let rot_t = mat_tensor(Mat3::from_quat(self.rotation).to_cols_array().to_vec(), 3);
+        let translation = Tensor::<B, 2>::from_data(
+            TensorData::new(self.translation.to_array().to_vec(), [1, 3]),
+            &device,
+        );
+        let means = splats.means.val().matmul(rot_t) * self.scale + translation;
+
+        // Quaternions are stored as [w, x, y, z], prepend the rotation: q' = r * q.
+        let Quat { x, y, z, w } = self.rotation;
+        let quat_mul_t = mat_tensor(
+            vec![
+                w, x, y, z, //
+                -x, w, z, -y, //
+                -y, -z, w, x, //
+                -z, y, -x, w,
+            ],
+            4,
+        );
+        let rotation = splats.rotation.val().matmul(quat_mul_t);
+
+        let log_scales = splats.log_scales.val() + self.scale.ln();
+
+        // Rotate the SH coefficients of all splats & channels at once, as [n * 3, coeffs] rows.
+        let degree = splats.sh_degree();
+        let sh_rot = mat_tensor(
+            sh_rotation_matrix(degree, self.rotation),
+            sh_coeffs_for_degree(degree) as usize,
+        );
+        let sh_coeffs = splats
+            .sh_coeffs
+            .val()
+            .swap_dims(1, 2)
+            .reshape([n * 3, n_coeffs])
+            .matmul(sh_rot.transpose())
+            .reshape([n, 3, n_coeffs])
+            .swap_dims(1, 2);
+
+        Splats::from_tensor_data(
+            means,
+            rotation,
+            log_scales,
+            sh_coeffs,
+            splats.raw_opacity.val(),
+        )
+    }
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+/// Find the transform that centers the dataset, scales the cameras to fit in a unit sphere, and
+/// aligns `up` with +Y.
+///
+/// `points` are the initial points of the scene, if any, and only used to center on with
+/// [`SceneCenter::Points`].
+pub fn normalization_transform(
+    dataset: &Dataset,
+    points: Option<&[Vec3]>,
+    center: SceneCenter,
+    up: Vec3,
+) -> WorldTransform {
+    let positions: Vec<Vec3> = dataset
+        .train
+        .views
+        .iter()
+        .chain(dataset.eval.iter().flat_map(|e| e.views.iter()))
+        .map(|v| v.camera.position)
+        .collect();
+
+    if positions.is_empty() {
+        return WorldTransform::IDENTITY;
+    }
+
+    let camera_center = positions.iter().sum::<Vec3>() / positions.len() as f32;
+    let origin = match (center, points) {
+        (SceneCenter::Points, Some(points)) if !points.is_empty() => Vec3::new(
+            median(points.iter().map(|p| p.x).collect()),
+            median(points.iter().map(|p| p.y).collect()),
+            median(points.iter().map(|p| p.z).collect()),
+        ),
+        (SceneCenter::Points, _) => {
+            log::warn!("No initial points to center the scene on, using the cameras instead.");
+            camera_center
+        }
+        (SceneCenter::Cameras, _) => camera_center,
+    };
+
+    let radius = positions
+        .iter()
+        .map(|p| p.distance(origin))
+        .fold(0.0f32, f32::max);
+    let scale = if radius > f32::EPSILON {
+        1.0 / radius
+    } else {
+        1.0
+    };
+
+    let rotation = if up.length_squared() > 0.0 {
+        Quat::from_rotation_arc(up.normalize(), Vec3::Y)
+    } else {
+        Quat::IDENTITY
+    };
+
+    WorldTransform {
+        scale,
+        rotation,
+        translation: -scale * (rotation * origin),
+    }
+}
+
+/// Read the positions of splats, to center on with [`SceneCenter::Points`].
+pub async fn splat_positions<B: Backend>(splats: &Splats<B>) -> Result<Vec<Vec3>> {
+    let means: Vec<f32> = splats.means.val().into_data_async().await.to_vec()?;
+    Ok(means
+        .chunks_exact(3)
+        .map(|c| Vec3::new(c[0], c[1], c[2]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::gaussian_splats::Splats;
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::{Tensor, TensorData},
+    };
+    use glam::{EulerRot, Quat, Vec3};
+
+    use super::WorldTransform;
+
+    fn tensor<const D: usize>(shape: [usize; D], device: &WgpuDevice) -> Tensor<Wgpu, D> {
+        let len = shape.iter().product();
+        let data: Vec<f32> = (0..len)
+            .map(|i| (i as f32 * 0.37).sin() * 0.8 + 0.1)
+            .collect();
+        Tensor::from_data(TensorData::new(data, shape), device)
+    }
+
+    async fn values<const D: usize>(tensor: Tensor<Wgpu, D>) -> Vec<f32> {
+        tensor
+            .into_data_async()
+            .await
+            .to_vec()
+            .expect("Failed to read floats")
+    }
+
+    async fn assert_close<const D: usize>(a: Tensor<Wgpu, D>, b: Tensor<Wgpu, D>, what: &str) {
+        let (a, b) = (values(a).await, values(b).await);
+        assert_eq!(a.len(), b.len(), "{what} should have the same size");
+        for (i, (a, b)) in a.iter().zip(&b).enumerate() {
+            assert!((a - b).abs() < 1e-4, "{what} differ at {i}: {a} vs {b}");
+        }
+    }
+
+    #[tokio::test]
+    async fn transform_splats_round_trip() {
+        let device = WgpuDevice::DefaultDevice;
+        let n = 16;
+        // Degree 3 SH, to rotate all bands.
+        let splats = Splats::<Wgpu>::from_tensor_data(
+            tensor([n, 3], &device),
+            tensor([n, 4], &device),
+            tensor([n, 3], &device),
+            tensor([n, 16, 3], &device),
+            tensor([n], &device),
+        );
+        let transform = WorldTransform {
+            scale: 2.5,
+            rotation: Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.0),
+            translation: Vec3::new(1.0, -2.0, 0.5),
+        };
+
+        let transformed = transform.transform_splats(splats.clone());
+        let means = values(splats.means.val()).await;
+        let transformed_means = values(transformed.means.val()).await;
+        let expected = transform.transform_point(Vec3::from_slice(&means[..3]));
+        assert!(
+            Vec3::from_slice(&transformed_means[..3]).abs_diff_eq(expected, 1e-4),
+            "Means should be transformed like points"
+        );
+
+        let round_trip = transform.inverse().transform_splats(transformed);
+        assert_close(splats.means.val(), round_trip.means.val(), "Means").await;
+        assert_close(
+            splats.rotation.val(),
+            round_trip.rotation.val(),
+            "Rotations",
+        )
+        .await;
+        assert_close(
+            splats.log_scales.val(),
+            round_trip.log_scales.val(),
+            "Scales",
+        )
+        .await;
+        assert_close(
+            splats.sh_coeffs.val(),
+            round_trip.sh_coeffs.val(),
+            "SH coefficients",
+        )
+        .await;
+    }
+}
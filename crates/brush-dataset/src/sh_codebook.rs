@@ -0,0 +1,482 @@
+use std::ops::Range;
+
+use anyhow::anyhow;
+use brush_render::gaussian_splats::Splats;
+use burn::{
+    prelude::Backend,
+    tensor::{Int, Tensor, TensorData},
+};
+use rand::{SeedableRng, seq::index};
+
+use crate::CompressionConfig;
+
+/// Names of the per-splat properties that are stored next to the SH index, in the order they're written.
+pub(crate) const VERTEX_PROPERTIES: [&str; 14] = [
+    "x", "y", "z", "scale_0", "scale_1", "scale_2", "opacity", "rot_0", "rot_1", "rot_2", "rot_3",
+    "f_dc_0", "f_dc_1", "f_dc_2",
+];
+
+// Max nr. of splats to fit the codebook on. Assigning all splats to the codebook afterwards
+// is cheap, iterating k-means over millions of splats is not.
+const MAX_FIT_SAMPLES: usize = 1 << 18;
+
+// Nr. of rows to compute codebook distances for at once. Keeps the [rows, codebook] distance matrix small.
+const ASSIGN_CHUNK: usize = 1 << 14;
+
+/// Nr. of bits used for a vertex property when quantizing attributes.
+pub(crate) fn quant_bits(property: &str) -> u32 {
+    match property {
+        // Positions need a bit more precision to not visibly jitter.
+        "x" | "y" | "z" => 16,
+        _ => 8,
+    }
+}
+
+pub(crate) fn quantize(value: f32, (min, max): (f32, f32), bits: u32) -> u32 {
+    let max_quant = ((1u64 << bits) - 1) as f32;
+    if max <= min {
+        return 0;
+    }
+    (((value - min) / (max - min)) * max_quant)
+        .round()
+        .clamp(0.0, max_quant) as u32
+}
+
+pub(crate) fn dequantize(value: u32, (min, max): (f32, f32), bits: u32) -> f32 {
+    let max_quant = ((1u64 << bits) - 1) as f32;
+    min + (value as f32 / max_quant) * (max - min)
+}
+
+/// Splats where the higher order SH bands are replaced by an entry of a shared codebook.
+pub struct ShCodebookSplats<B: Backend> {
+    /// The splats as they look after decoding the compressed data.
+    ///
+    /// Useful to measure how much quality is lost by compressing.
+    pub splats: Splats<B>,
+
+    /// Codebook entries, [codebook size, rest coeffs]. Each entry is in the
+    /// inria format, aka [channels, coeffs].
+    pub(crate) codebook: Vec<f32>,
+    pub(crate) rest_coeffs: usize,
+    /// For each splat, the index of its codebook entry.
+    pub(crate) indices: Vec<u32>,
+    /// For each splat, the values of [`VERTEX_PROPERTIES`].
+    pub(crate) attributes: Vec<[f32; 14]>,
+    /// Min & max of each of the [`VERTEX_PROPERTIES`] if attributes are quantized.
+    pub(crate) quant_ranges: Option<[(f32, f32); 14]>,
+}
+
+impl<B: Backend> ShCodebookSplats<B> {
+    pub fn codebook_size(&self) -> usize {
+        if self.rest_coeffs == 0 {
+            0
+        } else {
+            self.codebook.len() / self.rest_coeffs
+        }
+    }
+}
+
+fn index_tensor<B: Backend>(indices: Vec<usize>, device: &B::Device) -> Tensor<B, 1, Int> {
+    let len = indices.len();
+    let indices: Vec<i32> = indices.into_iter().map(|i| i as i32).collect();
+    Tensor::from_data(TensorData::new(indices, [len]), device)
+}
+
+fn attribute_tensor<B: Backend>(
+    attributes: &[[f32; 14]],
+    columns: Range<usize>,
+    device: &B::Device,
+) -> Tensor<B, 2> {
+    let width = columns.len();
+    let data: Vec<f32> = attributes
+        .iter()
+        .flat_map(|attr| attr[columns.clone()].iter().copied())
+        .collect();
+    Tensor::from_data(TensorData::new(data, [attributes.len(), width]), device)
+}
+
+async fn read_floats<B: Backend, const D: usize>(tensor: Tensor<B, D>) -> anyhow::Result<Vec<f32>> {
+    tensor
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(|e| anyhow!("Failed to read splat data {e:?}"))
+}
+
+/// Find the index of the nearest centroid for each row of data.
+fn assign_clusters<B: Backend>(data: Tensor<B, 2>, centroids: Tensor<B, 2>) -> Tensor<B, 1, Int> {
+    let [n, _] = data.dims();
+
+    // |x - c|^2 = |x|^2 - 2 x.c + |c|^2, where |x|^2 doesn't change which centroid is nearest.
+    let centroid_norms = centroids.clone().powf_scalar(2.0).sum_dim(1).transpose();
+    let centroids_t = centroids.transpose();
+
+    let chunks = (0..n)
+        .step_by(ASSIGN_CHUNK)
+        .map(|start| {
+            let end = (start + ASSIGN_CHUNK).min(n);
+            let rows = data.clone().slice([start..end]);
+            let dist = centroid_norms.clone() - rows.matmul(centroids_t.clone()) * 2.0;
+            dist.argmin(1).squeeze::<1>(1)
+        })
+        .collect();
+
+    Tensor::cat(chunks, 0)
+}
+
+/// Lloyd's k-means. Returns the centroids & the centroid index for each row of data.
+fn kmeans<B: Backend>(
+    data: Tensor<B, 2>,
+    codebook_size: usize,
+    iters: u32,
+    rng: &mut impl rand::Rng,
+) -> (Tensor<B, 2>, Tensor<B, 1, Int>) {
+    let device = data.device();
+    let [n, dim] = data.dims();
+    let k = codebook_size.clamp(1, n);
+
+    let fit = if n > MAX_FIT_SAMPLES {
+        let inds = index::sample(rng, n, MAX_FIT_SAMPLES).into_vec();
+        data.clone().select(0, index_tensor(inds, &device))
+    } else {
+        data.clone()
+    };
+    let fit_count = fit.dims()[0];
+
+    // Initialize centroids as random distinct samples.
+    let inds = index::sample(rng, fit_count, k).into_vec();
+    let mut centroids = fit.clone().select(0, index_tensor(inds, &device));
+
+    for _ in 0..iters {
+        let assignment = assign_clusters(fit.clone(), centroids.clone());
+
+        let sums =
+            Tensor::zeros([k, dim], &device).select_assign(0, assignment.clone(), fit.clone());
+        let counts = Tensor::<B, 1>::zeros([k], &device).select_assign(
+            0,
+            assignment,
+            Tensor::ones([fit_count], &device),
+        );
+        let updated = sums / counts.clone().clamp_min(1.0).unsqueeze_dim(1);
+
+        // Clusters without any members keep their previous centroid.
+        let empty = counts
+            .equal_elem(0.0)
+            .unsqueeze_dim::<2>(1)
+            .repeat_dim(1, dim);
+        centroids = updated.mask_where(empty, centroids);
+    }
+
+    let assignment = assign_clusters(data, centroids.clone());
+    (centroids, assignment)
+}
+
+/// Compress the splats by clustering the higher order SH coefficients into a codebook,
+/// and optionally quantizing the other attributes.
+pub async fn compress_splats<B: Backend>(
+    splats: Splats<B>,
+    config: &CompressionConfig,
+    seed: u64,
+) -> anyhow::Result<ShCodebookSplats<B>> {
+    let splats = splats.with_normed_rotations();
+    let device = splats.device();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+
+    let [n, coeffs, _] = splats.sh_coeffs.dims();
+    anyhow::ensure!(n > 0, "Can't compress splats without any splats.");
+    let rest_coeffs = (coeffs - 1) * 3;
+
+    let (centroids, assignment) = if rest_coeffs > 0 {
+        // Permute rest coefficients to inria format ([n, channel, coeffs]).
+        let sh_rest = splats
+            .sh_coeffs
+            .val()
+            .slice([0..n, 1..coeffs])
+            .permute([0, 2, 1])
+            .reshape([n, rest_coeffs]);
+
+        let (centroids, assignment) = kmeans(
+            sh_rest,
+            config.sh_codebook_size as usize,
+            config.sh_codebook_iters,
+            &mut rng,
+        );
+        (Some(centroids), Some(assignment))
+    } else {
+        (None, None)
+    };
+
+    let codebook = if let Some(centroids) = centroids.clone() {
+        read_floats(centroids).await?
+    } else {
+        vec![]
+    };
+
+    let indices = if let Some(assignment) = assignment.clone() {
+        assignment
+            .into_data_async()
+            .await
+            .convert::<i32>()
+            .to_vec::<i32>()
+            .map_err(|e| anyhow!("Failed to read codebook indices {e:?}"))?
+            .into_iter()
+            .map(|i| i as u32)
+            .collect()
+    } else {
+        vec![0; n]
+    };
+
+    let means = read_floats(splats.means.val()).await?;
+    let log_scales = read_floats(splats.log_scales.val()).await?;
+    let opacities = read_floats(splats.raw_opacity.val()).await?;
+    let rotations = read_floats(splats.rotation.val()).await?;
+    let sh_dc = read_floats(splats.sh_coeffs.val().slice([0..n, 0..1])).await?;
+
+    let mut attributes: Vec<[f32; 14]> = (0..n)
+        .map(|i| {
+            [
+                means[i * 3],
+                means[i * 3 + 1],
+                means[i * 3 + 2],
+                log_scales[i * 3],
+                log_scales[i * 3 + 1],
+                log_scales[i * 3 + 2],
+                opacities[i],
+                // Rotations are already in scalar first form, which matches rot_0..rot_3.
+                rotations[i * 4],
+                rotations[i * 4 + 1],
+                rotations[i * 4 + 2],
+                rotations[i * 4 + 3],
+                sh_dc[i * 3],
+                sh_dc[i * 3 + 1],
+                sh_dc[i * 3 + 2],
+            ]
+        })
+        .collect();
+
+    let quant_ranges = if config.quantize_attributes {
+        let mut ranges = [(f32::INFINITY, f32::NEG_INFINITY); 14];
+        for attr in &attributes {
+            for (range, &val) in ranges.iter_mut().zip(attr) {
+                range.0 = range.0.min(val);
+                range.1 = range.1.max(val);
+            }
+        }
+
+        // Round trip the values so the decoded splats match what ends up in the file.
+        for attr in &mut attributes {
+            for ((val, &range), name) in attr.iter_mut().zip(&ranges).zip(VERTEX_PROPERTIES) {
+                let bits = quant_bits(name);
+                *val = dequantize(quantize(*val, range, bits), range, bits);
+            }
+        }
+        Some(ranges)
+    } else {
+        None
+    };
+
+    let sh_dc = attribute_tensor::<B>(&attributes, 11..14, &device).reshape([n, 1, 3]);
+    let sh_coeffs = if let (Some(centroids), Some(assignment)) = (centroids, assignment) {
+        let sh_rest = centroids
+            .select(0, assignment)
+            .reshape([n, 3, coeffs - 1])
+            .permute([0, 2, 1]);
+        Tensor::cat(vec![sh_dc, sh_rest], 1)
+    } else {
+        sh_dc
+    };
+
+    let decoded = Splats::from_tensor_data(
+        attribute_tensor(&attributes, 0..3, &device),
+        attribute_tensor(&attributes, 7..11, &device),
+        attribute_tensor(&attributes, 3..6, &device),
+        sh_coeffs,
+        attribute_tensor::<B>(&attributes, 6..7, &device).reshape([n]),
+    );
+
+    Ok(ShCodebookSplats {
+        splats: decoded,
+        codebook,
+        rest_coeffs,
+        indices,
+        attributes,
+        quant_ranges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::gaussian_splats::Splats;
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::{Tensor, TensorData},
+    };
+    use glam::{Quat, Vec3};
+    use rand::SeedableRng;
+    use tokio_stream::StreamExt;
+
+    use super::{compress_splats, kmeans};
+    use crate::{
+        CompressionConfig, splat_export::codebook_splats_to_ply, splat_import::load_splat_from_ply,
+    };
+
+    async fn floats<const D: usize>(tensor: Tensor<Wgpu, D>) -> Vec<f32> {
+        tensor
+            .into_data_async()
+            .await
+            .to_vec()
+            .expect("Failed to read floats")
+    }
+
+    #[tokio::test]
+    async fn kmeans_two_clusters() {
+        let device = WgpuDevice::DefaultDevice;
+        // Two tight clusters around 0 and 10.
+        let data: Vec<f32> = (0..64)
+            .flat_map(|i| {
+                let center = if i < 32 { 0.0 } else { 10.0 };
+                let offset = (i % 4) as f32 * 0.05;
+                [center + offset, center - offset]
+            })
+            .collect();
+        let data = Tensor::<Wgpu, 2>::from_data(TensorData::new(data, [64, 2]), &device);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let (centroids, assignment) = kmeans(data, 2, 10, &mut rng);
+        let assignment: Vec<i32> = assignment
+            .into_data_async()
+            .await
+            .convert::<i32>()
+            .to_vec()
+            .expect("Failed to read assignment");
+
+        assert!(
+            assignment[..32].iter().all(|&a| a == assignment[0]),
+            "First cluster should share a centroid"
+        );
+        assert!(
+            assignment[32..].iter().all(|&a| a == assignment[32]),
+            "Second cluster should share a centroid"
+        );
+        assert_ne!(
+            assignment[0], assignment[32],
+            "Clusters should have different centroids"
+        );
+
+        let centroids = floats(centroids).await;
+        let first = assignment[0] as usize * 2;
+        let second = assignment[32] as usize * 2;
+        assert!(
+            (centroids[first] - 0.075).abs() < 1e-4,
+            "First centroid should be the cluster mean"
+        );
+        assert!(
+            (centroids[second] - 10.075).abs() < 1e-4,
+            "Second centroid should be the cluster mean"
+        );
+    }
+
+    fn test_splats(device: &WgpuDevice) -> Splats<Wgpu> {
+        let n = 64;
+        let means: Vec<_> = (0..n)
+            .map(|i| Vec3::new(i as f32 * 0.1, (i % 7) as f32 - 3.0, (i % 5) as f32 * 0.5))
+            .collect();
+        let rotations: Vec<_> = (0..n)
+            .map(|i| Quat::from_rotation_y(i as f32 * 0.1))
+            .collect();
+        let log_scales: Vec<_> = (0..n)
+            .map(|i| Vec3::splat(-2.0 - (i % 3) as f32 * 0.5))
+            .collect();
+        // SH degree 1, with a few distinct patterns for the rest coefficients.
+        let sh_coeffs: Vec<f32> = (0..n)
+            .flat_map(|i| {
+                let pattern = (i % 4) as f32;
+                (0..12).map(move |c| {
+                    if c < 3 {
+                        i as f32 * 0.01
+                    } else {
+                        pattern * 0.1 - c as f32 * 0.01
+                    }
+                })
+            })
+            .collect();
+        let opacities: Vec<f32> = (0..n).map(|i| (i % 9) as f32 - 4.0).collect();
+        Splats::from_raw(
+            &means,
+            Some(&rotations),
+            Some(&log_scales),
+            Some(&sh_coeffs),
+            Some(&opacities),
+            device,
+        )
+    }
+
+    async fn assert_splats_eq(a: Splats<Wgpu>, b: Splats<Wgpu>, tolerance: f32, case: &str) {
+        for (name, a, b) in [
+            (
+                "means",
+                floats(a.means.val()).await,
+                floats(b.means.val()).await,
+            ),
+            (
+                "rotations",
+                floats(a.rotation.val()).await,
+                floats(b.rotation.val()).await,
+            ),
+            (
+                "scales",
+                floats(a.log_scales.val()).await,
+                floats(b.log_scales.val()).await,
+            ),
+            (
+                "sh",
+                floats(a.sh_coeffs.val()).await,
+                floats(b.sh_coeffs.val()).await,
+            ),
+            (
+                "opacity",
+                floats(a.raw_opacity.val()).await,
+                floats(b.raw_opacity.val()).await,
+            ),
+        ] {
+            assert_eq!(a.len(), b.len(), "Different nr. of {name} values, {case}");
+            let max_diff = a
+                .iter()
+                .zip(&b)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            assert!(max_diff <= tolerance, "{name} differ by {max_diff}, {case}");
+        }
+    }
+
+    #[tokio::test]
+    async fn codebook_ply_round_trip() {
+        let device = WgpuDevice::DefaultDevice;
+
+        for quantize_attributes in [false, true] {
+            let config = CompressionConfig::new()
+                .with_export_sh_codebook(true)
+                .with_sh_codebook_size(4)
+                .with_quantize_attributes(quantize_attributes);
+            let compressed = compress_splats(test_splats(&device), &config, 0)
+                .await
+                .expect("Failed to compress splats");
+            assert_eq!(compressed.codebook_size(), 4, "Codebook size");
+
+            let ply = codebook_splats_to_ply(&compressed);
+            let mut stream = std::pin::pin!(load_splat_from_ply::<_, Wgpu>(
+                std::io::Cursor::new(ply),
+                None,
+                device.clone(),
+            ));
+            let mut loaded = None;
+            while let Some(message) = stream.next().await {
+                loaded = Some(message.expect("Failed to read codebook ply").splats);
+            }
+            let loaded = loaded.expect("No splats in codebook ply");
+
+            let case = format!("quantized: {quantize_attributes}");
+            assert_splats_eq(compressed.splats, loaded, 1e-4, &case).await;
+        }
+    }
+}
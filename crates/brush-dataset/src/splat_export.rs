@@ -1,12 +1,72 @@
-use crate::parsed_gaussian::ParsedGaussian;
+use crate::{
+    extra_properties::ExtraProperties,
+    parsed_gaussian::ParsedGaussian,
+    sh_codebook::{ShCodebookSplats, VERTEX_PROPERTIES, quant_bits, quantize},
+};
 use anyhow::anyhow;
 use brush_render::gaussian_splats::Splats;
 use burn::{prelude::Backend, tensor::DataError};
 use glam::{Quat, Vec3};
 use ply_rs::{
-    ply::{self, Ply, PropertyDef, PropertyType, ScalarType},
+    ply::{self, Ply, PropertyAccess, PropertyDef, PropertyType, ScalarType},
     writer::Writer,
 };
+use safetensors::{Dtype, tensor::TensorView};
+use std::{collections::HashMap, sync::Arc};
+
+/// A gaussian together with the names of its extra properties, to write them out.
+#[derive(Default)]
+struct ExportGaussian {
+    gaussian: ParsedGaussian<false>,
+    extra_names: Arc<[String]>,
+}
+
+impl ExportGaussian {
+    fn extra(&self, key: &str) -> Option<f64> {
+        let index = self.extra_names.iter().position(|name| name == key)?;
+        self.gaussian.extra.get(index).copied()
+    }
+}
+
+impl PropertyAccess for ExportGaussian {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_char(&self, key: &str) -> Option<i8> {
+        self.extra(key).map(|v| v as i8)
+    }
+
+    fn get_uchar(&self, key: &str) -> Option<u8> {
+        self.extra(key).map(|v| v as u8)
+    }
+
+    fn get_short(&self, key: &str) -> Option<i16> {
+        self.extra(key).map(|v| v as i16)
+    }
+
+    fn get_ushort(&self, key: &str) -> Option<u16> {
+        self.extra(key).map(|v| v as u16)
+    }
+
+    fn get_int(&self, key: &str) -> Option<i32> {
+        self.extra(key).map(|v| v as i32)
+    }
+
+    fn get_uint(&self, key: &str) -> Option<u32> {
+        self.extra(key).map(|v| v as u32)
+    }
+
+    fn get_float(&self, key: &str) -> Option<f32> {
+        self.gaussian
+            .get_float(key)
+            .or_else(|| self.extra(key).map(|v| v as f32))
+    }
+
+    fn get_double(&self, key: &str) -> Option<f64> {
+        self.extra(key)
+    }
+}
 
 async fn read_splat_data<B: Backend>(
     splats: Splats<B>,
@@ -60,6 +120,7 @@ async fn read_splat_data<B: Backend>(
                 ),
                 sh_dc,
                 sh_coeffs_rest,
+                extra: vec![],
             }
         })
         .collect();
@@ -68,6 +129,16 @@ async fn read_splat_data<B: Backend>(
 }
 
 pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u8>> {
+    splat_to_ply_with_properties(splats, &ExtraProperties::default()).await
+}
+
+/// Serialize splats to a ply file, including any extra properties that were loaded with them.
+///
+/// Extra properties are only written if they still match the splats one to one.
+pub async fn splat_to_ply_with_properties<B: Backend>(
+    splats: Splats<B>,
+    extra_properties: &ExtraProperties,
+) -> anyhow::Result<Vec<u8>> {
     let splats = splats.with_normed_rotations();
 
     let data = read_splat_data(splats.clone())
@@ -93,7 +164,40 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
         ));
     }
 
-    let mut ply: Ply<ParsedGaussian<false>> = Ply::new();
+    let extra_properties = if extra_properties.num_splats() == data.len() {
+        extra_properties.clone()
+    } else {
+        if !extra_properties.is_empty() {
+            log::warn!("Extra ply properties don't match the splats anymore, not exporting them.");
+        }
+        ExtraProperties::default()
+    };
+
+    for (name, ty) in &extra_properties.properties {
+        properties.push(PropertyDef::new(name, PropertyType::Scalar(ty.clone())));
+    }
+
+    let extra_names: Arc<[String]> = extra_properties
+        .properties
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let data = data
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut gaussian)| {
+            if !extra_properties.is_empty() {
+                gaussian.extra = extra_properties.row(i).to_vec();
+            }
+            ExportGaussian {
+                gaussian,
+                extra_names: extra_names.clone(),
+            }
+        })
+        .collect();
+
+    let mut ply: Ply<ExportGaussian> = Ply::new();
 
     // Create PLY header
     let mut vertex = ply::ElementDef::new("vertex");
@@ -105,7 +209,227 @@ pub async fn splat_to_ply<B: Backend>(splats: Splats<B>) -> anyhow::Result<Vec<u
     ply.payload.insert("vertex".to_owned(), data);
 
     let mut buf = vec![];
-    let writer = Writer::<ParsedGaussian<false>>::new();
+    let writer = Writer::<ExportGaussian>::new();
     writer.write_ply(&mut buf, &mut ply)?;
     Ok(buf)
 }
+
+async fn tensor_bytes<B: Backend, const D: usize>(
+    tensor: burn::tensor::Tensor<B, D>,
+) -> anyhow::Result<(Vec<usize>, Vec<u8>)> {
+    let shape = tensor.dims().to_vec();
+    let data: Vec<f32> = tensor
+        .into_data_async()
+        .await
+        .to_vec()
+        .map_err(|e| anyhow!("Failed to read data from splat {e:?}"))?;
+    Ok((shape, data.iter().flat_map(|f| f.to_le_bytes()).collect()))
+}
+
+/// Serialize splats to a safetensors file.
+///
+/// Unlike the ply export this stores the exact (pre-activation) parameters as they are trained,
+/// with the SH coefficients in Brush's [n, coeffs, channels] layout. The SH degree and up axis
+/// are stored in the metadata.
+pub async fn splat_to_safetensors<B: Backend>(
+    splats: Splats<B>,
+    up_axis: Option<Vec3>,
+) -> anyhow::Result<Vec<u8>> {
+    let sh_degree = splats.sh_degree();
+
+    let tensors = [
+        ("means", tensor_bytes(splats.means.val()).await?),
+        ("rotation", tensor_bytes(splats.rotation.val()).await?),
+        ("log_scales", tensor_bytes(splats.log_scales.val()).await?),
+        ("sh_coeffs", tensor_bytes(splats.sh_coeffs.val()).await?),
+        ("raw_opacity", tensor_bytes(splats.raw_opacity.val()).await?),
+    ];
+
+    let views = tensors
+        .iter()
+        .map(|(name, (shape, data))| Ok((*name, TensorView::new(Dtype::F32, shape.clone(), data)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut metadata = HashMap::from([
+        ("format".to_owned(), "brush_splats".to_owned()),
+        ("sh_degree".to_owned(), sh_degree.to_string()),
+    ]);
+    if let Some(up) = up_axis {
+        metadata.insert("up_axis".to_owned(), format!("{},{},{}", up.x, up.y, up.z));
+    }
+
+    Ok(safetensors::serialize(views, &Some(metadata))?)
+}
+
+/// Serialize codebook compressed splats to a ply file.
+///
+/// The codebook is stored as a separate `sh_codebook` element, and each vertex references an entry
+/// with its `sh_index`. When attributes are quantized, their ranges are stored in a `quant_range` element.
+pub fn codebook_splats_to_ply<B: Backend>(compressed: &ShCodebookSplats<B>) -> Vec<u8> {
+    let mut header = String::from("ply\nformat binary_little_endian 1.0\n");
+    header.push_str("comment Exported from Brush\n");
+    header.push_str("comment Vertical axis: y\n");
+
+    if compressed.quant_ranges.is_some() {
+        header.push_str("element quant_range 1\n");
+        for name in VERTEX_PROPERTIES {
+            header.push_str(&format!("property float min_{name}\n"));
+            header.push_str(&format!("property float max_{name}\n"));
+        }
+    }
+
+    header.push_str(&format!(
+        "element sh_codebook {}\n",
+        compressed.codebook_size()
+    ));
+    for i in 0..compressed.rest_coeffs {
+        header.push_str(&format!("property float f_rest_{i}\n"));
+    }
+
+    header.push_str(&format!("element vertex {}\n", compressed.attributes.len()));
+    for name in VERTEX_PROPERTIES {
+        let ty = match (compressed.quant_ranges.is_some(), quant_bits(name)) {
+            (false, _) => "float",
+            (true, 16) => "ushort",
+            (true, _) => "uchar",
+        };
+        header.push_str(&format!("property {ty} {name}\n"));
+    }
+    if compressed.rest_coeffs > 0 {
+        header.push_str("property uint sh_index\n");
+    }
+    header.push_str("end_header\n");
+
+    let mut buf = header.into_bytes();
+
+    if let Some(ranges) = compressed.quant_ranges {
+        for (min, max) in ranges {
+            buf.extend(min.to_le_bytes());
+            buf.extend(max.to_le_bytes());
+        }
+    }
+
+    for val in &compressed.codebook {
+        buf.extend(val.to_le_bytes());
+    }
+
+    for (attr, index) in compressed.attributes.iter().zip(&compressed.indices) {
+        for (i, (&val, name)) in attr.iter().zip(VERTEX_PROPERTIES).enumerate() {
+            if let Some(ranges) = compressed.quant_ranges {
+                let bits = quant_bits(name);
+                let quant = quantize(val, ranges[i], bits);
+                if bits == 16 {
+                    buf.extend((quant as u16).to_le_bytes());
+                } else {
+                    buf.push(quant as u8);
+                }
+            } else {
+                buf.extend(val.to_le_bytes());
+            }
+        }
+
+        if compressed.rest_coeffs > 0 {
+            buf.extend(index.to_le_bytes());
+        }
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use brush_render::gaussian_splats::Splats;
+    use burn::{
+        backend::{Wgpu, wgpu::WgpuDevice},
+        tensor::{Tensor, TensorData},
+    };
+    use glam::Vec3;
+    use safetensors::SafeTensors;
+    use tokio_stream::StreamExt;
+
+    use super::splat_to_safetensors;
+    use crate::splat_import::load_splat_from_safetensors;
+
+    fn tensor<const D: usize>(shape: [usize; D], device: &WgpuDevice) -> Tensor<Wgpu, D> {
+        // Values that don't survive a round trip through text or f16.
+        let len = shape.iter().product();
+        let data: Vec<f32> = (0..len)
+            .map(|i| (i as f32 + 0.1).sqrt() / 3.0 - 0.7)
+            .collect();
+        Tensor::from_data(TensorData::new(data, shape), device)
+    }
+
+    async fn bits<const D: usize>(tensor: Tensor<Wgpu, D>) -> Vec<u32> {
+        let data: Vec<f32> = tensor
+            .into_data_async()
+            .await
+            .to_vec()
+            .expect("Failed to read floats");
+        data.into_iter().map(f32::to_bits).collect()
+    }
+
+    #[tokio::test]
+    async fn safetensors_round_trip() {
+        let device = WgpuDevice::DefaultDevice;
+        let n = 37;
+        let splats = Splats::<Wgpu>::from_tensor_data(
+            tensor([n, 3], &device),
+            tensor([n, 4], &device),
+            tensor([n, 3], &device),
+            tensor([n, 9, 3], &device),
+            tensor([n], &device),
+        );
+        let up_axis = Vec3::new(0.1, -0.9, 0.3);
+
+        let data = splat_to_safetensors(splats.clone(), Some(up_axis))
+            .await
+            .expect("Failed to export safetensors");
+
+        let (_, metadata) = SafeTensors::read_metadata(&data).expect("Invalid safetensors");
+        let metadata = metadata.metadata().clone().expect("Missing metadata");
+        assert_eq!(
+            metadata.get("sh_degree").map(String::as_str),
+            Some("2"),
+            "SH degree metadata"
+        );
+
+        let mut stream = std::pin::pin!(load_splat_from_safetensors::<_, Wgpu>(
+            std::io::Cursor::new(data),
+            device.clone(),
+        ));
+        let message = stream
+            .next()
+            .await
+            .expect("No splats in safetensors")
+            .expect("Failed to load safetensors");
+        assert_eq!(message.meta.up_axis, Some(up_axis), "Up axis metadata");
+        assert_eq!(message.splats.sh_degree(), 2, "SH degree");
+
+        let loaded = message.splats;
+        assert_eq!(
+            bits(splats.means.val()).await,
+            bits(loaded.means.val()).await,
+            "Means should be identical"
+        );
+        assert_eq!(
+            bits(splats.rotation.val()).await,
+            bits(loaded.rotation.val()).await,
+            "Rotations should be identical"
+        );
+        assert_eq!(
+            bits(splats.log_scales.val()).await,
+            bits(loaded.log_scales.val()).await,
+            "Scales should be identical"
+        );
+        assert_eq!(
+            bits(splats.sh_coeffs.val()).await,
+            bits(loaded.sh_coeffs.val()).await,
+            "SH coefficients should be identical"
+        );
+        assert_eq!(
+            bits(splats.raw_opacity.val()).await,
+            bits(loaded.raw_opacity.val()).await,
+            "Opacities should be identical"
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use async_fn_stream::try_fn_stream;
-use brush_render::{gaussian_splats::inverse_sigmoid, sh::rgb_to_sh};
+use brush_render::{
+    gaussian_splats::inverse_sigmoid,
+    sh::{rgb_to_sh, sh_coeffs_for_degree},
+};
 use burn::{
     prelude::Backend,
     tensor::{Tensor, TensorData},
@@ -9,9 +12,10 @@ use burn::{
 use glam::{Quat, Vec3, Vec4};
 use ply_rs::{
     parser::Parser,
-    ply::{DefaultElement, ElementDef, Encoding, Header, Property, PropertyAccess},
+    ply::{DefaultElement, ElementDef, Encoding, Header, Property, PropertyAccess, PropertyType},
 };
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+use safetensors::{Dtype, SafeTensors};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
 use tokio_stream::{Stream, StreamExt};
 use tokio_with_wasm::alias as tokio_wasm;
 use tracing::trace_span;
@@ -19,7 +23,11 @@ use tracing::trace_span;
 use anyhow::{Context, Result};
 use brush_render::gaussian_splats::Splats;
 
-use crate::parsed_gaussian::ParsedGaussian;
+use crate::{
+    extra_properties::ExtraProperties,
+    parsed_gaussian::{Normalized, ParsedGaussian, is_known_property},
+    sh_codebook::VERTEX_PROPERTIES,
+};
 
 pub struct ParseMetadata {
     pub up_axis: Option<Vec3>,
@@ -31,12 +39,15 @@ pub struct ParseMetadata {
 pub struct SplatMessage<B: Backend> {
     pub meta: ParseMetadata,
     pub splats: Splats<B>,
+    /// Any per-splat properties Brush doesn't use, but which should be preserved on export.
+    pub extra_properties: ExtraProperties,
 }
 
 enum PlyFormat {
     Ply,
     Brush4DCompressed,
     SuperSplatCompressed,
+    ShCodebook,
 }
 
 fn interleave_coeffs(sh_dc: Vec3, sh_rest: &[f32], result: &mut Vec<f32>) {
@@ -131,6 +142,8 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
         let ply_type = if has_vertex && header.elements.first().is_some_and(|el| el.name == "chunk")
         {
             PlyFormat::SuperSplatCompressed
+        } else if has_vertex && header.elements.iter().any(|el| el.name == "sh_codebook") {
+            PlyFormat::ShCodebook
         } else if has_vertex && header.elements.iter().any(|el| el.name == "delta_vertex_") {
             PlyFormat::Brush4DCompressed
         } else if has_vertex {
@@ -171,12 +184,124 @@ pub fn load_splat_from_ply<T: AsyncRead + Unpin + 'static, B: Backend>(
                     emitter.emit(splat?).await;
                 }
             }
+            PlyFormat::ShCodebook => {
+                let mut stream = std::pin::pin!(parse_codebook_ply(
+                    reader,
+                    subsample_points,
+                    device,
+                    header,
+                    up_axis
+                ));
+                while let Some(splat) = stream.next().await {
+                    emitter.emit(splat?).await;
+                }
+            }
         };
 
         Ok(())
     })
 }
 
+fn safetensor_floats(tensors: &SafeTensors, name: &str) -> Result<(Vec<usize>, Vec<f32>)> {
+    let tensor = tensors
+        .tensor(name)
+        .with_context(|| format!("Missing tensor '{name}' in safetensors file."))?;
+    anyhow::ensure!(
+        tensor.dtype() == Dtype::F32,
+        "Tensor '{name}' must be f32, found {:?}",
+        tensor.dtype()
+    );
+    let data = tensor
+        .data()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    Ok((tensor.shape().to_vec(), data))
+}
+
+/// Load splats from a safetensors file, as written by [`crate::splat_export::splat_to_safetensors`].
+///
+/// Expects f32 tensors `means`, `rotation`, `log_scales`, `sh_coeffs` and `raw_opacity`.
+pub fn load_splat_from_safetensors<T: AsyncRead + Unpin + 'static, B: Backend>(
+    reader: T,
+    device: B::Device,
+) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
+    try_fn_stream(|emitter| async move {
+        let mut reader = reader;
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        let (_, metadata) = SafeTensors::read_metadata(&bytes)?;
+        let metadata = metadata.metadata().clone().unwrap_or_default();
+        let tensors = SafeTensors::deserialize(&bytes)?;
+
+        let (mean_shape, means) = safetensor_floats(&tensors, "means")?;
+        let (rot_shape, rotation) = safetensor_floats(&tensors, "rotation")?;
+        let (scale_shape, log_scales) = safetensor_floats(&tensors, "log_scales")?;
+        let (sh_shape, sh_coeffs) = safetensor_floats(&tensors, "sh_coeffs")?;
+        let (opac_shape, raw_opacity) = safetensor_floats(&tensors, "raw_opacity")?;
+
+        let n = mean_shape.first().copied().unwrap_or(0);
+        anyhow::ensure!(
+            mean_shape == [n, 3]
+                && rot_shape == [n, 4]
+                && scale_shape == [n, 3]
+                && sh_shape.len() == 3
+                && sh_shape[0] == n
+                && sh_shape[2] == 3
+                && opac_shape == [n],
+            "Unexpected tensor shapes in safetensors file."
+        );
+
+        if let Some(degree) = metadata.get("sh_degree") {
+            let degree: u32 = degree
+                .parse()
+                .with_context(|| format!("Invalid sh_degree metadata {degree}"))?;
+            anyhow::ensure!(
+                sh_coeffs_for_degree(degree) as usize == sh_shape[1],
+                "SH degree {degree} doesn't match {} SH coefficients.",
+                sh_shape[1]
+            );
+        }
+
+        let up_axis = metadata
+            .get("up_axis")
+            .map(|up| -> Result<Vec3> {
+                let axis: Vec<f32> = up
+                    .split(',')
+                    .map(|c| c.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .with_context(|| format!("Invalid up_axis metadata {up}"))?;
+                anyhow::ensure!(axis.len() == 3, "Invalid up_axis metadata {up}");
+                Ok(Vec3::new(axis[0], axis[1], axis[2]))
+            })
+            .transpose()?;
+
+        let splats = Splats::from_tensor_data(
+            Tensor::from_data(TensorData::new(means, mean_shape), &device),
+            Tensor::from_data(TensorData::new(rotation, rot_shape), &device),
+            Tensor::from_data(TensorData::new(log_scales, scale_shape), &device),
+            Tensor::from_data(TensorData::new(sh_coeffs, sh_shape), &device),
+            Tensor::from_data(TensorData::new(raw_opacity, opac_shape), &device),
+        );
+
+        emitter
+            .emit(SplatMessage {
+                meta: ParseMetadata {
+                    up_axis,
+                    total_splats: n as u32,
+                    frame_count: 0,
+                    current_frame: 0,
+                },
+                splats,
+                extra_properties: ExtraProperties::default(),
+            })
+            .await;
+
+        Ok(())
+    })
+}
+
 fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
     mut reader: T,
     subsample_points: Option<u32>,
@@ -193,6 +318,17 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
         let parser = Parser::<ParsedGaussian<false>>::new();
 
         let properties: HashSet<_> = vertex.properties.iter().map(|x| x.name.clone()).collect();
+        let mut extra_properties = ExtraProperties::new(
+            vertex
+                .properties
+                .iter()
+                .filter(|p| !is_known_property(&p.name))
+                .filter_map(|p| match &p.data_type {
+                    PropertyType::Scalar(ty) => Some((p.name.clone(), ty.clone())),
+                    PropertyType::List(..) => None,
+                })
+                .collect(),
+        );
         let mut means = Vec::with_capacity(vertex.count);
         let mut log_scales = properties
             .contains("scale_0")
@@ -215,6 +351,8 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
         for i in 0..vertex.count {
             yielder.try_yield().await;
 
+            let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
+
             // Doing this after first reading and parsing the points is quite wasteful, but
             // we do need to advance the reader.
             if let Some(subsample) = subsample_points {
@@ -223,8 +361,6 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                 }
             }
 
-            let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
-
             if !splat.is_finite() {
                 continue;
             }
@@ -242,6 +378,9 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             if let Some(sh_coeffs) = &mut sh_coeffs {
                 interleave_coeffs(splat.sh_dc, &splat.sh_coeffs_rest, sh_coeffs);
             }
+            if !extra_properties.is_empty() {
+                extra_properties.push_row(&splat.extra);
+            }
 
             if (i - last_update) >= update_every || i == vertex.count - 1 {
                 let splats = Splats::from_raw(
@@ -261,6 +400,7 @@ fn parse_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             current_frame: 0,
                         },
                         splats,
+                        extra_properties: extra_properties.clone(),
                     })
                     .await;
 
@@ -381,10 +521,13 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             // Occasionally yield.
             yielder.try_yield().await;
 
+            let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
+
             // Doing this after first reading and parsing the points is quite wasteful, but
             // we do need to advance the reader.
             if let Some(subsample) = subsample_points {
                 if i % subsample as usize != 0 {
+                    valid[i] = false;
                     continue;
                 }
             }
@@ -393,8 +536,6 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                 .get(i / 256)
                 .context("not enough quantization data to parse ply")?;
 
-            let splat = parse_elem(&mut reader, &parser, header.encoding, vertex).await?;
-
             // Don't add invalid splats.
             if !splat.is_finite() {
                 valid[i] = false;
@@ -431,6 +572,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             Some(&opacity),
                             &device,
                         ),
+                        extra_properties: ExtraProperties::default(),
                     })
                     .await;
                 last_update = i;
@@ -440,7 +582,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
         if let Some(sh_vals) = header.elements.get(2) {
             // Bit of a hack - use the unquantized parser as that handles SH values. Really we don't need
             // the entire splat parser though.
-            let parser = Parser::<ParsedGaussian<false>>::new();
+            let parser = Parser::<Normalized<ParsedGaussian<false>>>::new();
 
             if sh_vals.name != "sh" {
                 anyhow::bail!("Second element should be SH compression metadata!");
@@ -452,12 +594,13 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
             for i in 0..sh_vals.count {
                 yielder.try_yield().await;
 
-                if !valid[i] {
+                // Parse a splat - though nb only SH values will be used.
+                let Normalized(mut splat) =
+                    parse_elem(&mut reader, &parser, header.encoding, sh_vals).await?;
+
+                if !valid.get(i).copied().unwrap_or(false) {
                     continue;
                 }
-
-                // Parse a splat - though nb only SH values will be used.
-                let mut splat = parse_elem(&mut reader, &parser, header.encoding, sh_vals).await?;
                 for coeff in &mut splat.sh_coeffs_rest {
                     *coeff = 8.0 * (*coeff - 0.5);
                 }
@@ -487,6 +630,7 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                         Some(&opacity),
                         &device,
                     ),
+                    extra_properties: ExtraProperties::default(),
                 })
                 .await;
         }
@@ -495,6 +639,184 @@ fn parse_compressed_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
     })
 }
 
+fn parse_codebook_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
+    mut reader: T,
+    subsample_points: Option<u32>,
+    device: B::Device,
+    header: Header,
+    up_axis: Option<Vec3>,
+) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
+    #[derive(Default)]
+    struct CodebookVertex {
+        values: [f32; 14],
+        sh_index: u32,
+    }
+
+    impl PropertyAccess for CodebookVertex {
+        fn new() -> Self {
+            Self::default()
+        }
+
+        fn set_property(&mut self, key: &str, property: Property) {
+            if key == "sh_index" {
+                if let Property::UInt(index) = property {
+                    self.sh_index = index;
+                }
+                return;
+            }
+
+            let Some(slot) = VERTEX_PROPERTIES.iter().position(|p| *p == key) else {
+                return;
+            };
+
+            // Quantized values are normalized here, and mapped to their range afterwards.
+            self.values[slot] = match property {
+                Property::Float(value) => value,
+                Property::UChar(value) => value as f32 / u8::MAX as f32,
+                Property::UShort(value) => value as f32 / u16::MAX as f32,
+                _ => return,
+            };
+        }
+    }
+
+    /// A row of the codebook, with the rest coefficients in the inria format.
+    #[derive(Default)]
+    struct CodebookEntry {
+        coeffs: Vec<f32>,
+    }
+
+    impl PropertyAccess for CodebookEntry {
+        fn new() -> Self {
+            Self::default()
+        }
+
+        fn set_property(&mut self, key: &str, property: Property) {
+            let Some(idx) = key
+                .strip_prefix("f_rest_")
+                .and_then(|idx| idx.parse::<usize>().ok())
+            else {
+                return;
+            };
+            let Property::Float(value) = property else {
+                return;
+            };
+            if idx >= self.coeffs.len() {
+                self.coeffs.resize(idx + 1, 0.0);
+            }
+            self.coeffs[idx] = value;
+        }
+    }
+
+    try_fn_stream(|emitter| async move {
+        let mut yielder = TimeYield::new();
+
+        let mut ranges: HashMap<String, (f32, f32)> = HashMap::new();
+        let mut codebook: Vec<Vec<f32>> = vec![];
+
+        for element in &header.elements {
+            match element.name.as_str() {
+                "quant_range" => {
+                    let parser = Parser::<DefaultElement>::new();
+                    for _ in 0..element.count {
+                        let row =
+                            parse_elem(&mut reader, &parser, header.encoding, element).await?;
+                        for (key, prop) in row.iter() {
+                            let Property::Float(val) = *prop else {
+                                continue;
+                            };
+                            if let Some(name) = key.strip_prefix("min_") {
+                                ranges.entry(name.to_owned()).or_default().0 = val;
+                            } else if let Some(name) = key.strip_prefix("max_") {
+                                ranges.entry(name.to_owned()).or_default().1 = val;
+                            }
+                        }
+                    }
+                }
+                "sh_codebook" => {
+                    let parser = Parser::<CodebookEntry>::new();
+                    for _ in 0..element.count {
+                        yielder.try_yield().await;
+                        let entry =
+                            parse_elem(&mut reader, &parser, header.encoding, element).await?;
+                        codebook.push(entry.coeffs);
+                    }
+                }
+                "vertex" => {
+                    let parser = Parser::<CodebookVertex>::new();
+
+                    let mut means = Vec::with_capacity(element.count);
+                    let mut log_scales = Vec::with_capacity(element.count);
+                    let mut rotations = Vec::with_capacity(element.count);
+                    let mut sh_coeffs = Vec::with_capacity(element.count * 48);
+                    let mut opacity = Vec::with_capacity(element.count);
+
+                    let update_every = element.count.div_ceil(20);
+                    let mut last_update = 0;
+
+                    for i in 0..element.count {
+                        yielder.try_yield().await;
+
+                        let mut vertex =
+                            parse_elem(&mut reader, &parser, header.encoding, element).await?;
+
+                        // Doing this after first reading and parsing the points is quite wasteful, but
+                        // we do need to advance the reader.
+                        if let Some(subsample) = subsample_points {
+                            if i % subsample as usize != 0 {
+                                continue;
+                            }
+                        }
+
+                        for (val, name) in vertex.values.iter_mut().zip(VERTEX_PROPERTIES) {
+                            if let Some((min, max)) = ranges.get(name) {
+                                *val = min + *val * (max - min);
+                            }
+                        }
+
+                        let v = vertex.values;
+                        means.push(glam::vec3(v[0], v[1], v[2]));
+                        log_scales.push(glam::vec3(v[3], v[4], v[5]));
+                        opacity.push(v[6]);
+                        rotations.push(Quat::from_xyzw(v[8], v[9], v[10], v[7]));
+
+                        let sh_rest = codebook
+                            .get(vertex.sh_index as usize)
+                            .map_or(&[] as &[f32], |entry| entry.as_slice());
+                        interleave_coeffs(glam::vec3(v[11], v[12], v[13]), sh_rest, &mut sh_coeffs);
+
+                        // Occasionally send some updated splats.
+                        if (i - last_update) >= update_every || i == element.count - 1 {
+                            emitter
+                                .emit(SplatMessage {
+                                    meta: ParseMetadata {
+                                        total_splats: element.count as u32,
+                                        up_axis,
+                                        frame_count: 0,
+                                        current_frame: 0,
+                                    },
+                                    splats: Splats::from_raw(
+                                        &means,
+                                        Some(&rotations),
+                                        Some(&log_scales),
+                                        Some(&sh_coeffs),
+                                        Some(&opacity),
+                                        &device,
+                                    ),
+                                    extra_properties: ExtraProperties::default(),
+                                })
+                                .await;
+                            last_update = i;
+                        }
+                    }
+                }
+                name => anyhow::bail!("Unexpected element {name} in SH codebook ply."),
+            }
+        }
+
+        Ok(())
+    })
+}
+
 fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
     mut reader: T,
     subsample_points: Option<u32>,
@@ -503,7 +825,8 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
     up_axis: Option<Vec3>,
 ) -> impl Stream<Item = Result<SplatMessage<B>>> + 'static {
     try_fn_stream(|emitter| async move {
-        let parser = Parser::<ParsedGaussian<false>>::new();
+        // Delta frames are quantized to the range of their meta elements.
+        let parser = Parser::<Normalized<ParsedGaussian<false>>>::new();
         let mut yielder = TimeYield::new();
 
         // Check for frame count.
@@ -575,10 +898,14 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                                     opacity.as_deref(),
                                     &device,
                                 ),
+                                extra_properties: ExtraProperties::default(),
                             })
                             .await;
                     }
 
+                    let Normalized(splat) =
+                        parse_elem(&mut reader, &parser, header.encoding, element).await?;
+
                     // Doing this after first reading and parsing the points is quite wasteful, but
                     // we do need to advance the reader.
                     if let Some(subsample) = subsample_points {
@@ -587,8 +914,6 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                         }
                     }
 
-                    let splat = parse_elem(&mut reader, &parser, header.encoding, element).await?;
-
                     means.push(splat.mean);
                     if let Some(scales) = &mut log_scales {
                         scales.push(splat.log_scale);
@@ -621,15 +946,18 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             current_frame: frame,
                         },
                         splats,
+                        extra_properties: ExtraProperties::default(),
                     })
                     .await;
             } else if element.name.starts_with("meta_delta_min_") {
-                let splat = parse_elem(&mut reader, &parser, header.encoding, element).await?;
+                let Normalized(splat) =
+                    parse_elem(&mut reader, &parser, header.encoding, element).await?;
                 meta_min.mean = splat.mean;
                 meta_min.rotation = splat.rotation.into();
                 meta_min.scale = splat.log_scale;
             } else if element.name.starts_with("meta_delta_max_") {
-                let splat = parse_elem(&mut reader, &parser, header.encoding, element).await?;
+                let Normalized(splat) =
+                    parse_elem(&mut reader, &parser, header.encoding, element).await?;
                 meta_max.mean = splat.mean;
                 meta_max.rotation = splat.rotation.into();
                 meta_max.scale = splat.log_scale;
@@ -643,7 +971,7 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
 
                     // The splat we decode is normed to 0-1 (if quantized), so rescale to
                     // actual values afterwards.
-                    let splat_enc =
+                    let Normalized(splat_enc) =
                         parse_elem(&mut reader, &parser, header.encoding, element).await?;
 
                     // Let's only animate transforms for now.
@@ -710,6 +1038,7 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
                             splats.sh_coeffs.val(),
                             splats.raw_opacity.val(),
                         ),
+                        extra_properties: ExtraProperties::default(),
                     })
                     .await;
 
@@ -720,3 +1049,297 @@ fn parse_delta_ply<T: AsyncBufRead + Unpin + 'static, B: Backend>(
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_elem;
+    use crate::parsed_gaussian::{ParsedGaussian, normalized_value};
+    use brush_render::{gaussian_splats::inverse_sigmoid, sh::channel_to_sh};
+    use glam::Vec3;
+    use ply_rs::{
+        parser::Parser,
+        ply::{DefaultElement, Property},
+    };
+    use tokio::io::BufReader;
+
+    const ENCODINGS: [&str; 3] = ["ascii", "binary_little_endian", "binary_big_endian"];
+    const POSITION_TYPES: [&str; 2] = ["float", "double"];
+    const COLOR_TYPES: [&str; 3] = ["uchar", "ushort", "float"];
+    const SCALAR_TYPES: [&str; 8] = [
+        "char", "uchar", "short", "ushort", "int", "uint", "float", "double",
+    ];
+
+    fn encode_binary(value: f64, ty: &str, big_endian: bool) -> Vec<u8> {
+        macro_rules! bytes {
+            ($t:ty) => {
+                if big_endian {
+                    (value as $t).to_be_bytes().to_vec()
+                } else {
+                    (value as $t).to_le_bytes().to_vec()
+                }
+            };
+        }
+
+        match ty {
+            "char" => bytes!(i8),
+            "uchar" => bytes!(u8),
+            "short" => bytes!(i16),
+            "ushort" => bytes!(u16),
+            "int" => bytes!(i32),
+            "uint" => bytes!(u32),
+            "float" => bytes!(f32),
+            "double" => bytes!(f64),
+            _ => panic!("Unknown ply type {ty}"),
+        }
+    }
+
+    /// Write a ply with a single vertex element with the given properties & values.
+    fn write_ply(encoding: &str, properties: &[(&str, &str)], rows: &[Vec<f64>]) -> Vec<u8> {
+        let mut header = format!(
+            "ply\nformat {encoding} 1.0\nelement vertex {}\n",
+            rows.len()
+        );
+        for (name, ty) in properties {
+            header.push_str(&format!("property {ty} {name}\n"));
+        }
+        header.push_str("end_header\n");
+
+        let mut data = header.into_bytes();
+        for row in rows {
+            if encoding == "ascii" {
+                let line: Vec<_> = row.iter().map(|v| v.to_string()).collect();
+                data.extend(line.join(" ").into_bytes());
+                data.push(b'\n');
+            } else {
+                for (value, (_, ty)) in row.iter().zip(properties) {
+                    data.extend(encode_binary(*value, ty, encoding == "binary_big_endian"));
+                }
+            }
+        }
+        data
+    }
+
+    async fn parse_vertices(data: Vec<u8>) -> Vec<ParsedGaussian<false>> {
+        let mut reader = BufReader::new(std::io::Cursor::new(data));
+        let header = Parser::<DefaultElement>::new()
+            .read_header(&mut reader)
+            .await
+            .expect("Failed to read header");
+        let vertex = header.elements.first().expect("Missing vertex element");
+        let parser = Parser::<ParsedGaussian<false>>::new();
+
+        let mut vertices = vec![];
+        for _ in 0..vertex.count {
+            vertices.push(
+                parse_elem(&mut reader, &parser, header.encoding, vertex)
+                    .await
+                    .expect("Failed to parse vertex"),
+            );
+        }
+        vertices
+    }
+
+    fn max_value(ty: &str) -> f64 {
+        match ty {
+            "uchar" => u8::MAX as f64,
+            "ushort" => u16::MAX as f64,
+            _ => panic!("Not an integer type {ty}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_ply_combinations() {
+        for encoding in ENCODINGS {
+            for pos_ty in POSITION_TYPES {
+                for color_ty in COLOR_TYPES {
+                    let properties = [
+                        ("x", pos_ty),
+                        ("y", pos_ty),
+                        ("z", pos_ty),
+                        ("red", color_ty),
+                        ("green", color_ty),
+                        ("blue", color_ty),
+                    ];
+                    // Integer colors are quantized to the full range of their type.
+                    let quantize = |v: f64| {
+                        if color_ty == "float" {
+                            v
+                        } else {
+                            (v * max_value(color_ty)).round()
+                        }
+                    };
+                    let colors = [1.0, 0.0, 0.2];
+                    let rows = vec![
+                        vec![
+                            1.5,
+                            -2.25,
+                            3.125,
+                            quantize(1.0),
+                            quantize(0.0),
+                            quantize(0.2),
+                        ],
+                        vec![-1e6, 0.5, 0.0, quantize(0.0), quantize(1.0), quantize(0.0)],
+                    ];
+
+                    let data = write_ply(encoding, &properties, &rows);
+                    let vertices = parse_vertices(data).await;
+
+                    let case = format!("{encoding}, {pos_ty} positions, {color_ty} colors");
+                    assert_eq!(vertices.len(), 2, "Wrong vertex count for {case}");
+                    assert_eq!(
+                        vertices[0].mean,
+                        Vec3::new(1.5, -2.25, 3.125),
+                        "Wrong position for {case}"
+                    );
+                    assert_eq!(
+                        vertices[1].mean,
+                        Vec3::new(-1e6, 0.5, 0.0),
+                        "Wrong position for {case}"
+                    );
+
+                    let expected_sh = Vec3::from(colors.map(channel_to_sh));
+                    assert!(
+                        vertices[0].sh_dc.abs_diff_eq(expected_sh, 1e-4),
+                        "Wrong color for {case}: {} vs {expected_sh}",
+                        vertices[0].sh_dc
+                    );
+                    let expected_sh =
+                        Vec3::new(channel_to_sh(0.0), channel_to_sh(1.0), channel_to_sh(0.0));
+                    assert!(
+                        vertices[1].sh_dc.abs_diff_eq(expected_sh, 1e-4),
+                        "Wrong color for {case}: {} vs {expected_sh}",
+                        vertices[1].sh_dc
+                    );
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_all_scalar_types() {
+        for encoding in ENCODINGS {
+            for ty in SCALAR_TYPES {
+                // Store opacity in every type, and also a custom property that should be kept as is.
+                let properties = [("x", "float"), ("opacity", ty), ("label", ty)];
+                let rows = vec![vec![1.0, 100.0, 100.0], vec![2.0, 0.0, 7.0]];
+
+                let data = write_ply(encoding, &properties, &rows);
+                let vertices = parse_vertices(data).await;
+
+                let case = format!("{encoding}, {ty}");
+                assert_eq!(vertices.len(), 2, "Wrong vertex count for {case}");
+                assert_eq!(vertices[1].mean.x, 2.0, "Wrong position for {case}");
+
+                // Integer opacities are normalized, and converted to logits.
+                let normalized = match ty {
+                    "char" => Some(100.0 / i8::MAX as f32),
+                    "uchar" => Some(100.0 / u8::MAX as f32),
+                    "short" => Some(100.0 / i16::MAX as f32),
+                    "ushort" => Some(100.0 / u16::MAX as f32),
+                    "int" => Some((100.0 / i32::MAX as f64) as f32),
+                    "uint" => Some((100.0 / u32::MAX as f64) as f32),
+                    _ => None,
+                };
+                let expected = normalized.map_or(100.0, |n| inverse_sigmoid(n.max(1e-4)));
+                assert!(
+                    (vertices[0].opacity - expected).abs() < 1e-4,
+                    "Wrong normalized value for {case}"
+                );
+                assert_eq!(vertices[0].extra, [100.0], "Extra property lost for {case}");
+                assert_eq!(vertices[1].extra, [7.0], "Extra property lost for {case}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_integer_properties() {
+        for encoding in ENCODINGS {
+            // Only colors & opacity are quantized, other integer properties are plain numbers.
+            let properties = [
+                ("x", "int"),
+                ("y", "short"),
+                ("z", "uint"),
+                ("scale_0", "char"),
+                ("rot_0", "uchar"),
+                ("f_dc_1", "int"),
+                ("red", "uchar"),
+                ("opacity", "uchar"),
+            ];
+            let rows = vec![vec![5.0, -3.0, 1000.0, -2.0, 1.0, 2.0, 255.0, 255.0]];
+
+            let data = write_ply(encoding, &properties, &rows);
+            let vertices = parse_vertices(data).await;
+
+            assert_eq!(
+                vertices[0].mean,
+                Vec3::new(5.0, -3.0, 1000.0),
+                "Integer positions should be kept as is for {encoding}"
+            );
+            assert_eq!(
+                vertices[0].log_scale.x, -2.0,
+                "Integer scale should be kept as is for {encoding}"
+            );
+            assert_eq!(
+                vertices[0].rotation.w, 1.0,
+                "Integer rotation should be kept as is for {encoding}"
+            );
+            assert_eq!(
+                vertices[0].sh_dc.y, 2.0,
+                "Integer SH should be kept as is for {encoding}"
+            );
+            assert!(
+                (vertices[0].sh_dc.x - channel_to_sh(1.0)).abs() < 1e-6,
+                "Integer colors should be normalized for {encoding}"
+            );
+            let opacity = 1.0 / (1.0 + (-vertices[0].opacity).exp());
+            assert!(
+                opacity > 0.999,
+                "Integer opacity should be fully opaque for {encoding}, got {opacity}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalized_extremes() {
+        assert_eq!(
+            normalized_value(&Property::UChar(u8::MAX)),
+            Some(1.0),
+            "uchar max"
+        );
+        assert_eq!(
+            normalized_value(&Property::UShort(u16::MAX)),
+            Some(1.0),
+            "ushort max"
+        );
+        assert_eq!(
+            normalized_value(&Property::UInt(u32::MAX)),
+            Some(1.0),
+            "uint max"
+        );
+        assert_eq!(
+            normalized_value(&Property::Char(i8::MIN)),
+            Some(-1.0),
+            "char min"
+        );
+        assert_eq!(
+            normalized_value(&Property::Short(i16::MIN)),
+            Some(-1.0),
+            "short min"
+        );
+        assert_eq!(
+            normalized_value(&Property::Int(i32::MIN)),
+            Some(-1.0),
+            "int min"
+        );
+        assert_eq!(
+            normalized_value(&Property::Double(0.25)),
+            Some(0.25),
+            "double"
+        );
+        assert_eq!(
+            normalized_value(&Property::ListFloat(vec![1.0])),
+            None,
+            "lists are ignored"
+        );
+    }
+}
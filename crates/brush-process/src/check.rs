@@ -0,0 +1,19 @@
+use brush_dataset::dataset_report::DatasetReport;
+use burn_wgpu::{Wgpu, WgpuDevice};
+
+use crate::{data_source::DataSource, process_loop::ProcessArgs};
+
+/// Load a dataset without training on it, and report what was found.
+pub async fn check_dataset(
+    source: DataSource,
+    args: &ProcessArgs,
+    device: &WgpuDevice,
+) -> DatasetReport {
+    match source.into_vfs(&args.process_config, |_, _| {}).await {
+        Ok(vfs) => DatasetReport::create::<Wgpu>(vfs, &args.load_config, device).await,
+        Err(e) => DatasetReport {
+            errors: vec![format!("{e:#}")],
+            ..Default::default()
+        },
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::{path::Path, str::FromStr};
@@ -6,6 +7,10 @@ use anyhow::anyhow;
 
 use brush_dataset::WasmNotSend;
 use brush_dataset::brush_vfs::{BrushVfs, PathReader};
+
+use crate::process_loop::ProcessConfig;
+#[cfg(not(target_family = "wasm"))]
+use crate::url_cache::UrlCache;
 use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 use tokio_stream::StreamExt;
 use tokio_util::io::StreamReader;
@@ -13,6 +18,8 @@ use tokio_util::io::StreamReader;
 #[derive(Clone, Debug)]
 pub enum DataSource {
     PickFile,
+    /// Pick several loose files, eg. a set of plys or the images and data of a dataset.
+    PickFiles,
     PickDirectory,
     Url(String),
     Path(String),
@@ -25,6 +32,7 @@ impl FromStr for DataSource {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "pick-file" => Ok(Self::PickFile),
+            "pick-files" => Ok(Self::PickFiles),
             "pick-directory" | "dir" => Ok(Self::PickDirectory),
             s if s.starts_with("http://") || s.starts_with("https://") => {
                 Ok(Self::Url(s.to_owned()))
@@ -39,12 +47,33 @@ async fn read_at_most<R: AsyncRead + Unpin>(
     reader: &mut R,
     limit: usize,
 ) -> std::io::Result<Vec<u8>> {
-    let mut buffer = vec![0; limit];
-    let bytes_read = reader.read(&mut buffer).await?;
-    buffer.truncate(bytes_read);
+    // A single read can return less than requested, keep going until the limit or EOF.
+    let mut buffer = Vec::with_capacity(limit);
+    reader.take(limit as u64).read_to_end(&mut buffer).await?;
     Ok(buffer)
 }
 
+/// Make a file name unique among `used_names` by adding a number to it, like `img_1.png`.
+/// Files picked from different places can have the same name.
+fn unique_name(name: &str, used_names: &mut HashSet<String>) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut unique = name.to_owned();
+    let mut i = 1;
+    while !used_names.insert(unique.clone()) {
+        unique = format!("{stem}_{i}{ext}");
+        i += 1;
+    }
+    if unique != name {
+        log::warn!("Multiple picked files are named {name}, reading one as {unique}");
+    }
+    unique
+}
+
 impl DataSource {
     async fn vfs_from_reader(
         reader: impl AsyncRead + WasmNotSend + Unpin + 'static,
@@ -52,17 +81,31 @@ impl DataSource {
         // Small hack to peek some bytes: Read them
         // and add them at the start again.
         let mut data = BufReader::new(reader);
-        let peek = read_at_most(&mut data, 64).await?;
+        // Enough to see the magic of a tar header, which is at offset 257.
+        let peek = read_at_most(&mut data, 512).await?;
         let reader = std::io::Cursor::new(peek.clone()).chain(data);
 
         if peek.as_slice().starts_with(b"ply") {
             let mut path_reader = PathReader::default();
             path_reader.add(Path::new("input.ply"), reader);
             Ok(BrushVfs::from_paths(path_reader))
+        } else if peek
+            .get(8..)
+            .is_some_and(|header| header.starts_with(b"{\""))
+        {
+            // Safetensors files start with the header size, followed by a JSON header.
+            let mut path_reader = PathReader::default();
+            path_reader.add(Path::new("input.safetensors"), reader);
+            Ok(BrushVfs::from_paths(path_reader))
         } else if peek.starts_with(b"PK") {
             BrushVfs::from_zip_reader(reader)
                 .await
                 .map_err(|e| anyhow::anyhow!(e))
+        } else if peek.starts_with(&[0x1f, 0x8b]) {
+            // Gzip, assume it's a compressed tar archive.
+            BrushVfs::from_tar_reader(reader, true).await
+        } else if peek.get(257..262).is_some_and(|magic| magic == b"ustar") {
+            BrushVfs::from_tar_reader(reader, false).await
         } else if peek.starts_with(b"<!DOCTYPE html>") {
             anyhow::bail!(
                 "Failed to download data (are you trying to download from Google Drive? You might have to use the proxy."
@@ -72,11 +115,26 @@ impl DataSource {
             let path = Path::new(&string);
             BrushVfs::from_directory(path).await
         } else {
-            anyhow::bail!("only zip and ply files are supported.")
+            anyhow::bail!("only zip, tar, ply and safetensors files are supported.")
         }
     }
 
-    pub async fn into_vfs(self) -> anyhow::Result<BrushVfs> {
+    /// Open a downloaded file from the cache. Zip files are read lazily from disk.
+    #[cfg(not(target_family = "wasm"))]
+    async fn vfs_from_cached_file(path: &Path) -> anyhow::Result<BrushVfs> {
+        if let Ok(vfs) = BrushVfs::from_zip_file(std::fs::File::open(path)?) {
+            return Ok(vfs);
+        }
+        Self::vfs_from_reader(tokio::fs::File::open(path).await?).await
+    }
+
+    /// Open the data source. `on_download` is called with the number of bytes downloaded so far
+    /// and the total size, if known.
+    pub async fn into_vfs(
+        self,
+        config: &ProcessConfig,
+        on_download: impl Fn(u64, Option<u64>) + WasmNotSend + 'static,
+    ) -> anyhow::Result<BrushVfs> {
         match self {
             Self::PickFile => {
                 let picked = rrfd::pick_file().await.map_err(|e| anyhow!(e))?;
@@ -84,6 +142,29 @@ impl DataSource {
                 let reader = Cursor::new(data);
                 Self::vfs_from_reader(reader).await
             }
+            Self::PickFiles => {
+                let mut picked = rrfd::pick_files().await.map_err(|e| anyhow!(e))?;
+                // A single file might be an archive, open it like a picked file.
+                if picked.len() == 1 {
+                    let data = picked.remove(0).read().await;
+                    return Self::vfs_from_reader(Cursor::new(data)).await;
+                }
+
+                // The picked files are all in one folder, so they only need their names.
+                let mut path_reader = PathReader::default();
+                let mut used_names = HashSet::new();
+                for (i, file) in picked.into_iter().enumerate() {
+                    let name = file.file_name().unwrap_or_else(|| {
+                        let name = format!("file_{i}");
+                        log::warn!("Picked file {i} has no name, reading it as {name}");
+                        name
+                    });
+                    let name = unique_name(&name, &mut used_names);
+                    let data = file.read().await;
+                    path_reader.add(Path::new(&name), Cursor::new(data));
+                }
+                Ok(BrushVfs::from_paths(path_reader))
+            }
             Self::PickDirectory => {
                 let picked = rrfd::pick_directory().await.map_err(|e| anyhow!(e))?;
                 BrushVfs::from_directory(&picked).await
@@ -93,13 +174,29 @@ impl DataSource {
                 if !url.starts_with("http://") && !url.starts_with("https://") {
                     url = format!("https://{url}");
                 }
-                let response = reqwest::get(url)
-                    .await
-                    .map_err(|e| anyhow!(e))?
-                    .bytes_stream();
 
-                let response =
-                    response.map(|b| b.map_err(|_e| std::io::ErrorKind::ConnectionAborted));
+                #[cfg(not(target_family = "wasm"))]
+                if let Some(cache) = UrlCache::from_config(config) {
+                    match cache.fetch(&url, &on_download).await {
+                        Ok(Some(path)) => return Self::vfs_from_cached_file(&path).await,
+                        Ok(None) => log::info!("Can't cache {url}, downloading it directly."),
+                        Err(e) => log::warn!("Failed to use download cache for {url}: {e:#}"),
+                    }
+                }
+                #[cfg(target_family = "wasm")]
+                let _ = config;
+
+                let response = reqwest::get(url).await.map_err(|e| anyhow!(e))?;
+                let total = response.content_length();
+                on_download(0, total);
+
+                let mut done = 0;
+                let response = response.bytes_stream().map(move |b| {
+                    let b = b.map_err(|_e| std::io::ErrorKind::ConnectionAborted)?;
+                    done += b.len() as u64;
+                    on_download(done, total);
+                    Ok::<_, std::io::Error>(b)
+                });
                 let reader = StreamReader::new(response);
                 Self::vfs_from_reader(reader).await
             }
@@ -107,3 +204,23 @@ impl DataSource {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::unique_name;
+    use std::collections::HashSet;
+
+    #[test]
+    fn unique_picked_names() {
+        let mut used = HashSet::new();
+        let names: Vec<_> = ["img.png", "img.png", "img.png", "cameras"]
+            .into_iter()
+            .map(|n| unique_name(n, &mut used))
+            .collect();
+        assert_eq!(
+            names,
+            ["img.png", "img_1.png", "img_2.png", "cameras"],
+            "Duplicate names should get a number"
+        );
+    }
+}
@@ -2,5 +2,9 @@
 
 pub mod rerun_tools;
 
+pub mod check;
 pub mod data_source;
 pub mod process_loop;
+
+#[cfg(not(target_family = "wasm"))]
+pub mod url_cache;
@@ -1,4 +1,5 @@
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Context;
 use burn::prelude::Backend;
@@ -7,8 +8,15 @@ use burn_cubecl::cubecl::Runtime;
 use web_time::Instant;
 
 use crate::{data_source::DataSource, rerun_tools::VisualizeTools};
-use brush_dataset::{Dataset, brush_vfs::BrushVfs, splat_import};
+use brush_dataset::{
+    DataStream, Dataset, LoadProgress,
+    brush_vfs::BrushVfs,
+    extra_properties::ExtraProperties,
+    scene_normalization, sh_codebook,
+    splat_import::{self, ParseMetadata},
+};
 use brush_render::gaussian_splats::{RandomSplatsConfig, Splats};
+use brush_train::scene::{Scene, SceneView};
 use brush_train::train::{RefineStats, TrainBack, TrainStepStats};
 use burn::{backend::Autodiff, module::AutodiffModule};
 use burn_wgpu::{Wgpu, WgpuDevice, WgpuRuntime};
@@ -20,13 +28,24 @@ use tokio::sync::mpsc::{UnboundedSender, channel};
 use tokio_stream::StreamExt;
 
 #[allow(unused)]
-use brush_dataset::splat_export;
+use brush_dataset::{eval_split, nerfstudio_export, splat_export};
 
 use super::{
     ProcessArgs,
     train_stream::{self, train_stream},
 };
 
+/// What a [`ProcessMessage::Progress`] is counting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressKind {
+    /// Bytes downloaded from a URL.
+    Download,
+    /// Images of a dataset decoded.
+    Images,
+    /// Splats parsed from a ply file.
+    Splats,
+}
+
 pub enum ProcessMessage {
     NewSource,
     StartLoading {
@@ -49,6 +68,12 @@ pub enum ProcessMessage {
     Dataset {
         data: Dataset,
     },
+    /// Progress while loading data. The total isn't known for some downloads.
+    Progress {
+        kind: ProgressKind,
+        done: u64,
+        total: Option<u64>,
+    },
     /// Splat, or dataset and initial splat, are done loading.
     #[allow(unused)]
     DoneLoading {
@@ -75,11 +100,60 @@ pub enum ProcessMessage {
         avg_psnr: f32,
         avg_ssim: f32,
     },
+    /// Exported splats with a compressed SH codebook.
+    #[allow(unused)]
+    CompressedExport {
+        iter: u32,
+        codebook_size: usize,
+        /// Average PSNR lost on the eval views by compressing, if there's an eval set.
+        psnr_drop: Option<f32>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum ControlMessage {
     Paused(bool),
+    /// Add views to train on while training, eg. from a capture that's still in progress. The
+    /// cameras are in the original coordinates of the dataset.
+    AddViews(Vec<SceneView>),
+}
+
+/// Reports download progress. Progress is only informative, so updates are skipped while the
+/// receiver is busy, but the last one is always sent by [`Self::finish`].
+#[derive(Clone)]
+struct DownloadProgress {
+    output: Sender<ProcessMessage>,
+    /// The last update, if it was skipped.
+    skipped: Arc<Mutex<Option<(u64, Option<u64>)>>>,
+}
+
+impl DownloadProgress {
+    fn new(output: Sender<ProcessMessage>) -> Self {
+        Self {
+            output,
+            skipped: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn message(done: u64, total: Option<u64>) -> ProcessMessage {
+        ProcessMessage::Progress {
+            kind: ProgressKind::Download,
+            done,
+            total,
+        }
+    }
+
+    fn update(&self, done: u64, total: Option<u64>) {
+        let sent = self.output.try_send(Self::message(done, total)).is_ok();
+        *self.skipped.lock().expect("Lock poisoned") = (!sent).then_some((done, total));
+    }
+
+    async fn finish(&self) {
+        let skipped = self.skipped.lock().expect("Lock poisoned").take();
+        if let Some((done, total)) = skipped {
+            let _ = self.output.send(Self::message(done, total)).await;
+        }
+    }
 }
 
 async fn process_loop(
@@ -93,7 +167,13 @@ async fn process_loop(
         return;
     }
 
-    let vfs = source.into_vfs().await;
+    let download = DownloadProgress::new(output.clone());
+    let on_download = {
+        let download = download.clone();
+        move |done: u64, total: Option<u64>| download.update(done, total)
+    };
+    let vfs = source.into_vfs(&args.process_config, on_download).await;
+    download.finish().await;
 
     let vfs = match vfs {
         Ok(vfs) => vfs,
@@ -106,10 +186,10 @@ async fn process_loop(
     let paths: Vec<_> = vfs.file_names().collect();
     log::info!("Mounted VFS with {} files", paths.len());
 
-    let result = if paths
-        .iter()
-        .all(|p| p.extension().is_some_and(|p| p == "ply"))
-    {
+    let result = if paths.iter().all(|p| {
+        p.extension()
+            .is_some_and(|p| p == "ply" || p == "safetensors")
+    }) {
         view_process_loop(paths, output.clone(), vfs, device).await
     } else {
         train_process_loop(output.clone(), vfs, device, control_receiver, &args).await
@@ -120,6 +200,22 @@ async fn process_loop(
     }
 }
 
+fn images_progress(progress: &LoadProgress) -> ProcessMessage {
+    ProcessMessage::Progress {
+        kind: ProgressKind::Images,
+        done: progress.views_done() as u64,
+        total: Some(progress.views_total() as u64),
+    }
+}
+
+fn splat_progress(num_splats: u32, meta: &ParseMetadata) -> ProcessMessage {
+    ProcessMessage::Progress {
+        kind: ProgressKind::Splats,
+        done: num_splats as u64,
+        total: Some(meta.total_splats as u64),
+    }
+}
+
 async fn view_process_loop(
     paths: Vec<std::path::PathBuf>,
     output: Sender<ProcessMessage>,
@@ -139,18 +235,29 @@ async fn view_process_loop(
             return Ok(());
         }
 
-        let sub_sample = None; // Subsampling a trained ply doesn't really make sense.
-        let splat_stream = splat_import::load_splat_from_ply(
-            vfs.open_path(path).await?,
-            sub_sample,
-            device.clone(),
-        );
-
-        let mut splat_stream = std::pin::pin!(splat_stream);
+        let reader = vfs.open_path(path).await?;
+        let mut splat_stream: DataStream<_> =
+            if path.extension().is_some_and(|ext| ext == "safetensors") {
+                Box::pin(splat_import::load_splat_from_safetensors(
+                    reader,
+                    device.clone(),
+                ))
+            } else {
+                let sub_sample = None; // Subsampling a trained ply doesn't really make sense.
+                Box::pin(splat_import::load_splat_from_ply(
+                    reader,
+                    sub_sample,
+                    device.clone(),
+                ))
+            };
 
         while let Some(message) = splat_stream.next().await {
             let message = message?;
 
+            let _ = output
+                .send(splat_progress(message.splats.num_splats(), &message.meta))
+                .await;
+
             // If there's multiple ply files in a zip, don't support animated plys, that would
             // get rather mind bending.
             let (frame, total_frames) = if paths.len() == 1 {
@@ -180,6 +287,50 @@ async fn view_process_loop(
     Ok(())
 }
 
+/// Export the loaded dataset if asked to, and record which views were held out, so the same split
+/// can be used again.
+#[cfg(not(target_family = "wasm"))]
+async fn export_dataset(
+    dataset: &Dataset,
+    process_config: &super::ProcessConfig,
+) -> anyhow::Result<()> {
+    if let Some(images) = process_config.export_dataset {
+        let export_path =
+            Path::new(process_config.export_path.as_deref().unwrap_or(".")).to_owned();
+        log::info!("Exporting dataset to {export_path:?}");
+
+        // Encoding images is slow, so do it on a blocking thread and write each file right away.
+        let dataset = dataset.clone();
+        tokio::task::spawn_blocking(move || {
+            nerfstudio_export::dataset_to_nerfstudio(&dataset, images, |file| {
+                let path = export_path.join(&file.path);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, file.data)
+                    .with_context(|| format!("Failed to export dataset file {path:?}"))
+            })
+        })
+        .await??;
+    }
+
+    if let (Some(export_path), Some(split)) = (
+        &process_config.export_path,
+        eval_split::eval_split_file(dataset),
+    ) {
+        let path = Path::new(export_path).join(eval_split::EVAL_SPLIT_FILE);
+        let written = async {
+            tokio::fs::create_dir_all(export_path).await?;
+            tokio::fs::write(&path, split).await
+        };
+        if let Err(e) = written.await {
+            log::warn!("Failed to write eval split {path:?}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
 async fn train_process_loop(
     output: Sender<ProcessMessage>,
     vfs: BrushVfs,
@@ -189,6 +340,12 @@ async fn train_process_loop(
 ) -> Result<(), anyhow::Error> {
     let process_config = &process_args.process_config;
 
+    anyhow::ensure!(
+        !(process_args.compression_config.export_sh_codebook
+            && process_config.export_name.ends_with(".safetensors")),
+        "SH codebook exports are written as ply, but the export name is a .safetensors file."
+    );
+
     let _ = output
         .send(ProcessMessage::StartLoading { training: true })
         .await;
@@ -198,35 +355,64 @@ async fn train_process_loop(
 
     // Load initial splats if included
     let mut initial_splats = None;
+    // Any ply properties Brush doesn't use itself, to preserve them on export.
+    let mut extra_properties = ExtraProperties::default();
 
-    let mut dataset = Dataset::empty();
+    let progress = LoadProgress::default();
     let (mut splat_stream, mut data_stream) =
-        brush_dataset::load_dataset(vfs.clone(), &process_args.load_config, &device).await?;
+        brush_dataset::load_dataset(vfs.clone(), &process_args.load_config, &progress, &device)
+            .await?;
 
     let visualize = VisualizeTools::new(process_args.rerun_config.rerun_enabled);
 
-    // Read dataset stream.
-    while let Some(d) = data_stream.next().await {
-        dataset = d.context("Failed to parse dataset. \n")?;
+    // Read the whole dataset before training, or when training while loading, until there are
+    // views to train on. Normalizing the scene needs all views upfront either way.
+    let wait_for_all =
+        !process_config.train_while_loading || process_args.load_config.normalize_scene.is_some();
+    let mut loaded = Dataset::empty();
+    let mut loading_done = false;
+    while wait_for_all || loaded.train.views.is_empty() {
+        let Some(d) = data_stream.next().await else {
+            loading_done = true;
+            break;
+        };
+        loaded = d.context("Failed to parse dataset. \n")?;
 
+        let _ = output.send(images_progress(&progress)).await;
         let _ = output
             .send(ProcessMessage::Dataset {
-                data: dataset.clone(),
+                data: loaded.clone(),
             })
             .await;
     }
 
-    visualize.log_scene(&dataset.train, process_args.rerun_config.rerun_max_img_size)?;
+    anyhow::ensure!(
+        !loaded.train.views.is_empty(),
+        "The dataset has no views to train on. Check that the eval split leaves some views."
+    );
 
-    let estimated_up = dataset.estimate_up();
+    #[cfg(not(target_family = "wasm"))]
+    if loading_done {
+        export_dataset(&loaded, process_config).await?;
+    }
+
+    let mut dataset = loaded.clone();
+    // How many train views of the dataset stream were passed on to training.
+    let mut streamed_views = loaded.train.views.len();
+
+    let mut up_axis = dataset.estimate_up();
 
     // Read initial splats if any.
     while let Some(message) = splat_stream.next().await {
         let message = message?;
+        let _ = output
+            .send(splat_progress(message.splats.num_splats(), &message.meta))
+            .await;
+        // If the metadata has an up axis prefer that, otherwise estimate
+        // the up direction.
+        up_axis = message.meta.up_axis.unwrap_or(up_axis);
         let msg = ProcessMessage::ViewSplats {
-            // If the metadata has an up axis prefer that, otherwise estimate
-            // the up direction.
-            up_axis: message.meta.up_axis.or(Some(estimated_up)),
+            up_axis: Some(up_axis),
             splats: Box::new(message.splats.valid()),
             frame: 0,
             total_frames: 0,
@@ -235,8 +421,47 @@ async fn train_process_loop(
             return Ok(());
         }
         initial_splats = Some(message.splats);
+        extra_properties = message.extra_properties;
     }
 
+    if let Some(center) = process_args.load_config.normalize_scene {
+        let points = match &initial_splats {
+            Some(splats) => Some(scene_normalization::splat_positions(splats).await?),
+            None => None,
+        };
+        let transform = scene_normalization::normalization_transform(
+            &dataset,
+            points.as_deref(),
+            center,
+            up_axis,
+        );
+        log::info!("Normalizing scene with {transform:?}");
+
+        dataset = transform.transform_dataset(&dataset);
+        initial_splats = initial_splats.map(|splats| transform.transform_splats(splats));
+        up_axis = transform.transform_direction(up_axis);
+
+        let _ = output
+            .send(ProcessMessage::Dataset {
+                data: dataset.clone(),
+            })
+            .await;
+
+        if let Some(splats) = &initial_splats {
+            let msg = ProcessMessage::ViewSplats {
+                up_axis: Some(up_axis),
+                splats: Box::new(splats.valid()),
+                frame: 0,
+                total_frames: 0,
+            };
+            if output.send(msg).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    visualize.log_scene(&dataset.train, process_args.rerun_config.rerun_max_img_size)?;
+
     let _ = output
         .send(ProcessMessage::DoneLoading { training: true })
         .await;
@@ -261,34 +486,66 @@ async fn train_process_loop(
 
     let mut control_receiver = control_receiver;
 
-    let eval_scene = dataset.eval.clone();
+    let mut eval_scene = dataset.eval.clone();
+    // Exports are mapped back to the original coordinates of the dataset.
+    let world_transform = dataset.world_transform;
+    let (views_sender, views_receiver) = unbounded_channel();
     let stream = train_stream(
-        dataset,
+        dataset.clone(),
         splats,
+        extra_properties,
         process_args.train_config.clone(),
         device.clone(),
         process_args.process_config.start_iter,
+        views_receiver,
     );
     let mut stream = std::pin::pin!(stream);
 
     let mut train_paused = false;
 
     loop {
-        let control = if train_paused {
-            control_receiver.recv().await
-        } else {
-            control_receiver.try_recv().ok()
-        };
-
-        if let Some(control) = control {
-            match control {
-                ControlMessage::Paused(paused) => {
-                    train_paused = paused;
+        let msg = tokio::select! {
+            Some(control) = control_receiver.recv() => {
+                match control {
+                    ControlMessage::Paused(paused) => {
+                        train_paused = paused;
+                    }
+                    ControlMessage::AddViews(views) => {
+                        let views: Vec<_> = views
+                            .into_iter()
+                            .map(|view| SceneView {
+                                camera: world_transform.transform_camera(&view.camera),
+                                ..view
+                            })
+                            .collect();
+                        log::info!("Adding {} views to the training set", views.len());
+                        add_train_views(&mut dataset, views, &views_sender, &output).await;
+                    }
                 }
+                continue;
             }
-        }
-
-        let msg = stream.next().await;
+            d = data_stream.next(), if !loading_done => {
+                if let Some(d) = d {
+                    loaded = d.context("Failed to parse dataset. \n")?;
+                    let _ = output.send(images_progress(&progress)).await;
+
+                    let transformed = world_transform.transform_dataset(&loaded);
+                    let views = transformed.train.views[streamed_views..].to_vec();
+                    streamed_views = transformed.train.views.len();
+                    eval_scene = transformed.eval;
+                    dataset.eval = eval_scene.clone();
+                    add_train_views(&mut dataset, views, &views_sender, &output).await;
+                } else {
+                    loading_done = true;
+                    #[cfg(not(target_family = "wasm"))]
+                    export_dataset(&loaded, process_config).await?;
+                }
+                continue;
+            }
+            msg = stream.next(), if !train_paused => msg,
+            // Paused without anyone left to unpause.
+            else => break,
+        };
 
         let Some(msg) = msg else {
             break;
@@ -303,6 +560,7 @@ async fn train_process_loop(
                 stats,
                 iter,
                 timestamp,
+                extra_properties,
             } => {
                 #[allow(unused)]
                 let export_path =
@@ -326,9 +584,11 @@ async fn train_process_loop(
                             *splats.clone(),
                             eval_scene,
                             None,
+                            process_args.train_config.linear_space,
                             &mut rng,
                             &device,
                         ) {
+                            let sample = sample?;
                             count += 1;
                             psnr += sample.psnr.clone().into_scalar_async().await;
                             ssim += sample.ssim.clone().into_scalar_async().await;
@@ -403,7 +663,89 @@ async fn train_process_loop(
                     // Nb: this COULD easily be done in the spawned future as well,
                     // but for memory reasons it's not great to keep another copy of the
                     // field.
-                    let splat_data = splat_export::splat_to_ply(splats).await?;
+                    let export_splats = if world_transform.is_identity() {
+                        splats.clone()
+                    } else {
+                        world_transform.inverse().transform_splats(splats.clone())
+                    };
+
+                    // Ply viewers expect sRGB colors, so bake in the display transform. Raw
+                    // safetensors exports keep the linear colors.
+                    let codebook = process_args.compression_config.export_sh_codebook;
+                    let bake_colors = process_args.train_config.linear_space
+                        && !export_name.ends_with(".safetensors");
+                    let bake = |splats| {
+                        if bake_colors {
+                            let exposure = process_config.export_exposure;
+                            brush_train::image::bake_display_colors(splats, exposure)
+                        } else {
+                            splats
+                        }
+                    };
+
+                    let splat_data = if codebook {
+                        let compressed = sh_codebook::compress_splats(
+                            bake(export_splats.clone()),
+                            &process_args.compression_config,
+                            process_config.seed,
+                        )
+                        .await?;
+
+                        let psnr_drop = if let Some(eval_scene) = eval_scene.as_ref() {
+                            // Compare the colors as trained, so the drop doesn't include any
+                            // error from the baked display transform.
+                            let unbaked = if bake_colors {
+                                sh_codebook::compress_splats(
+                                    export_splats,
+                                    &process_args.compression_config,
+                                    process_config.seed,
+                                )
+                                .await?
+                                .splats
+                            } else {
+                                compressed.splats.clone()
+                            };
+                            let linear_space = process_args.train_config.linear_space;
+                            let psnr = average_psnr(
+                                splats,
+                                eval_scene,
+                                linear_space,
+                                process_config.seed,
+                                &device,
+                            )
+                            .await?;
+                            let psnr_compressed = average_psnr(
+                                world_transform.transform_splats(unbaked),
+                                eval_scene,
+                                linear_space,
+                                process_config.seed,
+                                &device,
+                            )
+                            .await?;
+                            let drop = psnr - psnr_compressed;
+                            log::info!("Compressed export at iter {iter} loses {drop:.3} PSNR");
+                            Some(drop)
+                        } else {
+                            None
+                        };
+
+                        let _ = output
+                            .send(ProcessMessage::CompressedExport {
+                                iter,
+                                codebook_size: compressed.codebook_size(),
+                                psnr_drop,
+                            })
+                            .await;
+
+                        splat_export::codebook_splats_to_ply(&compressed)
+                    } else if export_name.ends_with(".safetensors") {
+                        let up_axis = world_transform.inverse().transform_direction(up_axis);
+                        splat_export::splat_to_safetensors(export_splats, Some(up_axis)).await?
+                    } else {
+                        let export_splats = bake(export_splats);
+                        splat_export::splat_to_ply_with_properties(export_splats, &extra_properties)
+                            .await?
+                    };
 
                     tokio::task::spawn(async move {
                         if let Err(e) = tokio::fs::write(export_path.join(&export_name), splat_data)
@@ -467,6 +809,46 @@ async fn train_process_loop(
     Ok(())
 }
 
+/// Add views to the training set of a running training process. The views are in the coordinates
+/// of the training set already.
+async fn add_train_views(
+    dataset: &mut Dataset,
+    views: Vec<SceneView>,
+    views_sender: &UnboundedSender<Vec<SceneView>>,
+    output: &Sender<ProcessMessage>,
+) {
+    if !views.is_empty() {
+        dataset.train = Scene::new(dataset.train.views.iter().chain(&views).cloned().collect());
+        let _ = views_sender.send(views);
+    }
+    let _ = output
+        .send(ProcessMessage::Dataset {
+            data: dataset.clone(),
+        })
+        .await;
+}
+
+/// Average PSNR of the splats over all views of the scene, compared the same way as during
+/// evaluation.
+async fn average_psnr<B: Backend + brush_render::SplatForward<B>>(
+    splats: Splats<B>,
+    scene: &Scene,
+    linear_space: bool,
+    seed: u64,
+    device: &B::Device,
+) -> anyhow::Result<f32> {
+    // Use a fixed rng so different splats are compared on identical views.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut psnr = 0.0;
+    let mut count = 0;
+    for sample in brush_train::eval::eval_stats(splats, scene, None, linear_space, &mut rng, device)
+    {
+        psnr += sample?.psnr.into_scalar_async().await;
+        count += 1;
+    }
+    Ok(psnr / count.max(1) as f32)
+}
+
 pub struct RunningProcess {
     pub start_args: ProcessArgs,
     pub messages: Receiver<ProcessMessage>,
@@ -493,3 +875,60 @@ pub fn start_process(source: DataSource, args: ProcessArgs, device: WgpuDevice)
         control: train_sender,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadProgress, ProcessMessage, ProgressKind};
+    use tokio::sync::mpsc::channel;
+
+    fn download_done(message: Option<ProcessMessage>) -> Option<u64> {
+        match message {
+            Some(ProcessMessage::Progress {
+                kind: ProgressKind::Download,
+                done,
+                total,
+            }) => {
+                assert_eq!(total, Some(30), "Total should be passed on");
+                Some(done)
+            }
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn download_progress() {
+        let (output, mut receiver) = channel(1);
+        let progress = DownloadProgress::new(output);
+
+        // Only the first update fits in the channel, the rest are skipped.
+        for done in [10, 20, 30] {
+            progress.update(done, Some(30));
+        }
+        assert_eq!(
+            download_done(receiver.recv().await),
+            Some(10),
+            "First update should be sent"
+        );
+        assert!(
+            receiver.try_recv().is_err(),
+            "Busy updates should be skipped"
+        );
+
+        progress.finish().await;
+        assert_eq!(
+            download_done(receiver.recv().await),
+            Some(30),
+            "Last update should always be sent"
+        );
+
+        // Nothing is sent twice.
+        progress.update(30, Some(30));
+        progress.finish().await;
+        assert_eq!(
+            download_done(receiver.recv().await),
+            Some(30),
+            "Update should be sent"
+        );
+        assert!(receiver.try_recv().is_err(), "Sent update shouldn't repeat");
+    }
+}
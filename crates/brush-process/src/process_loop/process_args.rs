@@ -1,4 +1,6 @@
-use brush_dataset::{LoadDataseConfig, ModelConfig};
+use brush_dataset::{
+    CompressionConfig, LoadDataseConfig, ModelConfig, nerfstudio_export::DatasetImageExport,
+};
 use brush_train::train::TrainConfig;
 use burn::config::Config;
 use clap::Args;
@@ -29,7 +31,7 @@ pub struct ProcessConfig {
     #[arg(long, help_heading = "Process options")]
     pub export_path: Option<String>,
 
-    /// Filename of exported ply file
+    /// Filename of exported ply file. Use a .safetensors extension to export raw splat parameters instead.
     #[arg(
         long,
         help_heading = "Process options",
@@ -38,6 +40,38 @@ pub struct ProcessConfig {
     #[config(default = "String::from(\"./export_{iter}.ply\")")]
     pub export_name: String,
 
+    /// Exposure in stops, applied to the colors of exported ply files when training in linear space.
+    #[arg(long, help_heading = "Process options", default_value = "0.0")]
+    #[config(default = 0.0)]
+    pub export_exposure: f32,
+
+    /// Write the loaded dataset to the export path as nerfstudio transforms.json. Images can be
+    /// referenced in place, or written out as png or jpeg.
+    #[arg(long, help_heading = "Process options")]
+    pub export_dataset: Option<DatasetImageExport>,
+
+    /// Always download URL data sources, instead of using the local download cache.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    #[config(default = false)]
+    pub no_url_cache: bool,
+
+    /// Directory to cache downloads from URLs in. Defaults to the user's cache directory.
+    #[arg(long, help_heading = "Process options")]
+    pub url_cache_dir: Option<String>,
+
+    /// Maximum size of the download cache in MB. The least recently used downloads are removed
+    /// first.
+    #[arg(long, help_heading = "Process options", default_value = "20480")]
+    #[config(default = 20480)]
+    pub url_cache_mb: u64,
+
+    /// Start training as soon as the first views are loaded, and add the rest while training.
+    /// Useful for large datasets, or sources that keep adding views. Ignored when normalizing the
+    /// scene, which needs all views upfront.
+    #[arg(long, help_heading = "Process options", default_value = "false")]
+    #[config(default = false)]
+    pub train_while_loading: bool,
+
     /// Iterationto resume from
     #[config(default = 0)]
     #[arg(long, help_heading = "Process options", default_value = "0")]
@@ -75,6 +109,8 @@ pub struct ProcessArgs {
     pub process_config: ProcessConfig,
     #[clap(flatten)]
     pub rerun_config: RerunConfig,
+    #[clap(flatten)]
+    pub compression_config: CompressionConfig,
 }
 
 impl Default for ProcessArgs {
@@ -85,6 +121,7 @@ impl Default for ProcessArgs {
             load_config: LoadDataseConfig::new(),
             process_config: ProcessConfig::new(),
             rerun_config: RerunConfig::new(),
+            compression_config: CompressionConfig::new(),
         }
     }
 }
@@ -1,13 +1,17 @@
 /// A default training loop for Brush.
+use std::sync::Arc;
+
 use async_fn_stream::try_fn_stream;
 
-use brush_dataset::{Dataset, scene_loader::SceneLoader};
+use brush_dataset::{Dataset, extra_properties::ExtraProperties, scene_loader::SceneLoader};
 use brush_render::gaussian_splats::Splats;
+use brush_train::scene::{ExtentEstimate, SceneView};
 use brush_train::train::TrainBack;
 use brush_train::train::{RefineStats, SplatTrainer, TrainConfig, TrainStepStats};
 
 use burn::{module::AutodiffModule, tensor::backend::AutodiffBackend};
 use burn_wgpu::WgpuDevice;
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_stream::Stream;
 use web_time::Instant;
 
@@ -17,6 +21,8 @@ pub enum TrainMessage {
         stats: Box<TrainStepStats<TrainBack>>,
         iter: u32,
         timestamp: Instant,
+        /// Extra ply properties, kept in sync with the refined splats.
+        extra_properties: Arc<ExtraProperties>,
     },
     RefineStep {
         stats: Box<RefineStats>,
@@ -29,36 +35,56 @@ pub enum TrainMessage {
 pub(crate) fn train_stream(
     dataset: Dataset,
     initial_splats: Splats<TrainBack>,
+    extra_properties: ExtraProperties,
     config: TrainConfig,
     device: WgpuDevice,
     start_iter: u32,
+    mut new_views: UnboundedReceiver<Vec<SceneView>>,
 ) -> impl Stream<Item = anyhow::Result<TrainMessage>> {
     try_fn_stream(|emitter| async move {
         let mut splats = initial_splats;
 
-        let train_scene = dataset.train.clone();
-
-        let mut dataloader = SceneLoader::new(&train_scene, 42, &device);
+        let mut dataloader = SceneLoader::new(&dataset.train, 42, config.linear_space, &device);
 
-        let scene_extent = train_scene.estimate_extent().unwrap_or(1.0);
-        let mut trainer = SplatTrainer::new(&config, &device);
+        let mut extent = ExtentEstimate::default();
+        extent.add_views(dataset.train.views.iter());
+        let mut scene_extent = extent.extent().unwrap_or(1.0);
+        // Only track where refined splats come from when there is data to keep in sync.
+        let mut trainer =
+            SplatTrainer::new(&config, &device).with_source_tracking(!extra_properties.is_empty());
+        let mut extra_properties = Arc::new(extra_properties);
 
         let mut iter = start_iter;
 
         #[allow(clippy::infinite_loop)]
         loop {
-            let batch = dataloader.next_batch().await;
+            // Without any views the loader waits for new ones, so wait on both.
+            let batch = tokio::select! {
+                Some(views) = new_views.recv() => {
+                    // Keep the extent up to date with all views trained on so far.
+                    extent.add_views(&views);
+                    scene_extent = extent.extent().unwrap_or(1.0);
+                    dataloader.add_views(views);
+                    continue;
+                }
+                batch = dataloader.next_batch() => batch?,
+            };
 
             let (new_splats, stats) = trainer.step(scene_extent, iter, batch, splats);
-            let (new_splats, refine) = trainer.refine_if_needed(iter, new_splats).await;
+            let (new_splats, mut refine) = trainer.refine_if_needed(iter, new_splats).await?;
             splats = new_splats;
 
+            if let Some(sources) = refine.as_mut().and_then(|r| r.source_indices.take()) {
+                extra_properties = Arc::new(extra_properties.select(&sources));
+            }
+
             emitter
                 .emit(TrainMessage::TrainStep {
                     splats: Box::new(splats.valid()),
                     stats: Box::new(stats),
                     iter,
                     timestamp: Instant::now(),
+                    extra_properties: extra_properties.clone(),
                 })
                 .await;
 
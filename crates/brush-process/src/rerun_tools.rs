@@ -127,7 +127,7 @@ impl VisualizeTools {
 
                 for (i, view) in scene.views.iter().step_by(view_step).enumerate() {
                     let path = format!("world/dataset/camera/{i}");
-                    let log_img = clamp_img_to_max_size(view.image.clone(), max_img_size);
+                    let log_img = clamp_img_to_max_size(view.image.load()?, max_img_size);
 
                     let img_size = glam::uvec2(log_img.width(), log_img.height());
 
@@ -180,7 +180,7 @@ impl VisualizeTools {
                 let rendered = eval_render.to_rgb8();
 
                 let [w, h] = [rendered.width(), rendered.height()];
-                let gt_img = &view.view.image;
+                let gt_img = view.view.image.load()?;
                 let gt_rerun_img = if gt_img.color().has_alpha() {
                     rerun::Image::from_rgba32(gt_img.to_rgba8().into_vec(), [w, h])
                 } else {
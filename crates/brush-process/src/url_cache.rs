@@ -0,0 +1,308 @@
+// A persistent cache for data downloaded from URLs.
+//
+// Downloads are keyed on the URL and the ETag / Last-Modified headers, so a changed file on the
+// server is downloaded again. Interrupted downloads are kept as a partial file and resumed with
+// a Range request. When the cache grows too big, the least recently used downloads are removed.
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use reqwest::{
+    StatusCode,
+    header::{CONTENT_LENGTH, ETAG, HeaderMap, IF_RANGE, LAST_MODIFIED, RANGE},
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio_stream::StreamExt;
+
+use crate::process_loop::ProcessConfig;
+
+pub struct UrlCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+fn header_str(headers: &HeaderMap, name: impl reqwest::header::AsHeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_owned)
+}
+
+fn touch(path: &Path) -> std::io::Result<()> {
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+impl UrlCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// The cache to use for these settings, if any.
+    pub fn from_config(config: &ProcessConfig) -> Option<Self> {
+        if config.no_url_cache {
+            return None;
+        }
+        let dir = config
+            .url_cache_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| dirs::cache_dir().map(|d| d.join("brush").join("downloads")))?;
+        Some(Self::new(dir, config.url_cache_mb * 1024 * 1024))
+    }
+
+    /// Download a URL into the cache, or find it there, and return the path of the cached file.
+    /// `on_progress` is called with the number of bytes downloaded and the total size, if known.
+    ///
+    /// Returns `None` if the server doesn't send an ETag or Last-Modified header, as the download
+    /// then can't be checked for changes later.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        on_progress: impl Fn(u64, Option<u64>),
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let client = reqwest::Client::new();
+        let head = client.head(url).send().await?.error_for_status()?;
+        let etag = header_str(head.headers(), ETAG);
+        let last_modified = header_str(head.headers(), LAST_MODIFIED);
+        let Some(validator) = etag.clone().or_else(|| last_modified.clone()) else {
+            return Ok(None);
+        };
+        let total_len: Option<u64> =
+            header_str(head.headers(), CONTENT_LENGTH).and_then(|l| l.parse().ok());
+
+        let mut hasher = Sha256::new();
+        for part in [Some(url), etag.as_deref(), last_modified.as_deref()] {
+            hasher.update(part.unwrap_or_default());
+            hasher.update([0]);
+        }
+        let path = self.dir.join(format!("{:x}", hasher.finalize()));
+
+        if path.exists() {
+            log::info!("Using cached download of {url}");
+            // Mark as recently used.
+            if let Err(e) = touch(&path) {
+                log::warn!("Failed to update cache entry {path:?}: {e}");
+            }
+            return Ok(Some(path));
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let partial = path.with_extension("part");
+        let have = tokio::fs::metadata(&partial)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        // The download might have been interrupted after the last bytes were written.
+        if total_len.is_none_or(|len| have < len) {
+            let mut request = client.get(url);
+            if have > 0 {
+                // If-Range makes the server send everything if the file changed in the meantime.
+                request = request
+                    .header(RANGE, format!("bytes={have}-"))
+                    .header(IF_RANGE, validator);
+            }
+            let response = request.send().await?.error_for_status()?;
+
+            let (mut file, mut done) = if response.status() == StatusCode::PARTIAL_CONTENT {
+                log::info!("Resuming download of {url} from {have} bytes");
+                let file = tokio::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&partial)
+                    .await?;
+                (file, have)
+            } else {
+                (tokio::fs::File::create(&partial).await?, 0)
+            };
+
+            on_progress(done, total_len);
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                file.write_all(&chunk).await?;
+                done += chunk.len() as u64;
+                on_progress(done, total_len);
+            }
+            file.flush().await?;
+        }
+
+        let len = tokio::fs::metadata(&partial).await?.len();
+        if let Some(total_len) = total_len {
+            anyhow::ensure!(
+                len == total_len,
+                "Incomplete download of {url}: got {len} of {total_len} bytes"
+            );
+        }
+        tokio::fs::rename(&partial, &path)
+            .await
+            .with_context(|| format!("Failed to move download to {path:?}"))?;
+
+        if let Err(e) = self.evict(&path) {
+            log::warn!("Failed to clean up download cache: {e}");
+        }
+        Ok(Some(path))
+    }
+
+    /// Remove the least recently used files until the cache fits in its size limit.
+    fn evict(&self, keep: &Path) -> std::io::Result<()> {
+        let mut entries = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                entries.push((entry.path(), meta.len(), meta.modified()?));
+            }
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path != keep {
+                log::info!("Removing {path:?} from download cache");
+                std::fs::remove_file(&path)?;
+                total -= len;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlCache;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// Serve `CONTENT` over HTTP, with Range support. Returns the URL, and the method and range
+    /// start of each request.
+    async fn serve() -> (String, Arc<Mutex<Vec<(String, Option<usize>)>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let url = format!(
+            "http://{}/data.zip",
+            listener.local_addr().expect("No addr")
+        );
+        let requests = Arc::new(Mutex::new(vec![]));
+        let log = requests.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let Ok(read) = socket.read(&mut buf).await else {
+                        break;
+                    };
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                let method = request
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned();
+                let range = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                log.lock().expect("Poisoned").push((method.clone(), range));
+
+                let (status, body) = match range {
+                    Some(start) => ("206 Partial Content", &CONTENT[start..]),
+                    None => ("200 OK", CONTENT),
+                };
+                let header = format!(
+                    "HTTP/1.1 {status}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(header.as_bytes()).await;
+                if method != "head" {
+                    let _ = socket.write_all(body).await;
+                }
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn caches_and_resumes_downloads() {
+        let (url, requests) = serve().await;
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let cache = UrlCache::new(dir.path().to_path_buf(), u64::MAX);
+
+        // Pretend an earlier download was interrupted halfway.
+        let first = cache
+            .fetch(&url, |_, _| {})
+            .await
+            .expect("Failed to download")
+            .expect("Should be cacheable");
+        std::fs::remove_file(&first).expect("Failed to remove");
+        std::fs::write(first.with_extension("part"), &CONTENT[..10]).expect("Failed to write");
+        requests.lock().expect("Poisoned").clear();
+
+        let path = cache
+            .fetch(&url, |_, _| {})
+            .await
+            .expect("Failed to resume")
+            .expect("Should be cacheable");
+        assert_eq!(path, first, "Cache key should be stable");
+        assert_eq!(
+            std::fs::read(&path).expect("Missing download"),
+            CONTENT,
+            "Resumed download should be complete"
+        );
+        assert!(
+            requests
+                .lock()
+                .expect("Poisoned")
+                .contains(&("get".to_owned(), Some(10))),
+            "Download should resume with a range request"
+        );
+
+        // A cache hit doesn't download anything.
+        requests.lock().expect("Poisoned").clear();
+        cache.fetch(&url, |_, _| {}).await.expect("Failed to fetch");
+        assert!(
+            requests
+                .lock()
+                .expect("Poisoned")
+                .iter()
+                .all(|(method, _)| method == "head"),
+            "Cached file shouldn't be downloaded again"
+        );
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let (url, _) = serve().await;
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let old = dir.path().join("old");
+        std::fs::write(&old, CONTENT).expect("Failed to write");
+
+        let cache = UrlCache::new(dir.path().to_path_buf(), CONTENT.len() as u64);
+        let path = cache
+            .fetch(&url, |_, _| {})
+            .await
+            .expect("Failed to download")
+            .expect("Should be cacheable");
+        assert!(path.exists(), "New download should be kept");
+        assert!(!old.exists(), "Old download should be evicted");
+    }
+}
@@ -1,4 +1,4 @@
-use glam::Vec3;
+use glam::{DQuat, DVec3, Quat, Vec3};
 
 use crate::shaders;
 
@@ -30,3 +30,195 @@ pub fn rgb_to_sh(rgb: Vec3) -> Vec3 {
         channel_to_sh(rgb.z),
     )
 }
+
+/// Evaluate the real SH basis functions of a single band, in the order the renderer uses.
+fn sh_band_basis(band: u32, dir: DVec3) -> Vec<f64> {
+    let DVec3 { x, y, z } = dir;
+    let z2 = z * z;
+
+    if band == 0 {
+        return vec![0.28209479177387814];
+    }
+    if band == 1 {
+        let f = 0.48860251190292;
+        return vec![-f * y, f * z, -f * x];
+    }
+
+    let c1 = x * x - y * y;
+    let s1 = 2.0 * x * y;
+    let sh6 = 0.9461746957575601 * z2 - 0.3153915652525201;
+
+    if band == 2 {
+        let tmp0 = -1.092548430592079 * z;
+        let tmp1 = 0.5462742152960395;
+        return vec![tmp1 * s1, tmp0 * y, sh6, tmp0 * x, tmp1 * c1];
+    }
+
+    let c2 = x * c1 - y * s1;
+    let s2 = x * s1 + y * c1;
+    let tmp0 = -2.285228997322329 * z2 + 0.4570457994644658;
+    let tmp1 = 1.445305721320277 * z;
+    let tmp2 = -0.5900435899266435;
+    let sh12 = z * (1.865881662950577 * z2 - 1.119528997770346);
+
+    if band == 3 {
+        return vec![
+            tmp2 * s2,
+            tmp1 * s1,
+            tmp0 * y,
+            sh12,
+            tmp0 * x,
+            tmp1 * c1,
+            tmp2 * c2,
+        ];
+    }
+
+    assert_eq!(band, 4, "Only SH bands up to 4 are supported");
+
+    let c3 = x * c2 - y * s2;
+    let s3 = x * s2 + y * c2;
+    let tmp0 = z * (-4.683325804901025 * z2 + 2.007139630671868);
+    let tmp1 = 3.31161143515146 * z2 - 0.47308734787878;
+    let tmp2 = -1.770130769779931 * z;
+    let tmp3 = 0.6258357354491763;
+    let sh20 = 1.984313483298443 * z * sh12 - 1.006230589874905 * sh6;
+
+    vec![
+        tmp3 * s3,
+        tmp2 * s2,
+        tmp1 * s1,
+        tmp0 * y,
+        sh20,
+        tmp0 * x,
+        tmp1 * c1,
+        tmp2 * c2,
+        tmp3 * c3,
+    ]
+}
+
+/// Solve `a * x = b` for square `a` with gaussian elimination. Both are row major.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let n = a.len();
+
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap_or(col);
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let f = a[row][col] / a[col][col];
+            for k in 0..n {
+                a[row][k] -= f * a[col][k];
+                b[row][k] -= f * b[col][k];
+            }
+        }
+    }
+
+    for (row, b_row) in b.iter_mut().enumerate() {
+        for v in b_row.iter_mut() {
+            *v /= a[row][row];
+        }
+    }
+    b
+}
+
+/// Matrix that rotates the SH coefficients of a band, such that the rotated coefficients
+/// evaluated in a rotated direction give the same value as the original coefficients.
+///
+/// Returned row major, such that `rotated[i] = sum_j m[i][j] * coeffs[j]`.
+fn sh_band_rotation(band: u32, rotation: DQuat) -> Vec<Vec<f64>> {
+    // Each band is closed under rotation, so a least squares fit of
+    // f'(d) = f(R^-1 d) over enough directions gives the exact rotation.
+    const SAMPLES: usize = 64;
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    let inv_rot = rotation.inverse();
+
+    let (basis, rotated_basis): (Vec<_>, Vec<_>) = (0..SAMPLES)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f64 + 0.5) / SAMPLES as f64;
+            let r = (1.0 - z * z).sqrt();
+            let phi = i as f64 * golden_angle;
+            let dir = DVec3::new(r * phi.cos(), r * phi.sin(), z);
+            (sh_band_basis(band, dir), sh_band_basis(band, inv_rot * dir))
+        })
+        .unzip();
+
+    let n = basis[0].len();
+    // Normal equations: (A^T A) M = A^T B.
+    let normal = |rhs: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| (0..SAMPLES).map(|s| basis[s][i] * rhs[s][j]).sum())
+                    .collect()
+            })
+            .collect()
+    };
+    solve_linear(normal(&basis), normal(&rotated_basis))
+}
+
+/// Matrix that rotates all SH coefficients up to `degree` by `rotation`.
+///
+/// The matrix is `[coeffs, coeffs]` row major, with coeffs the nr. of coefficients for `degree`.
+pub fn sh_rotation_matrix(degree: u32, rotation: Quat) -> Vec<f32> {
+    let n_coeffs = sh_coeffs_for_degree(degree) as usize;
+    let rotation = rotation.as_dquat().normalize();
+    let mut matrix = vec![0.0; n_coeffs * n_coeffs];
+
+    for band in 0..=degree {
+        let offset = sh_coeffs_for_degree(band) as usize - (2 * band as usize + 1);
+        for (i, row) in sh_band_rotation(band, rotation).iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                matrix[(offset + i) * n_coeffs + offset + j] = v as f32;
+            }
+        }
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec3, Quat, Vec3};
+
+    use super::{sh_band_basis, sh_coeffs_for_degree, sh_rotation_matrix};
+
+    fn eval_sh(degree: u32, coeffs: &[f32], dir: DVec3) -> f64 {
+        (0..=degree)
+            .flat_map(|band| sh_band_basis(band, dir))
+            .zip(coeffs)
+            .map(|(b, &c)| b * c as f64)
+            .sum()
+    }
+
+    #[test]
+    fn rotated_sh_matches_rotated_direction() {
+        let degree = 4;
+        let n = sh_coeffs_for_degree(degree) as usize;
+        let rotation = Quat::from_axis_angle(Vec3::new(0.3, -1.0, 0.5).normalize(), 1.1);
+        let matrix = sh_rotation_matrix(degree, rotation);
+
+        let coeffs: Vec<f32> = (0..n).map(|i| ((i * 7 % 5) as f32 - 2.0) * 0.3).collect();
+        let rotated: Vec<f32> = (0..n)
+            .map(|i| (0..n).map(|j| matrix[i * n + j] * coeffs[j]).sum())
+            .collect();
+
+        for dir in [
+            DVec3::X,
+            DVec3::new(0.2, -0.7, 0.4),
+            DVec3::new(-1.0, 0.5, -0.3),
+        ] {
+            let dir = dir.normalize();
+            let original = eval_sh(degree, &coeffs, dir);
+            let rot = eval_sh(degree, &rotated, rotation.as_dquat() * dir);
+            assert!(
+                (original - rot).abs() < 1e-4,
+                "Rotated SH differs: {original} vs {rot}"
+            );
+        }
+    }
+}
@@ -4,7 +4,7 @@ use burn::prelude::Backend;
 use burn::tensor::Tensor;
 use rand::seq::IteratorRandom;
 
-use crate::image::view_to_sample;
+use crate::image::{linear_to_display, view_to_sample};
 use crate::scene::{Scene, SceneView};
 use crate::ssim::Ssim;
 
@@ -23,9 +23,10 @@ pub fn eval_stats<B: Backend + SplatForward<B>>(
     splats: Splats<B>,
     eval_scene: &Scene,
     num_frames: Option<usize>,
+    linear_space: bool,
     rng: &mut impl rand::Rng,
     device: &B::Device,
-) -> impl Iterator<Item = EvalSample<B>> + 'static {
+) -> impl Iterator<Item = anyhow::Result<EvalSample<B>>> + 'static {
     let indices = if let Some(num) = num_frames {
         (0..eval_scene.views.len()).choose_multiple(rng, num)
     } else {
@@ -41,12 +42,22 @@ pub fn eval_stats<B: Backend + SplatForward<B>>(
         // Compare MSE in RGB only, not sure if this should include alpha.
         let res = glam::uvec2(view.image.width(), view.image.height());
 
-        let gt_tensor = view_to_sample::<B>(&view, &device);
+        let gt_tensor = view_to_sample::<B>(&view, linear_space, &device)?;
         let gt_rgb = gt_tensor.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
 
         let (rendered, aux) = splats.render(&view.camera, res, true);
         let render_rgb = rendered.slice([0..res.y as usize, 0..res.x as usize, 0..3]);
 
+        // Compare linear renders as they'd be displayed, otherwise highlights dominate the error.
+        let (gt_rgb, render_rgb) = if linear_space {
+            (
+                linear_to_display(gt_rgb, 0.0),
+                linear_to_display(render_rgb, 0.0),
+            )
+        } else {
+            (gt_rgb, render_rgb)
+        };
+
         // Simulate 8-bit roundtrip for fair comparison.
         let render_rgb = (render_rgb * 255.0).round() / 255.0;
 
@@ -59,13 +70,13 @@ pub fn eval_stats<B: Backend + SplatForward<B>>(
         let ssim_measure = Ssim::new(11, 3, &device);
         let ssim = ssim_measure.ssim(render_rgb.clone(), gt_rgb).mean();
 
-        EvalSample {
+        Ok(EvalSample {
             index,
             view,
             psnr,
             ssim,
             rendered: render_rgb,
             aux,
-        }
+        })
     })
 }
@@ -1,3 +1,4 @@
+use brush_render::{gaussian_splats::Splats, shaders::project_visible::SH_C0};
 use burn::{
     prelude::Backend,
     tensor::{DType, Tensor, TensorData},
@@ -6,18 +7,62 @@ use image::{DynamicImage, Rgb32FImage, Rgba32FImage};
 
 use crate::scene::{SceneView, ViewImageType};
 
+/// Linear value where highlights start to be compressed by [`linear_to_display`].
+const HIGHLIGHT_KNEE: f32 = 0.8;
+
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Float images (eg. EXR, HDR) are stored as linear radiance, everything else is sRGB encoded.
+fn is_linear_image(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
 // Converts an image to a train sample. The tensor will be a floating point image with a [0, 1] image.
+// When training in linear space, colors are linear and can exceed 1 for HDR images.
 //
 // This assume the input image has un-premultiplied alpha, whereas the output has pre-multiplied alpha.
-pub fn view_to_sample<B: Backend>(view: &SceneView, device: &B::Device) -> Tensor<B, 3> {
-    let image = &view.image;
+pub fn view_to_sample<B: Backend>(
+    view: &SceneView,
+    linear_space: bool,
+    device: &B::Device,
+) -> anyhow::Result<Tensor<B, 3>> {
+    let image = view.image.load()?;
     let (w, h) = (image.width(), image.height());
 
+    let convert: Option<fn(f32) -> f32> = match (is_linear_image(&image), linear_space) {
+        (true, false) => Some(linear_to_srgb),
+        (false, true) => Some(srgb_to_linear),
+        _ => None,
+    };
+
     let tensor_data = if image.color().has_alpha() {
         // Assume image has un-multiplied alpha and convert it to pre-multiplied.
         let mut rgba = image.to_rgba32f();
-        if view.img_type == ViewImageType::Alpha {
-            for pixel in rgba.pixels_mut() {
+        for pixel in rgba.pixels_mut() {
+            if let Some(convert) = convert {
+                for c in &mut pixel.0[..3] {
+                    *c = convert(*c);
+                }
+            }
+            if view.img_type == ViewImageType::Alpha {
                 let a = pixel[3];
                 pixel[0] *= a;
                 pixel[1] *= a;
@@ -26,10 +71,75 @@ pub fn view_to_sample<B: Backend>(view: &SceneView, device: &B::Device) -> Tenso
         }
         TensorData::new(rgba.into_vec(), [h as usize, w as usize, 4])
     } else {
-        TensorData::new(image.to_rgb32f().into_vec(), [h as usize, w as usize, 3])
+        let mut rgb = image.to_rgb32f();
+        if let Some(convert) = convert {
+            for c in rgb.iter_mut() {
+                *c = convert(*c);
+            }
+        }
+        TensorData::new(rgb.into_vec(), [h as usize, w as usize, 3])
+    };
+
+    Ok(Tensor::from_data(tensor_data, device))
+}
+
+/// Map linear colors to display (sRGB) colors. Colors are scaled by the exposure (in stops), and
+/// highlights are rolled off smoothly towards 1 instead of clipping.
+pub fn linear_values_to_display<B: Backend, const D: usize>(
+    values: Tensor<B, D>,
+    exposure: f32,
+) -> Tensor<B, D> {
+    let values = (values * exposure.exp2()).clamp_min(0.0);
+
+    // Identity below the knee, and an exponential shoulder above it with a matching slope.
+    let range = 1.0 - HIGHLIGHT_KNEE;
+    let shoulder = ((values.clone() - HIGHLIGHT_KNEE) / -range).exp() * -range + 1.0;
+    let above = values.clone().greater_elem(HIGHLIGHT_KNEE);
+    let mapped = values.mask_where(above, shoulder).clamp(0.0, 1.0);
+
+    let srgb = mapped.clone().powf_scalar(1.0 / 2.4) * 1.055 - 0.055;
+    let dark = mapped.clone().lower_equal_elem(0.003_130_8);
+    srgb.mask_where(dark, mapped * 12.92)
+}
+
+/// Map a linear [h, w, c] image to display colors, see [`linear_values_to_display`]. Any alpha
+/// channel is kept as is.
+pub fn linear_to_display<B: Backend>(img: Tensor<B, 3>, exposure: f32) -> Tensor<B, 3> {
+    let [h, w, c] = img.dims();
+    let rgb = linear_values_to_display(img.clone().slice([0..h, 0..w, 0..3]), exposure);
+    if c > 3 {
+        Tensor::cat(vec![rgb, img.slice([0..h, 0..w, 3..c])], 2)
+    } else {
+        rgb
+    }
+}
+
+/// Bake the display transform into the colors of splats trained in linear space, so they can be
+/// shown by viewers that expect sRGB colors. The base color is mapped exactly, view dependent
+/// colors are scaled along with it.
+pub fn bake_display_colors<B: Backend>(splats: Splats<B>, exposure: f32) -> Splats<B> {
+    let [n, n_coeffs, _] = splats.sh_coeffs.dims();
+    let sh_coeffs = splats.sh_coeffs.val();
+
+    let dc = sh_coeffs.clone().slice([0..n, 0..1, 0..3]);
+    let base = (dc * SH_C0 + 0.5).clamp_min(1e-4);
+    let display = linear_values_to_display(base.clone(), exposure);
+    let dc = (display.clone() - 0.5) / SH_C0;
+
+    let sh_coeffs = if n_coeffs > 1 {
+        let rest = sh_coeffs.slice([0..n, 1..n_coeffs, 0..3]) * (display / base);
+        Tensor::cat(vec![dc, rest], 1)
+    } else {
+        dc
     };
 
-    Tensor::from_data(tensor_data, device)
+    Splats::from_tensor_data(
+        splats.means.val(),
+        splats.rotation.val(),
+        splats.log_scales.val(),
+        sh_coeffs,
+        splats.raw_opacity.val(),
+    )
 }
 
 pub trait TensorDataToImage {
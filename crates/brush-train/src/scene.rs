@@ -1,6 +1,14 @@
 use brush_render::{bounding_box::BoundingBox, camera::Camera};
 use glam::{Affine3A, Vec3, vec3};
-use std::sync::Arc;
+use image::DynamicImage;
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ViewType {
@@ -15,12 +23,176 @@ pub enum ViewImageType {
     Masked,
 }
 
+/// Decodes the image of a view on demand.
+pub trait ImageLoader: Send + Sync {
+    fn load(&self) -> anyhow::Result<DynamicImage>;
+}
+
+/// A bounded LRU cache of decoded images, shared by all lazily loaded views of a dataset.
+pub struct ImageCache {
+    max_bytes: usize,
+    next_id: AtomicU64,
+    // Least recently used images are at the front.
+    entries: Mutex<VecDeque<(u64, Arc<DynamicImage>)>>,
+}
+
+impl ImageCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            next_id: AtomicU64::new(0),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn get_or_load(&self, id: u64, loader: &dyn ImageLoader) -> anyhow::Result<Arc<DynamicImage>> {
+        let mut entries = self.entries.lock().expect("Image cache poisoned");
+        if let Some(pos) = entries.iter().position(|(entry_id, _)| *entry_id == id) {
+            let entry = entries.remove(pos).expect("Entry must exist");
+            let image = entry.1.clone();
+            entries.push_back(entry);
+            drop(entries);
+            return Ok(image);
+        }
+        drop(entries);
+
+        // Decode without holding the lock, so other views can still be read meanwhile.
+        let image = Arc::new(loader.load()?);
+
+        let mut entries = self.entries.lock().expect("Image cache poisoned");
+        // Another thread might have loaded the same image meanwhile.
+        if !entries.iter().any(|(entry_id, _)| *entry_id == id) {
+            entries.push_back((id, image.clone()));
+        }
+        let mut total: usize = entries.iter().map(|(_, img)| img.as_bytes().len()).sum();
+        // Always keep the latest image, even if it's bigger than the whole cache.
+        while total > self.max_bytes && entries.len() > 1 {
+            if let Some((_, evicted)) = entries.pop_front() {
+                total -= evicted.as_bytes().len();
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[derive(Clone)]
+enum ImageSource {
+    Loaded(Arc<DynamicImage>),
+    Lazy {
+        id: u64,
+        loader: Arc<dyn ImageLoader>,
+        cache: Arc<ImageCache>,
+    },
+}
+
+/// The image of a view. This is either kept in memory, or decoded on demand with the size and
+/// format known up front.
+#[derive(Clone)]
+pub struct ViewImage {
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+    source: ImageSource,
+}
+
+impl Debug for ViewImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ViewImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("has_alpha", &self.has_alpha)
+            .field("lazy", &self.is_lazy())
+            .finish()
+    }
+}
+
+impl ViewImage {
+    pub fn loaded(image: Arc<DynamicImage>) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            has_alpha: image.color().has_alpha(),
+            source: ImageSource::Loaded(image),
+        }
+    }
+
+    /// An image that is decoded by `loader` when needed. The loader has to produce an image of
+    /// exactly this size and alpha.
+    pub fn lazy(
+        width: u32,
+        height: u32,
+        has_alpha: bool,
+        loader: Arc<dyn ImageLoader>,
+        cache: Arc<ImageCache>,
+    ) -> Self {
+        let id = cache.next_id.fetch_add(1, Ordering::Relaxed);
+        Self {
+            width,
+            height,
+            has_alpha,
+            source: ImageSource::Lazy { id, loader, cache },
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        matches!(self.source, ImageSource::Lazy { .. })
+    }
+
+    /// Get the decoded image, loading it if it isn't in memory.
+    pub fn load(&self) -> anyhow::Result<Arc<DynamicImage>> {
+        match &self.source {
+            ImageSource::Loaded(image) => Ok(image.clone()),
+            ImageSource::Lazy { id, loader, cache } => {
+                let image = cache.get_or_load(*id, loader.as_ref())?;
+                anyhow::ensure!(
+                    image.width() == self.width && image.height() == self.height,
+                    "Lazily loaded image is {}x{}, expected {}x{}",
+                    image.width(),
+                    image.height(),
+                    self.width,
+                    self.height
+                );
+                Ok(image)
+            }
+        }
+    }
+}
+
+/// Optional information about how a view was captured, when the dataset or image has it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewMetadata {
+    /// Capture time in seconds. Only meaningful relative to other views of the same dataset.
+    pub timestamp: Option<f64>,
+    /// The camera or rig that captured the view. Views with the same id share their intrinsics.
+    pub camera_id: Option<u32>,
+    /// Exposure time in seconds.
+    pub exposure_time: Option<f32>,
+    pub iso: Option<u32>,
+    /// Aperture as f-number.
+    pub f_number: Option<f32>,
+    /// Size of the image file, before it was resized to the max resolution.
+    pub source_size: Option<glam::UVec2>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SceneView {
     pub path: String,
     pub camera: Camera,
-    pub image: Arc<image::DynamicImage>,
+    pub image: ViewImage,
     pub img_type: ViewImageType,
+    pub metadata: ViewMetadata,
 }
 
 // Encapsulates a multi-view scene including cameras and the splats.
@@ -49,6 +221,45 @@ fn find_two_smallest(v: Vec3) -> (f32, f32) {
     (arr[0], arr[1])
 }
 
+/// Estimate of the scene extent, updated as views are added without keeping the views around.
+/// Gives the same result as [`Scene::estimate_extent`] on a scene of all added views.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtentEstimate {
+    min: Vec3,
+    max: Vec3,
+    num_views: usize,
+}
+
+impl Default for ExtentEstimate {
+    fn default() -> Self {
+        Self {
+            min: Vec3::splat(f32::INFINITY),
+            max: Vec3::splat(f32::NEG_INFINITY),
+            num_views: 0,
+        }
+    }
+}
+
+impl ExtentEstimate {
+    pub fn add_views<'a>(&mut self, views: impl IntoIterator<Item = &'a SceneView>) {
+        for view in views {
+            self.min = self.min.min(view.camera.position);
+            self.max = self.max.max(view.camera.position);
+            self.num_views += 1;
+        }
+    }
+
+    pub fn extent(&self) -> Option<f32> {
+        if self.num_views < 5 {
+            None
+        } else {
+            // TODO: This is really sensitive to outliers.
+            let smallest = find_two_smallest(self.max - self.min);
+            Some(smallest.0.hypot(smallest.1))
+        }
+    }
+}
+
 impl Scene {
     pub fn new(views: Vec<SceneView>) -> Self {
         Self {
@@ -91,13 +302,8 @@ impl Scene {
     }
 
     pub fn estimate_extent(&self) -> Option<f32> {
-        if self.views.len() < 5 {
-            None
-        } else {
-            // TODO: This is really sensitive to outliers.
-            let bounds = self.bounds();
-            let smallest = find_two_smallest(bounds.extent * 2.0);
-            Some(smallest.0.hypot(smallest.1))
-        }
+        let mut estimate = ExtentEstimate::default();
+        estimate.add_views(self.views.iter());
+        estimate.extent()
     }
 }
@@ -0,0 +1,43 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use image::{DynamicImage, RgbImage};
+
+use crate::scene::{ImageCache, ImageLoader, ViewImage};
+
+struct CountingLoader {
+    loads: Arc<AtomicUsize>,
+}
+
+impl ImageLoader for CountingLoader {
+    fn load(&self) -> anyhow::Result<DynamicImage> {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        Ok(RgbImage::new(4, 4).into())
+    }
+}
+
+#[test]
+fn lazy_images_are_cached_and_evicted() {
+    // Room for two 4x4 rgb images.
+    let cache = Arc::new(ImageCache::new(2 * 4 * 4 * 3));
+    let loads: Vec<_> = (0..3).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+    let images: Vec<_> = loads
+        .iter()
+        .map(|loads| {
+            let loader = CountingLoader {
+                loads: loads.clone(),
+            };
+            ViewImage::lazy(4, 4, false, Arc::new(loader), cache.clone())
+        })
+        .collect();
+
+    for index in [0, 1, 0, 2, 0, 1] {
+        images[index].load().expect("Image should load");
+    }
+
+    let counts: Vec<_> = loads.iter().map(|l| l.load(Ordering::Relaxed)).collect();
+    // Image 0 stays cached as it's used most recently, 1 is evicted by 2.
+    assert_eq!(counts, vec![1, 2, 1], "Unexpected nr. of image loads");
+}
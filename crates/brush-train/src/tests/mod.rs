@@ -1,2 +1,3 @@
+mod image_cache;
 mod reference;
 mod safetensor_utils;
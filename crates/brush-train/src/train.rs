@@ -1,6 +1,6 @@
 use std::f64::consts::SQRT_2;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use brush_render::gaussian_splats::{Splats, inverse_sigmoid};
 
 use brush_render::sh::sh_coeffs_for_degree;
@@ -129,6 +129,12 @@ pub struct TrainConfig {
     #[config(default = 10000000)]
     #[arg(long, help_heading = "Refine options", default_value = "10000000")]
     pub max_splats: u32,
+
+    /// Train on linear colors instead of sRGB. This keeps the full range of HDR (EXR, HDR) images,
+    /// exposure and tonemapping are then applied when viewing or exporting.
+    #[config(default = false)]
+    #[arg(long, help_heading = "Training options", default_value = "false")]
+    pub linear_space: bool,
 }
 
 pub type TrainBack = Autodiff<Wgpu>;
@@ -143,6 +149,11 @@ pub struct SceneBatch<B: Backend> {
 pub struct RefineStats {
     pub num_added: u32,
     pub num_pruned: u32,
+    /// For each splat after refining, the index of the splat it originated from before refining.
+    ///
+    /// This allows mapping any per-splat data that lives outside of the splats along. Only set
+    /// when the trainer tracks sources, see [`SplatTrainer::with_source_tracking`].
+    pub source_indices: Option<Vec<u32>>,
 }
 
 #[derive(Clone)]
@@ -172,6 +183,7 @@ pub struct SplatTrainer {
 
     refine_record: Option<RefineRecord<<TrainBack as AutodiffBackend>::InnerBackend>>,
     optim: Option<OptimizerType>,
+    track_sources: bool,
 }
 
 fn quaternion_vec_multiply<B: Backend>(
@@ -245,9 +257,17 @@ impl SplatTrainer {
             optim: None,
             refine_record: None,
             ssim,
+            track_sources: false,
         }
     }
 
+    /// Report where each splat came from when refining. This needs to read back indices from
+    /// the GPU, so only enable it when there is per-splat data to keep in sync.
+    pub fn with_source_tracking(mut self, track_sources: bool) -> Self {
+        self.track_sources = track_sources;
+        self
+    }
+
     pub fn step(
         &mut self,
         scene_extent: f32,
@@ -309,7 +329,7 @@ impl SplatTrainer {
             l1_rgb
         };
 
-        let loss = if batch.gt_view.image.color().has_alpha() {
+        let loss = if batch.gt_view.image.has_alpha() {
             let alpha_input = batch.gt_image.clone().slice([0..img_h, 0..img_w, 3..4]);
 
             match batch.gt_view.img_type {
@@ -477,15 +497,17 @@ impl SplatTrainer {
         &mut self,
         iter: u32,
         splats: Splats<TrainBack>,
-    ) -> (Splats<TrainBack>, Option<RefineStats>) {
+    ) -> Result<(Splats<TrainBack>, Option<RefineStats>)> {
         if iter == 0 || iter % self.config.refine_every != 0 {
-            return (splats, None);
+            return Ok((splats, None));
         }
 
         let device = splats.means.device();
         let client = WgpuRuntime::client(&device);
         client.memory_cleanup();
 
+        let start_count = splats.num_splats();
+
         // If not refining, update splat to step with gradients applied.
         // Prune dead splats. This ALWAYS happen even if we're not "refining" anymore.
         let mut record = self
@@ -503,8 +525,9 @@ impl SplatTrainer {
             .inner()
             .lower_elem(inverse_sigmoid(MIN_OPACITY));
 
-        let (mut splats, refiner, pruned_count) =
+        let (mut splats, refiner, kept_indices) =
             prune_points(splats, &mut record, refiner, alpha_mask).await;
+        let pruned_count = start_count - splats.num_splats();
 
         let mut add_indices = HashSet::new();
 
@@ -551,11 +574,12 @@ impl SplatTrainer {
             }
         }
 
+        let add_indices: Vec<i32> = add_indices.into_iter().collect();
         let refine_count = add_indices.len();
 
         if refine_count > 0 {
             let refine_inds = Tensor::from_data(
-                TensorData::new(add_indices.into_iter().collect(), [refine_count]),
+                TensorData::new(add_indices.clone(), [refine_count]),
                 &device,
             );
 
@@ -645,16 +669,48 @@ impl SplatTrainer {
 
         client.memory_cleanup();
 
-        (
+        let source_indices = if self.track_sources {
+            Some(source_indices(kept_indices, start_count, &add_indices).await?)
+        } else {
+            None
+        };
+
+        Ok((
             splats,
             Some(RefineStats {
                 num_added: refine_count as u32,
                 num_pruned: pruned_count,
+                source_indices,
             }),
-        )
+        ))
     }
 }
 
+/// For each splat after refining, the index of the splat it originated from. `kept` are the
+/// splats that survived pruning, or `None` if nothing was pruned.
+async fn source_indices<B: Backend>(
+    kept: Option<Tensor<B, 1, Int>>,
+    start_count: u32,
+    add_indices: &[i32],
+) -> Result<Vec<u32>> {
+    let kept: Vec<u32> = if let Some(kept) = kept {
+        kept.into_data_async()
+            .await
+            .convert::<i32>()
+            .to_vec::<i32>()
+            .map_err(|e| anyhow!("Failed to read kept splat indices {e:?}"))?
+            .into_iter()
+            .map(|i| i as u32)
+            .collect()
+    } else {
+        (0..start_count).collect()
+    };
+
+    // New splats are appended at the end, and originate from the splat they were sampled from.
+    let added: Vec<u32> = add_indices.iter().map(|&i| kept[i as usize]).collect();
+    Ok(kept.into_iter().chain(added).collect())
+}
+
 fn map_splats_and_opt<B: AutodiffBackend>(
     mut splats: Splats<B>,
     record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
@@ -721,12 +777,19 @@ fn map_opt<B: AutodiffBackend, const D: usize>(
 //
 // Args:
 //   mask: bool[n]. If True, prune this Gaussian.
+//
+// Returns the pruned splats, refine record, and the indices of the splats that were kept if
+// any were pruned.
 async fn prune_points<B: AutodiffBackend>(
     mut splats: Splats<B>,
     record: &mut HashMap<ParamId, AdaptorRecord<AdamScaled, B>>,
     mut refiner: RefineRecord<B::InnerBackend>,
     prune: Tensor<B::InnerBackend, 1, Bool>,
-) -> (Splats<B>, RefineRecord<B::InnerBackend>, u32) {
+) -> (
+    Splats<B>,
+    RefineRecord<B::InnerBackend>,
+    Option<Tensor<B::InnerBackend, 1, Int>>,
+) {
     assert_eq!(
         prune.dims()[0] as u32,
         splats.num_splats(),
@@ -735,14 +798,14 @@ async fn prune_points<B: AutodiffBackend>(
 
     let prune_count = prune.dims()[0];
     if prune_count == 0 {
-        return (splats, refiner, 0);
+        return (splats, refiner, None);
     }
 
     let valid_inds = prune.bool_not().argwhere_async().await;
 
     if valid_inds.dims()[0] == 0 {
         log::warn!("Trying to create empty splat!");
-        return (splats, refiner, 0);
+        return (splats, refiner, None);
     }
 
     let start_splats = splats.num_splats();
@@ -764,10 +827,11 @@ async fn prune_points<B: AutodiffBackend>(
             |x| x.select(0, valid_inds.clone()),
             |x| x.select(0, valid_inds.clone()),
         );
-        refiner = refiner.keep(valid_inds);
+        refiner = refiner.keep(valid_inds.clone());
+        return (splats, refiner, Some(valid_inds));
     }
 
-    (splats, refiner, start_splats - new_points)
+    (splats, refiner, None)
 }
 
 #[cfg(test)]
@@ -4,11 +4,14 @@ use std::collections::HashMap;
 use std::io::{self, BufRead, Read};
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncReadExt;
-use tokio::io::{AsyncBufRead, AsyncRead};
+use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+mod projection;
 
 // TODO: Really these should each hold their respective params but bit of an annoying refactor. We just need
 // basic params.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CameraModel {
     SimplePinhole,
     Pinhole,
@@ -23,6 +26,18 @@ pub enum CameraModel {
     ThinPrismFisheye,
 }
 
+/// The inner error of an [`io::Error`] when a camera uses a model this reader doesn't know.
+#[derive(Debug, Clone)]
+pub struct UnsupportedCameraModel(pub String);
+
+impl std::fmt::Display for UnsupportedCameraModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unsupported camera model {}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedCameraModel {}
+
 impl CameraModel {
     fn from_id(id: i32) -> Option<Self> {
         match id {
@@ -58,6 +73,38 @@ impl CameraModel {
         }
     }
 
+    fn id(&self) -> i32 {
+        match self {
+            Self::SimplePinhole => 0,
+            Self::Pinhole => 1,
+            Self::SimpleRadial => 2,
+            Self::Radial => 3,
+            Self::OpenCV => 4,
+            Self::OpenCvFishEye => 5,
+            Self::FullOpenCV => 6,
+            Self::Fov => 7,
+            Self::SimpleRadialFisheye => 8,
+            Self::RadialFisheye => 9,
+            Self::ThinPrismFisheye => 10,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SimplePinhole => "SIMPLE_PINHOLE",
+            Self::Pinhole => "PINHOLE",
+            Self::SimpleRadial => "SIMPLE_RADIAL",
+            Self::Radial => "RADIAL",
+            Self::OpenCV => "OPENCV",
+            Self::OpenCvFishEye => "OPENCV_FISHEYE",
+            Self::FullOpenCV => "FULL_OPENCV",
+            Self::Fov => "FOV",
+            Self::SimpleRadialFisheye => "SIMPLE_RADIAL_FISHEYE",
+            Self::RadialFisheye => "RADIAL_FISHEYE",
+            Self::ThinPrismFisheye => "THIN_PRISM_FISHEYE",
+        }
+    }
+
     fn num_params(&self) -> usize {
         match self {
             Self::SimplePinhole => 3,
@@ -178,8 +225,12 @@ async fn read_cameras_text<R: AsyncRead + Unpin>(reader: R) -> io::Result<HashMa
         }
 
         let id = parse(parts[0])?;
-        let model = CameraModel::from_name(parts[1])
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid camera model"))?;
+        let model = CameraModel::from_name(parts[1]).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                UnsupportedCameraModel(parts[1].to_owned()),
+            )
+        })?;
 
         let width = parse(parts[2])?;
         let height = parse(parts[3])?;
@@ -223,8 +274,12 @@ async fn read_cameras_binary<R: AsyncRead + Unpin>(
         let width = reader.read_u64_le().await?;
         let height = reader.read_u64_le().await?;
 
-        let model = CameraModel::from_id(model_id)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid camera model"))?;
+        let model = CameraModel::from_id(model_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                UnsupportedCameraModel(format!("id {model_id}")),
+            )
+        })?;
 
         let num_params = model.num_params();
         let mut params = Vec::with_capacity(num_params);
@@ -342,7 +397,7 @@ async fn read_images_binary<R: AsyncBufRead + Unpin>(
                 reader.read_f64_le().await? as f32,
                 reader.read_f64_le().await? as f32,
             ));
-            point3d_ids.push(reader.read_i64().await?);
+            point3d_ids.push(reader.read_i64_le().await?);
         }
 
         images.insert(
@@ -428,7 +483,7 @@ async fn read_points3d_binary<R: AsyncRead + Unpin>(
     let num_points = reader.read_u64_le().await?;
 
     for _ in 0..num_points {
-        let point3d_id = reader.read_i64().await?;
+        let point3d_id = reader.read_i64_le().await?;
         let xyz = glam::Vec3::new(
             reader.read_f64_le().await? as f32,
             reader.read_f64_le().await? as f32,
@@ -465,6 +520,194 @@ async fn read_points3d_binary<R: AsyncRead + Unpin>(
     Ok(points3d)
 }
 
+fn sorted_by_id<K: Ord + Copy, V>(map: &HashMap<K, V>) -> Vec<(K, &V)> {
+    let mut entries: Vec<_> = map.iter().map(|(k, v)| (*k, v)).collect();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+async fn write_cameras_text<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    cameras: &HashMap<i32, Camera>,
+) -> io::Result<()> {
+    let mut out = String::new();
+    out.push_str("# Camera list with one line of data per camera:\n");
+    out.push_str("#   CAMERA_ID, MODEL, WIDTH, HEIGHT, PARAMS[]\n");
+    out.push_str(&format!("# Number of cameras: {}\n", cameras.len()));
+
+    for (id, camera) in sorted_by_id(cameras) {
+        out.push_str(&format!(
+            "{id} {} {} {}",
+            camera.model.name(),
+            camera.width,
+            camera.height
+        ));
+        for param in &camera.params {
+            out.push_str(&format!(" {param}"));
+        }
+        out.push('\n');
+    }
+
+    writer.write_all(out.as_bytes()).await?;
+    writer.flush().await
+}
+
+async fn write_cameras_binary<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    cameras: &HashMap<i32, Camera>,
+) -> io::Result<()> {
+    writer.write_u64_le(cameras.len() as u64).await?;
+
+    for (id, camera) in sorted_by_id(cameras) {
+        if camera.params.len() != camera.model.num_params() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid number of camera parameters",
+            ));
+        }
+
+        writer.write_i32_le(id).await?;
+        writer.write_i32_le(camera.model.id()).await?;
+        writer.write_u64_le(camera.width).await?;
+        writer.write_u64_le(camera.height).await?;
+        for &param in &camera.params {
+            writer.write_f64_le(param).await?;
+        }
+    }
+
+    writer.flush().await
+}
+
+async fn write_images_text<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    images: &HashMap<i32, Image>,
+) -> io::Result<()> {
+    let mean_observations = if images.is_empty() {
+        0.0
+    } else {
+        images.values().map(|img| img.xys.len()).sum::<usize>() as f64 / images.len() as f64
+    };
+
+    let mut out = String::new();
+    out.push_str("# Image list with two lines of data per image:\n");
+    out.push_str("#   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n");
+    out.push_str("#   POINTS2D[] as (X, Y, POINT3D_ID)\n");
+    out.push_str(&format!(
+        "# Number of images: {}, mean observations per image: {mean_observations}\n",
+        images.len()
+    ));
+
+    for (id, image) in sorted_by_id(images) {
+        let q = image.quat;
+        let t = image.tvec;
+        out.push_str(&format!(
+            "{id} {} {} {} {} {} {} {} {} {}\n",
+            q.w, q.x, q.y, q.z, t.x, t.y, t.z, image.camera_id, image.name
+        ));
+
+        let points: Vec<String> = image
+            .xys
+            .iter()
+            .zip(&image.point3d_ids)
+            .map(|(xy, id)| format!("{} {} {id}", xy.x, xy.y))
+            .collect();
+        out.push_str(&points.join(" "));
+        out.push('\n');
+    }
+
+    writer.write_all(out.as_bytes()).await?;
+    writer.flush().await
+}
+
+async fn write_images_binary<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    images: &HashMap<i32, Image>,
+) -> io::Result<()> {
+    writer.write_u64_le(images.len() as u64).await?;
+
+    for (id, image) in sorted_by_id(images) {
+        writer.write_i32_le(id).await?;
+
+        let q = image.quat;
+        for v in [q.w, q.x, q.y, q.z] {
+            writer.write_f64_le(v as f64).await?;
+        }
+        for v in image.tvec.to_array() {
+            writer.write_f64_le(v as f64).await?;
+        }
+        writer.write_i32_le(image.camera_id).await?;
+        writer.write_all(image.name.as_bytes()).await?;
+        writer.write_u8(0).await?;
+
+        writer.write_u64_le(image.xys.len() as u64).await?;
+        for (xy, &point_id) in image.xys.iter().zip(&image.point3d_ids) {
+            writer.write_f64_le(xy.x as f64).await?;
+            writer.write_f64_le(xy.y as f64).await?;
+            writer.write_i64_le(point_id).await?;
+        }
+    }
+
+    writer.flush().await
+}
+
+async fn write_points3d_text<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    points3d: &HashMap<i64, Point3D>,
+) -> io::Result<()> {
+    let mean_track_length = if points3d.is_empty() {
+        0.0
+    } else {
+        points3d.values().map(|p| p.image_ids.len()).sum::<usize>() as f64 / points3d.len() as f64
+    };
+
+    let mut out = String::new();
+    out.push_str("# 3D point list with one line of data per point:\n");
+    out.push_str("#   POINT3D_ID, X, Y, Z, R, G, B, ERROR, TRACK[] as (IMAGE_ID, POINT2D_IDX)\n");
+    out.push_str(&format!(
+        "# Number of points: {}, mean track length: {mean_track_length}\n",
+        points3d.len()
+    ));
+
+    for (id, point) in sorted_by_id(points3d) {
+        let [r, g, b] = point.rgb;
+        out.push_str(&format!(
+            "{id} {} {} {} {r} {g} {b} {}",
+            point.xyz.x, point.xyz.y, point.xyz.z, point.error
+        ));
+        for (image_id, point2d_idx) in point.image_ids.iter().zip(&point.point2d_idxs) {
+            out.push_str(&format!(" {image_id} {point2d_idx}"));
+        }
+        out.push('\n');
+    }
+
+    writer.write_all(out.as_bytes()).await?;
+    writer.flush().await
+}
+
+async fn write_points3d_binary<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    points3d: &HashMap<i64, Point3D>,
+) -> io::Result<()> {
+    writer.write_u64_le(points3d.len() as u64).await?;
+
+    for (id, point) in sorted_by_id(points3d) {
+        writer.write_i64_le(id).await?;
+        for v in point.xyz.to_array() {
+            writer.write_f64_le(v as f64).await?;
+        }
+        writer.write_all(&point.rgb).await?;
+        writer.write_f64_le(point.error).await?;
+
+        writer.write_u64_le(point.image_ids.len() as u64).await?;
+        for (&image_id, &point2d_idx) in point.image_ids.iter().zip(&point.point2d_idxs) {
+            writer.write_i32_le(image_id).await?;
+            writer.write_i32_le(point2d_idx).await?;
+        }
+    }
+
+    writer.flush().await
+}
+
 pub async fn read_cameras<R: AsyncRead + Unpin>(
     mut reader: R,
     binary: bool,
@@ -497,3 +740,207 @@ pub async fn read_points3d<R: AsyncRead + Unpin>(
         read_points3d_text(reader).await
     }
 }
+
+pub async fn write_cameras<W: AsyncWrite + Unpin>(
+    writer: W,
+    cameras: &HashMap<i32, Camera>,
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_cameras_binary(writer, cameras).await
+    } else {
+        write_cameras_text(writer, cameras).await
+    }
+}
+
+pub async fn write_images<W: AsyncWrite + Unpin>(
+    writer: W,
+    images: &HashMap<i32, Image>,
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_images_binary(writer, images).await
+    } else {
+        write_images_text(writer, images).await
+    }
+}
+
+pub async fn write_points3d<W: AsyncWrite + Unpin>(
+    writer: W,
+    points3d: &HashMap<i64, Point3D>,
+    binary: bool,
+) -> io::Result<()> {
+    if binary {
+        write_points3d_binary(writer, points3d).await
+    } else {
+        write_points3d_text(writer, points3d).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cameras() -> HashMap<i32, Camera> {
+        [
+            Camera {
+                id: 1,
+                model: CameraModel::Pinhole,
+                width: 1920,
+                height: 1080,
+                params: vec![1500.5, 1501.25, 960.0, 540.125],
+            },
+            Camera {
+                id: 2,
+                model: CameraModel::OpenCV,
+                width: 640,
+                height: 480,
+                params: vec![500.0, 510.0, 320.5, 240.5, 0.1, -0.05, 0.001, -0.002],
+            },
+            Camera {
+                id: 7,
+                model: CameraModel::SimpleRadial,
+                width: 100,
+                height: 50,
+                params: vec![80.0, 50.0, 25.0, 0.0123],
+            },
+        ]
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect()
+    }
+
+    fn test_images() -> HashMap<i32, Image> {
+        HashMap::from([
+            (
+                1,
+                Image {
+                    tvec: glam::vec3(0.5, -1.25, 3.0),
+                    quat: glam::quat(0.1, 0.2, 0.3, 0.927_362).normalize(),
+                    camera_id: 1,
+                    name: "images/frame_0001.jpg".to_owned(),
+                    xys: vec![glam::vec2(10.5, 20.25), glam::vec2(100.0, 200.0)],
+                    point3d_ids: vec![3, -1],
+                },
+            ),
+            (
+                4,
+                Image {
+                    tvec: glam::vec3(-2.0, 0.0, 1.0e-3),
+                    quat: glam::Quat::IDENTITY,
+                    camera_id: 7,
+                    name: "b.png".to_owned(),
+                    xys: vec![],
+                    point3d_ids: vec![],
+                },
+            ),
+        ])
+    }
+
+    fn test_points() -> HashMap<i64, Point3D> {
+        HashMap::from([
+            (
+                3,
+                Point3D {
+                    xyz: glam::vec3(1.0, 2.5, -3.75),
+                    rgb: [255, 128, 0],
+                    error: 0.75,
+                    image_ids: vec![1, 4],
+                    point2d_idxs: vec![0, 12],
+                },
+            ),
+            (
+                1_000_000_000_000,
+                Point3D {
+                    xyz: glam::vec3(-0.125, 0.0, 1e6),
+                    rgb: [1, 2, 3],
+                    error: 0.0,
+                    image_ids: vec![1],
+                    point2d_idxs: vec![1],
+                },
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn cameras_round_trip() {
+        let cameras = test_cameras();
+
+        for binary in [false, true] {
+            let mut data = vec![];
+            write_cameras(&mut data, &cameras, binary)
+                .await
+                .expect("Failed to write cameras");
+            let read = read_cameras(data.as_slice(), binary)
+                .await
+                .expect("Failed to read cameras");
+
+            assert_eq!(read.len(), cameras.len(), "Camera count mismatch");
+            for (id, cam) in &cameras {
+                let other = &read[id];
+                assert_eq!(other.id, cam.id, "Camera id mismatch");
+                assert_eq!(other.model, cam.model, "Camera model mismatch");
+                assert_eq!(other.width, cam.width, "Camera width mismatch");
+                assert_eq!(other.height, cam.height, "Camera height mismatch");
+                assert_eq!(other.params, cam.params, "Camera params mismatch");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn images_round_trip() {
+        let images = test_images();
+
+        for binary in [false, true] {
+            let mut data = vec![];
+            write_images(&mut data, &images, binary)
+                .await
+                .expect("Failed to write images");
+            let read = read_images(data.as_slice(), binary)
+                .await
+                .expect("Failed to read images");
+
+            assert_eq!(read.len(), images.len(), "Image count mismatch");
+            for (id, img) in &images {
+                let other = &read[id];
+                assert_eq!(other.quat, img.quat, "Image rotation mismatch");
+                assert_eq!(other.tvec, img.tvec, "Image translation mismatch");
+                assert_eq!(other.camera_id, img.camera_id, "Image camera mismatch");
+                assert_eq!(other.name, img.name, "Image name mismatch");
+                assert_eq!(other.xys, img.xys, "Image points mismatch");
+                assert_eq!(
+                    other.point3d_ids, img.point3d_ids,
+                    "Image point ids mismatch"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn points3d_round_trip() {
+        let points = test_points();
+
+        for binary in [false, true] {
+            let mut data = vec![];
+            write_points3d(&mut data, &points, binary)
+                .await
+                .expect("Failed to write points");
+            let read = read_points3d(data.as_slice(), binary)
+                .await
+                .expect("Failed to read points");
+
+            assert_eq!(read.len(), points.len(), "Point count mismatch");
+            for (id, point) in &points {
+                let other = &read[id];
+                assert_eq!(other.xyz, point.xyz, "Point position mismatch");
+                assert_eq!(other.rgb, point.rgb, "Point color mismatch");
+                assert_eq!(other.error, point.error, "Point error mismatch");
+                assert_eq!(other.image_ids, point.image_ids, "Track images mismatch");
+                assert_eq!(
+                    other.point2d_idxs, point.point2d_idxs,
+                    "Track points mismatch"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Projection and (un)distortion for all COLMAP camera models, following the definitions in
+//! COLMAP's `models.h`.
+
+use glam::{DMat2, DVec2, DVec3};
+
+use crate::{Camera, CameraModel};
+
+impl CameraModel {
+    /// Whether the model has a single focal length for both axes.
+    fn has_single_focal(&self) -> bool {
+        matches!(
+            self,
+            Self::SimplePinhole
+                | Self::SimpleRadial
+                | Self::Radial
+                | Self::SimpleRadialFisheye
+                | Self::RadialFisheye
+        )
+    }
+
+    /// Apply the distortion of this model to normalized image coordinates, returning the
+    /// offset to add.
+    fn distortion(&self, extra: &[f64], p: DVec2) -> DVec2 {
+        let (u, v) = (p.x, p.y);
+
+        match self {
+            Self::SimplePinhole | Self::Pinhole => DVec2::ZERO,
+            Self::SimpleRadial => {
+                let radial = extra[0] * p.length_squared();
+                p * radial
+            }
+            Self::Radial => {
+                let r2 = p.length_squared();
+                let radial = extra[0] * r2 + extra[1] * r2 * r2;
+                p * radial
+            }
+            Self::OpenCV => {
+                let [k1, k2, p1, p2] = [extra[0], extra[1], extra[2], extra[3]];
+                let (u2, uv, v2) = (u * u, u * v, v * v);
+                let r2 = u2 + v2;
+                let radial = k1 * r2 + k2 * r2 * r2;
+                DVec2::new(
+                    u * radial + 2.0 * p1 * uv + p2 * (r2 + 2.0 * u2),
+                    v * radial + 2.0 * p2 * uv + p1 * (r2 + 2.0 * v2),
+                )
+            }
+            Self::FullOpenCV => {
+                let [k1, k2, p1, p2, k3, k4, k5, k6] = [
+                    extra[0], extra[1], extra[2], extra[3], extra[4], extra[5], extra[6], extra[7],
+                ];
+                let (u2, uv, v2) = (u * u, u * v, v * v);
+                let r2 = u2 + v2;
+                let r4 = r2 * r2;
+                let r6 = r4 * r2;
+                let radial =
+                    (1.0 + k1 * r2 + k2 * r4 + k3 * r6) / (1.0 + k4 * r2 + k5 * r4 + k6 * r6);
+                DVec2::new(
+                    u * radial + 2.0 * p1 * uv + p2 * (r2 + 2.0 * u2) - u,
+                    v * radial + 2.0 * p2 * uv + p1 * (r2 + 2.0 * v2) - v,
+                )
+            }
+            Self::OpenCvFishEye => fisheye(p, &[extra[0], extra[1], extra[2], extra[3]]) - p,
+            Self::SimpleRadialFisheye => fisheye(p, &[extra[0]]) - p,
+            Self::RadialFisheye => fisheye(p, &[extra[0], extra[1]]) - p,
+            Self::ThinPrismFisheye => {
+                let [k1, k2, p1, p2, k3, k4, sx1, sy1] = [
+                    extra[0], extra[1], extra[2], extra[3], extra[4], extra[5], extra[6], extra[7],
+                ];
+                let d = fisheye(p, &[k1, k2, k3, k4]);
+                let (u2, uv, v2) = (d.x * d.x, d.x * d.y, d.y * d.y);
+                let r2 = u2 + v2;
+                DVec2::new(
+                    d.x + 2.0 * p1 * uv + p2 * (r2 + 2.0 * u2) + sx1 * r2 - u,
+                    d.y + 2.0 * p2 * uv + p1 * (r2 + 2.0 * v2) + sy1 * r2 - v,
+                )
+            }
+            Self::Fov => p * fov_distort_factor(extra[0], p.length_squared()) - p,
+        }
+    }
+}
+
+/// Equidistant fisheye distortion with up to 4 polynomial coefficients.
+fn fisheye(p: DVec2, k: &[f64]) -> DVec2 {
+    let r = p.length();
+    if r <= f64::EPSILON {
+        return p;
+    }
+    let theta = r.atan();
+    let theta2 = theta * theta;
+    let mut poly = 1.0;
+    let mut theta_pow = theta2;
+    for &k in k {
+        poly += k * theta_pow;
+        theta_pow *= theta2;
+    }
+    p * (theta * poly / r)
+}
+
+/// Scale factor from undistorted to distorted coordinates of the FOV model.
+fn fov_distort_factor(omega: f64, r2: f64) -> f64 {
+    const EPS: f64 = 1e-4;
+    let omega2 = omega * omega;
+    if omega2 < EPS {
+        // Taylor expansion around omega = 0.
+        1.0 + omega2 / 12.0 - omega2 * r2 / 3.0
+    } else if r2 < EPS {
+        // Taylor expansion around r = 0.
+        let tan_half = (omega / 2.0).tan();
+        2.0 * tan_half * (3.0 - 4.0 * r2 * tan_half * tan_half) / (3.0 * omega)
+    } else {
+        let r = r2.sqrt();
+        (r * 2.0 * (omega / 2.0).tan()).atan() / (r * omega)
+    }
+}
+
+/// Scale factor from distorted to undistorted coordinates of the FOV model.
+fn fov_undistort_factor(omega: f64, r2: f64) -> f64 {
+    const EPS: f64 = 1e-4;
+    let omega2 = omega * omega;
+    if omega2 < EPS {
+        // Taylor expansion around omega = 0.
+        1.0 - omega2 / 12.0 + omega2 * r2 / 3.0
+    } else if r2 < EPS {
+        // Taylor expansion around r = 0.
+        omega * (omega2 * r2 + 3.0) / (6.0 * (omega / 2.0).tan())
+    } else {
+        let r = r2.sqrt();
+        (r * omega).tan() / (r * 2.0 * (omega / 2.0).tan())
+    }
+}
+
+impl Camera {
+    /// Principal point in full precision.
+    fn center(&self) -> DVec2 {
+        if self.model.has_single_focal() {
+            DVec2::new(self.params[1], self.params[2])
+        } else {
+            DVec2::new(self.params[2], self.params[3])
+        }
+    }
+
+    /// Distortion parameters, ie. all parameters after the focal length and principal point.
+    pub fn extra_params(&self) -> &[f64] {
+        let num_base = if self.model.has_single_focal() { 3 } else { 4 };
+        &self.params[num_base..]
+    }
+
+    /// Apply lens distortion to normalized image coordinates (x / z, y / z).
+    pub fn distort(&self, normalized: DVec2) -> DVec2 {
+        normalized + self.model.distortion(self.extra_params(), normalized)
+    }
+
+    /// Remove lens distortion from normalized image coordinates.
+    ///
+    /// Most models have no closed form inverse, so this iteratively solves for the undistorted
+    /// coordinates with Newton's method, like COLMAP does.
+    pub fn undistort(&self, distorted: DVec2) -> DVec2 {
+        const NUM_ITERATIONS: usize = 100;
+        const MAX_STEP_NORM: f64 = 1e-10;
+        const REL_STEP_SIZE: f64 = 1e-6;
+
+        let extra = self.extra_params();
+
+        match self.model {
+            CameraModel::SimplePinhole | CameraModel::Pinhole => return distorted,
+            CameraModel::Fov => {
+                return distorted * fov_undistort_factor(extra[0], distorted.length_squared());
+            }
+            _ => {}
+        }
+
+        let distortion = |p: DVec2| self.model.distortion(extra, p);
+
+        let mut x = distorted;
+        for _ in 0..NUM_ITERATIONS {
+            let step0 = f64::EPSILON.max((REL_STEP_SIZE * x.x).abs());
+            let step1 = f64::EPSILON.max((REL_STEP_SIZE * x.y).abs());
+
+            let dx = distortion(x);
+            let dx_0b = distortion(x - DVec2::new(step0, 0.0));
+            let dx_0f = distortion(x + DVec2::new(step0, 0.0));
+            let dx_1b = distortion(x - DVec2::new(0.0, step1));
+            let dx_1f = distortion(x + DVec2::new(0.0, step1));
+
+            let jacobian = DMat2::from_cols(
+                DVec2::new(1.0, 0.0) + (dx_0f - dx_0b) / (2.0 * step0),
+                DVec2::new(0.0, 1.0) + (dx_1f - dx_1b) / (2.0 * step1),
+            );
+
+            let step = jacobian.inverse() * (x + dx - distorted);
+            if !step.is_finite() {
+                break;
+            }
+            x -= step;
+
+            if step.length_squared() < MAX_STEP_NORM {
+                break;
+            }
+        }
+        x
+    }
+
+    /// Project a point in camera space (+Z forward) to pixel coordinates.
+    pub fn project(&self, point: DVec3) -> DVec2 {
+        let normalized = point.truncate() / point.z;
+        let (fx, fy) = self.focal();
+        let c = self.center();
+        self.distort(normalized) * DVec2::new(fx, fy) + c
+    }
+
+    /// Unproject pixel coordinates to a point in camera space on the z = 1 plane.
+    pub fn unproject(&self, pixel: DVec2) -> DVec3 {
+        let (fx, fy) = self.focal();
+        let c = self.center();
+        let distorted = (pixel - c) / DVec2::new(fx, fy);
+        self.undistort(distorted).extend(1.0)
+    }
+
+    /// Map a pixel in the distorted image to where it would be in an undistorted pinhole image with
+    /// the same focal length and principal point.
+    pub fn undistort_pixel(&self, pixel: DVec2) -> DVec2 {
+        let (fx, fy) = self.focal();
+        let c = self.center();
+        self.unproject(pixel).truncate() * DVec2::new(fx, fy) + c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(model: CameraModel, params: &[f64]) -> Camera {
+        Camera {
+            id: 1,
+            model,
+            width: 640,
+            height: 480,
+            params: params.to_vec(),
+        }
+    }
+
+    fn all_models() -> Vec<Camera> {
+        vec![
+            camera(CameraModel::SimplePinhole, &[500.0, 320.0, 240.0]),
+            camera(CameraModel::Pinhole, &[500.0, 510.0, 320.0, 240.0]),
+            camera(CameraModel::SimpleRadial, &[500.0, 320.0, 240.0, 0.1]),
+            camera(CameraModel::Radial, &[500.0, 320.0, 240.0, 0.1, -0.02]),
+            camera(
+                CameraModel::OpenCV,
+                &[500.0, 510.0, 320.0, 240.0, 0.1, -0.02, 0.001, -0.002],
+            ),
+            camera(
+                CameraModel::OpenCvFishEye,
+                &[500.0, 510.0, 320.0, 240.0, 0.1, -0.02, 0.003, -0.001],
+            ),
+            camera(
+                CameraModel::FullOpenCV,
+                &[
+                    500.0, 510.0, 320.0, 240.0, 0.1, -0.02, 0.001, -0.002, 0.003, 0.01, -0.004,
+                    0.001,
+                ],
+            ),
+            camera(CameraModel::Fov, &[500.0, 510.0, 320.0, 240.0, 0.9]),
+            camera(
+                CameraModel::SimpleRadialFisheye,
+                &[500.0, 320.0, 240.0, 0.1],
+            ),
+            camera(
+                CameraModel::RadialFisheye,
+                &[500.0, 320.0, 240.0, 0.1, -0.02],
+            ),
+            camera(
+                CameraModel::ThinPrismFisheye,
+                &[
+                    500.0, 510.0, 320.0, 240.0, 0.1, -0.02, 0.001, -0.002, 0.003, -0.001, 0.002,
+                    -0.003,
+                ],
+            ),
+        ]
+    }
+
+    fn assert_close(a: DVec2, b: DVec2, tol: f64, msg: &str) {
+        assert!((a - b).abs().max_element() < tol, "{msg}: {a} != {b}");
+    }
+
+    #[test]
+    fn known_projections() {
+        let point = DVec3::new(0.5, 0.25, 1.0);
+
+        // r2 = 0.3125, radial = 1 + 0.1 * r2.
+        let cam = camera(CameraModel::SimpleRadial, &[100.0, 50.0, 50.0, 0.1]);
+        assert_close(
+            cam.project(point),
+            DVec2::new(101.5625, 75.781_25),
+            1e-9,
+            "Simple radial",
+        );
+
+        // radial = 0.1 * r2 - 0.2 * r2^2 = 0.01171875
+        // du = 0.5 * radial + 2 * 0.01 * 0.125 + 0.02 * (0.3125 + 0.5) = 0.024609375
+        // dv = 0.25 * radial + 2 * 0.02 * 0.125 + 0.01 * (0.3125 + 0.125) = 0.0123046875
+        let cam = camera(
+            CameraModel::OpenCV,
+            &[100.0, 200.0, 50.0, 60.0, 0.1, -0.2, 0.01, 0.02],
+        );
+        assert_close(
+            cam.project(point),
+            DVec2::new(102.460_937_5, 112.460_937_5),
+            1e-9,
+            "OpenCV",
+        );
+
+        // Without distortion coefficients, the fisheye maps radius r to atan(r).
+        let cam = camera(
+            CameraModel::OpenCvFishEye,
+            &[100.0, 100.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        );
+        assert_close(
+            cam.project(DVec3::new(1.0, 0.0, 1.0)),
+            DVec2::new(100.0 * std::f64::consts::FRAC_PI_4, 0.0),
+            1e-9,
+            "Fisheye",
+        );
+
+        // The FOV model maps r to atan(2 r tan(w / 2)) / w.
+        let omega = 0.9f64;
+        let cam = camera(CameraModel::Fov, &[100.0, 100.0, 0.0, 0.0, omega]);
+        let expected = (2.0 * (omega / 2.0).tan()).atan() / omega;
+        assert_close(
+            cam.project(DVec3::new(1.0, 0.0, 1.0)),
+            DVec2::new(100.0 * expected, 0.0),
+            1e-9,
+            "FOV",
+        );
+    }
+
+    #[test]
+    fn project_unproject_round_trip() {
+        for cam in all_models() {
+            for pixel in [
+                DVec2::new(320.0, 240.0),
+                DVec2::new(0.0, 0.0),
+                DVec2::new(100.5, 400.25),
+                DVec2::new(639.0, 479.0),
+                DVec2::new(500.0, 20.0),
+            ] {
+                let ray = cam.unproject(pixel);
+                assert!(
+                    (ray.z - 1.0).abs() < 1e-12,
+                    "Unprojected ray should be on z = 1"
+                );
+                let reprojected = cam.project(ray * 3.0);
+                assert_close(
+                    reprojected,
+                    pixel,
+                    1e-6,
+                    &format!("{:?} round trip", cam.model),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn undistort_inverts_distort() {
+        for cam in all_models() {
+            for p in [
+                DVec2::new(0.0, 0.0),
+                DVec2::new(0.1, -0.2),
+                DVec2::new(-0.4, 0.3),
+                DVec2::new(0.5, 0.5),
+            ] {
+                let undistorted = cam.undistort(cam.distort(p));
+                assert_close(undistorted, p, 1e-8, &format!("{:?} undistort", cam.model));
+            }
+        }
+    }
+
+    #[test]
+    fn pinhole_undistort_pixel_is_identity() {
+        let cam = camera(CameraModel::Pinhole, &[500.0, 510.0, 320.0, 240.0]);
+        let pixel = DVec2::new(12.5, 300.0);
+        assert_close(cam.undistort_pixel(pixel), pixel, 1e-9, "Pinhole");
+    }
+}
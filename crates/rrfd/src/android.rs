@@ -1,6 +1,6 @@
 use anyhow::{Context, Result, anyhow};
 use jni::JNIEnv;
-use jni::objects::{GlobalRef, JClass, JStaticMethodID};
+use jni::objects::{GlobalRef, JClass, JIntArray, JObjectArray, JStaticMethodID, JString};
 use jni::signature::Primitive;
 use jni::sys::jint;
 use lazy_static::lazy_static;
@@ -13,7 +13,9 @@ use tokio::sync::mpsc::Sender;
 lazy_static! {
     static ref VM: RwLock<Option<Arc<jni::JavaVM>>> = RwLock::new(None);
     static ref CHANNEL: RwLock<Option<Sender<Option<File>>>> = RwLock::new(None);
+    static ref FILES_CHANNEL: RwLock<Option<Sender<Vec<(File, String)>>>> = RwLock::new(None);
     static ref START_FILE_PICKER: RwLock<Option<JStaticMethodID>> = RwLock::new(None);
+    static ref START_FILES_PICKER: RwLock<Option<JStaticMethodID>> = RwLock::new(None);
     static ref FILE_PICKER_CLASS: RwLock<Option<GlobalRef>> = RwLock::new(None);
 }
 
@@ -24,15 +26,50 @@ pub fn jni_initialize(vm: Arc<jni::JavaVM>) {
     let method = env
         .get_static_method_id(&class, "startFilePicker", "()V")
         .unwrap();
+    let files_method = env
+        .get_static_method_id(&class, "startFilesPicker", "()V")
+        .unwrap();
     *FILE_PICKER_CLASS
         .write()
         .expect("Failed to write JNI data.") = Some(env.new_global_ref(class).unwrap());
     *START_FILE_PICKER
         .write()
         .expect("Failed to write JNI data.") = Some(method);
+    *START_FILES_PICKER
+        .write()
+        .expect("Failed to write JNI data.") = Some(files_method);
     *VM.write().unwrap() = Some(vm);
 }
 
+/// Call one of the static methods of the Java `FilePicker` which start a picker.
+fn start_picker(method: &RwLock<Option<JStaticMethodID>>) -> Result<()> {
+    let java_vm = VM
+        .read()
+        .unwrap()
+        .clone()
+        .expect("Failed to initialize Java VM");
+    let mut env = java_vm.attach_current_thread()?;
+
+    let class = FILE_PICKER_CLASS
+        .read()
+        .expect("Failed to initialize FilePicker class");
+    let method = method
+        .read()
+        .expect("Failed to initialize FilePicker method");
+
+    // SAFETY: This is safe as long as we cached the method in the right way, and
+    // this matches the Java side. Not much more we can do here.
+    let _ = unsafe {
+        env.call_static_method_unchecked(
+            class.as_ref().expect("Failed to get class reference"),
+            method.as_ref().expect("Failed to get method reference"),
+            jni::signature::ReturnType::Primitive(Primitive::Void),
+            &[],
+        )
+    }?;
+    Ok(())
+}
+
 #[allow(unused)]
 pub(crate) async fn pick_file() -> Result<File> {
     let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
@@ -45,33 +82,8 @@ pub(crate) async fn pick_file() -> Result<File> {
         }
     }
 
-    // Call method. Be sure this is scoped so we drop all guards before waiting.
-    {
-        let java_vm = VM
-            .read()
-            .unwrap()
-            .clone()
-            .expect("Failed to initialize Java VM");
-        let mut env = java_vm.attach_current_thread()?;
-
-        let class = FILE_PICKER_CLASS
-            .read()
-            .expect("Failed to initialize FilePicker class");
-        let method = START_FILE_PICKER
-            .read()
-            .expect("Failed to initialize FilePicker method");
-
-        // SAFETY: This is safe as long as we cached the method in the right way, and
-        // this matches the Java side. Not much more we can do here.
-        let _ = unsafe {
-            env.call_static_method_unchecked(
-                class.as_ref().expect("Failed to get class reference"),
-                method.as_ref().expect("Failed to get method reference"),
-                jni::signature::ReturnType::Primitive(Primitive::Void),
-                &[],
-            )
-        }?;
-    }
+    // All JNI guards are dropped once the picker is started, before waiting.
+    start_picker(&START_FILE_PICKER)?;
 
     let file = receiver
         .recv()
@@ -82,6 +94,29 @@ pub(crate) async fn pick_file() -> Result<File> {
     file.context("No file selected")
 }
 
+/// Pick any number of files, returning the files with their display names.
+#[allow(unused)]
+pub(crate) async fn pick_files() -> Result<Vec<(File, String)>> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    {
+        let channel = FILES_CHANNEL.write();
+        if let Ok(mut channel) = channel {
+            *channel = Some(sender);
+        } else {
+            anyhow::bail!("Failed to initialize file picker");
+        }
+    }
+
+    start_picker(&START_FILES_PICKER)?;
+
+    let files = receiver
+        .recv()
+        .await
+        .ok_or(anyhow!("Failed to receive anything"))?;
+    anyhow::ensure!(!files.is_empty(), "No files selected");
+    Ok(files)
+}
+
 #[unsafe(no_mangle)]
 extern "system" fn Java_com_splats_app_FilePicker_onFilePickerResult<'local>(
     _env: JNIEnv<'local>,
@@ -105,3 +140,41 @@ extern "system" fn Java_com_splats_app_FilePicker_onFilePickerResult<'local>(
         }
     }
 }
+
+fn read_picked_files(
+    env: &mut JNIEnv,
+    fds: &JIntArray,
+    names: &JObjectArray,
+) -> jni::errors::Result<Vec<(File, String)>> {
+    let count = env.get_array_length(fds)?;
+    let mut raw_fds = vec![0; count as usize];
+    env.get_int_array_region(fds, 0, &mut raw_fds)?;
+
+    let mut files = vec![];
+    for (i, fd) in raw_fds.into_iter().enumerate() {
+        let name = JString::from(env.get_object_array_element(names, i as i32)?);
+        let name: String = env.get_string(&name)?.into();
+        if fd >= 0 {
+            // SAFETY: As for a single file, JNI passes us open file descriptors we now own.
+            files.push((unsafe { File::from_raw_fd(fd) }, name));
+        }
+    }
+    Ok(files)
+}
+
+#[unsafe(no_mangle)]
+extern "system" fn Java_com_splats_app_FilePicker_onFilesPickerResult<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    fds: JIntArray<'local>,
+    names: JObjectArray<'local>,
+) {
+    let files = read_picked_files(&mut env, &fds, &names).unwrap_or_default();
+
+    if let Ok(ch) = FILES_CHANNEL.read() {
+        if let Some(ch) = ch.as_ref() {
+            ch.try_send(files)
+                .expect("Failed to send file picking result");
+        }
+    }
+}
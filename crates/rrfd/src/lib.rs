@@ -10,16 +10,29 @@ pub enum FileHandle {
     #[cfg(not(target_os = "android"))]
     Rfd(rfd::FileHandle),
     #[cfg(target_os = "android")]
-    Android(tokio::fs::File),
+    Android {
+        file: tokio::fs::File,
+        name: Option<String>,
+    },
 }
 
 impl FileHandle {
+    /// The name of the picked file, if known.
+    pub fn file_name(&self) -> Option<String> {
+        match self {
+            #[cfg(not(target_os = "android"))]
+            Self::Rfd(file_handle) => Some(file_handle.file_name()),
+            #[cfg(target_os = "android")]
+            Self::Android { name, .. } => name.clone(),
+        }
+    }
+
     pub async fn write(&self, data: &[u8]) -> std::io::Result<()> {
         match self {
             #[cfg(not(target_os = "android"))]
             Self::Rfd(file_handle) => file_handle.write(data).await,
             #[cfg(target_os = "android")]
-            Self::Android(_) => {
+            Self::Android { .. } => {
                 let _ = data;
                 unimplemented!("No saving on Android yet.")
             }
@@ -31,7 +44,7 @@ impl FileHandle {
             #[cfg(not(target_os = "android"))]
             Self::Rfd(file_handle) => file_handle.read().await,
             #[cfg(target_os = "android")]
-            Self::Android(file) => {
+            Self::Android { file, .. } => {
                 use tokio::io::AsyncReadExt;
 
                 let mut buf = vec![];
@@ -55,7 +68,34 @@ pub async fn pick_file() -> Result<FileHandle> {
 
     #[cfg(target_os = "android")]
     {
-        android::pick_file().await.map(FileHandle::Android)
+        android::pick_file()
+            .await
+            .map(|file| FileHandle::Android { file, name: None })
+    }
+}
+
+/// Pick one or more files.
+pub async fn pick_files() -> Result<Vec<FileHandle>> {
+    #[cfg(not(target_os = "android"))]
+    {
+        let files = rfd::AsyncFileDialog::new()
+            .pick_files()
+            .await
+            .filter(|files| !files.is_empty())
+            .context("No files selected")?;
+        Ok(files.into_iter().map(FileHandle::Rfd).collect())
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        let files = android::pick_files().await?;
+        Ok(files
+            .into_iter()
+            .map(|(file, name)| FileHandle::Android {
+                file,
+                name: Some(name),
+            })
+            .collect())
     }
 }
 
@@ -10,7 +10,7 @@ use brush_render::{
 };
 use brush_train::{
     image::view_to_sample,
-    scene::{SceneView, ViewImageType},
+    scene::{SceneView, ViewImage, ViewImageType},
     train::{SceneBatch, SplatTrainer, TrainBack, TrainConfig},
 };
 use brush_ui::burn_texture::BurnTexture;
@@ -55,7 +55,9 @@ fn spawn_train_loop(
 
         // One batch of training data, it's the same every step so can just cosntruct it once.
         let batch = SceneBatch {
-            gt_image: view_to_sample(&gt_view, &device).unsqueeze(),
+            gt_image: view_to_sample(&gt_view, false, &device)
+                .expect("Failed to load image")
+                .unsqueeze(),
             gt_view,
         };
 
@@ -63,7 +65,10 @@ fn spawn_train_loop(
 
         loop {
             let (new_splats, _) = trainer.step(1.0, iter, batch.clone(), splats);
-            let (new_splats, _) = trainer.refine_if_needed(iter, new_splats).await;
+            let (new_splats, _) = trainer
+                .refine_if_needed(iter, new_splats)
+                .await
+                .expect("Failed to refine splats");
 
             splats = new_splats;
             iter += 1;
@@ -121,15 +126,17 @@ impl App {
         let view = SceneView {
             path: "crabby".to_owned(),
             camera,
-            image: Arc::new(image),
+            image: ViewImage::loaded(Arc::new(image)),
             img_type: ViewImageType::Alpha,
+            metadata: Default::default(),
         };
 
         let (sender, receiver) = tokio::sync::mpsc::channel(32);
 
+        let image = view.image.load().expect("Failed to load image");
         let color_img = egui::ColorImage::from_rgb(
-            [view.image.width() as usize, view.image.height() as usize],
-            &view.image.to_rgb8().into_vec(),
+            [image.width() as usize, image.height() as usize],
+            &image.to_rgb8().into_vec(),
         );
         let handle =
             cc.egui_ctx